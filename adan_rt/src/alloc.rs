@@ -0,0 +1,109 @@
+use std::alloc::{self, Layout};
+use std::ptr;
+
+// Blocks handed out to compiled code carry their size in a header in front of them, so
+// `adan_free` only needs the pointer, like free(3).
+const HEADER: usize = 16;
+
+fn layout(size: usize) -> Layout {
+    Layout::from_size_align(size + HEADER, HEADER).expect("allocation too large")
+}
+
+// Zeroed block of `size` bytes, aligned for any ADAN value.
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_alloc(size: i64) -> *mut u8 {
+    let size = size.max(0) as usize;
+    let layout = layout(size);
+    let block = unsafe { alloc::alloc_zeroed(layout) };
+    if block.is_null() {
+        alloc::handle_alloc_error(layout);
+    }
+
+    unsafe {
+        (block as *mut usize).write(size);
+        block.add(HEADER)
+    }
+}
+
+// Grows or shrinks a block from `adan_alloc`, keeping its contents. A null block is a fresh allocation.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_realloc(block: *mut u8, size: i64) -> *mut u8 {
+    if block.is_null() {
+        return adan_alloc(size);
+    }
+
+    let size = size.max(0) as usize;
+    unsafe {
+        let start = block.sub(HEADER);
+        let old_size = (start as *const usize).read();
+        let grown = alloc::realloc(start, layout(old_size), size + HEADER);
+        if grown.is_null() {
+            alloc::handle_alloc_error(layout(size));
+        }
+        if size > old_size {
+            ptr::write_bytes(grown.add(HEADER + old_size), 0, size - old_size);
+        }
+
+        (grown as *mut usize).write(size);
+        grown.add(HEADER)
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_free(block: *mut u8) {
+    if block.is_null() {
+        return;
+    }
+
+    unsafe {
+        let start = block.sub(HEADER);
+        let size = (start as *const usize).read();
+        alloc::dealloc(start, layout(size));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_start_zeroed_and_aligned() {
+        let block = adan_alloc(40);
+        assert_eq!(block as usize % HEADER, 0);
+        assert!(unsafe { std::slice::from_raw_parts(block, 40) }.iter().all(|b| *b == 0));
+        unsafe { adan_free(block) };
+    }
+
+    #[test]
+    fn realloc_keeps_contents_and_zeroes_the_growth() {
+        let block = adan_alloc(4);
+        unsafe {
+            ptr::copy_nonoverlapping([1u8, 2, 3, 4].as_ptr(), block, 4);
+            let grown = adan_realloc(block, 64);
+            let bytes = std::slice::from_raw_parts(grown, 64);
+            assert_eq!(bytes[..4], [1, 2, 3, 4]);
+            assert!(bytes[4..].iter().all(|b| *b == 0));
+
+            let shrunk = adan_realloc(grown, 2);
+            assert_eq!(std::slice::from_raw_parts(shrunk, 2), [1, 2]);
+            adan_free(shrunk);
+        }
+    }
+
+    #[test]
+    fn null_is_a_fresh_block_and_free_ignores_it() {
+        let block = unsafe { adan_realloc(ptr::null_mut(), 8) };
+        assert!(!block.is_null());
+        unsafe {
+            adan_free(block);
+            adan_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn negative_sizes_are_empty_blocks() {
+        let block = adan_alloc(-5);
+        assert!(!block.is_null());
+        unsafe { adan_free(block) };
+    }
+}
@@ -0,0 +1,40 @@
+// Chars are single bytes, classified as ASCII so bytes of multi-byte UTF-8 text are never letters.
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_char_is_digit(c: u8) -> bool {
+    c.is_ascii_digit()
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_char_is_alpha(c: u8) -> bool {
+    c.is_ascii_alphabetic()
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_char_is_alnum(c: u8) -> bool {
+    c.is_ascii_alphanumeric()
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_char_is_space(c: u8) -> bool {
+    c.is_ascii_whitespace()
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_char_is_upper(c: u8) -> bool {
+    c.is_ascii_uppercase()
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_char_is_lower(c: u8) -> bool {
+    c.is_ascii_lowercase()
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_char_to_upper(c: u8) -> u8 {
+    c.to_ascii_uppercase()
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_char_to_lower(c: u8) -> u8 {
+    c.to_ascii_lowercase()
+}
@@ -0,0 +1,106 @@
+use std::env;
+use std::ffi::{c_char, OsStr};
+use std::ptr;
+
+use crate::map::{adan_map_insert, adan_map_new, AdanMap};
+use crate::string::{adan_str_free, bytes, from_bytes, from_string, text};
+
+// Variable names and values are bytes on Unix, they're handed over as they are.
+fn name<'a>(s: *const c_char) -> &'a OsStr {
+    unsafe { OsStr::from_encoded_bytes_unchecked(bytes(s)) }
+}
+
+// The value of the variable `name`, "" when it isn't set.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_env_get(name_str: *const c_char) -> *mut c_char {
+    let value = env::var_os(name(name_str)).unwrap_or_default();
+    from_bytes(value.as_encoded_bytes())
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_env_has(name_str: *const c_char) -> bool {
+    env::var_os(name(name_str)).is_some()
+}
+
+// Null when it worked, otherwise why the name or value can't be used.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_env_set(name_str: *const c_char, value: *const c_char) -> *mut c_char {
+    let (key, value) = unsafe { (bytes(name_str), bytes(value)) };
+    if key.is_empty() || key.contains(&b'=') || key.contains(&0) {
+        return from_string(format!("env.set: \"{}\" is not a valid variable name", text(name_str)));
+    }
+    if value.contains(&0) {
+        return from_string(format!("env.set: the value of {} contains a NUL character", text(name_str)));
+    }
+
+    // Programs are single threaded, nothing else reads the environment while it changes.
+    unsafe { env::set_var(name(name_str), OsStr::from_encoded_bytes_unchecked(value)) };
+    ptr::null_mut()
+}
+
+// Every variable in a new Map<String, String>, sorted by name.
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_env_vars() -> *mut AdanMap {
+    let mut vars: Vec<_> = env::vars_os().collect();
+    vars.sort();
+
+    let handle_size = size_of::<*mut c_char>() as i64;
+    let map = adan_map_new(handle_size, handle_size, 1);
+    for (key, value) in vars {
+        let key = from_bytes(key.as_encoded_bytes());
+        let value = from_bytes(value.as_encoded_bytes());
+        // The map keeps its own copy of each key.
+        unsafe {
+            adan_map_insert(map, (&raw const key).cast(), (&raw const value).cast());
+            adan_str_free(key);
+        }
+    }
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{adan_map_free, adan_map_free_strings, adan_map_get};
+
+    fn s(text: &str) -> *mut c_char {
+        from_string(text.to_string())
+    }
+
+    fn owned(result: *mut c_char) -> String {
+        let copy = text(result).into_owned();
+        unsafe { adan_str_free(result) };
+        copy
+    }
+
+    #[test]
+    fn set_variables_read_back() {
+        let (key, value) = (s("ADAN_RT_ENV_TEST"), s("on=yes"));
+        unsafe {
+            assert!(!adan_env_has(key));
+            assert_eq!(owned(adan_env_get(key)), "");
+            assert!(adan_env_set(key, value).is_null());
+            assert!(adan_env_has(key));
+            assert_eq!(owned(adan_env_get(key)), "on=yes");
+
+            let vars = adan_env_vars();
+            let found = adan_map_get(vars, (&raw const key).cast());
+            assert!(!found.is_null());
+            assert_eq!(text(found.cast::<*mut c_char>().read_unaligned()), "on=yes");
+            adan_map_free_strings(vars);
+            adan_map_free(vars);
+            adan_str_free(key);
+            adan_str_free(value);
+        }
+    }
+
+    #[test]
+    fn bad_names_and_values_are_errors() {
+        unsafe {
+            assert_eq!(owned(adan_env_set(s(""), s("x"))), "env.set: \"\" is not a valid variable name");
+            assert_eq!(owned(adan_env_set(s("A=B"), s("x"))), "env.set: \"A=B\" is not a valid variable name");
+            assert_eq!(owned(adan_env_set(s("ADAN_RT_NUL"), s("a\0b"))), "env.set: the value of ADAN_RT_NUL contains a NUL character");
+            assert!(!adan_env_has(s("ADAN_RT_NUL")));
+        }
+    }
+}
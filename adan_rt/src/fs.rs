@@ -0,0 +1,285 @@
+use std::ffi::c_char;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::ptr;
+use std::sync::Mutex;
+
+use crate::string::{bytes, from_bytes, from_string, text};
+use crate::vector::{adan_vec_clear, adan_vec_free_strings, adan_vec_push, AdanVec};
+
+// Every function here returns null when it worked and otherwise the error message, which the
+// compiler puts into the `Fs_*` record it hands back. Nothing panics on an I/O error.
+fn report(path: &Path, result: io::Result<()>) -> *mut c_char {
+    match result {
+        Ok(()) => ptr::null_mut(),
+        Err(e) => from_string(format!("{}: {}", path.display(), e)),
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_fs_read_to_string(path_str: *const c_char, out: *mut *mut c_char) -> *mut c_char {
+    let path = text(path_str);
+    let path = Path::new(path.as_ref());
+    report(path, fs::read(path).map(|contents| unsafe { out.write(from_bytes(&contents)) }))
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_fs_write(path_str: *const c_char, contents: *const c_char) -> *mut c_char {
+    let path = text(path_str);
+    let path = Path::new(path.as_ref());
+    report(path, fs::write(path, unsafe { bytes(contents) }))
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_fs_append(path_str: *const c_char, contents: *const c_char) -> *mut c_char {
+    let path = text(path_str);
+    let path = Path::new(path.as_ref());
+    let appended = OpenOptions::new().create(true).append(true).open(path)
+        .and_then(|mut file| file.write_all(unsafe { bytes(contents) }));
+    report(path, appended)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_fs_exists(path_str: *const c_char) -> bool {
+    Path::new(text(path_str).as_ref()).exists()
+}
+
+// Removes a file or an empty directory, never a whole tree.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_fs_remove(path_str: *const c_char) -> *mut c_char {
+    let path = text(path_str);
+    let path = Path::new(path.as_ref());
+    let removed = if path.is_dir() { fs::remove_dir(path) } else { fs::remove_file(path) };
+    report(path, removed)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_fs_create_dir_all(path_str: *const c_char) -> *mut c_char {
+    let path = text(path_str);
+    let path = Path::new(path.as_ref());
+    report(path, fs::create_dir_all(path))
+}
+
+// Replaces what `entries`, a vector of strings, holds with the names in the directory, sorted.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_fs_list_dir(path_str: *const c_char, entries: *mut AdanVec) -> *mut c_char {
+    let path = text(path_str);
+    let path = Path::new(path.as_ref());
+    let names = fs::read_dir(path).and_then(|dir| {
+        dir.map(|entry| entry.map(|e| e.file_name().to_string_lossy().into_owned())).collect::<io::Result<Vec<_>>>()
+    });
+
+    report(path, names.map(|mut names| {
+        names.sort();
+        unsafe {
+            adan_vec_free_strings(entries);
+            adan_vec_clear(entries);
+        }
+        for name in names {
+            let name = from_string(name);
+            unsafe { adan_vec_push(entries, (&raw const name).cast()) };
+        }
+    }))
+}
+
+// Files opened with adan_fs_open are numbered by their place in this table, closing one leaves
+// its place empty so numbers are never reused for a different file.
+enum OpenFile {
+    Read(BufReader<File>),
+    Write(File),
+}
+
+static FILES: Mutex<Vec<Option<OpenFile>>> = Mutex::new(Vec::new());
+
+fn with_file<T>(file: i64, f: impl FnOnce(&mut OpenFile) -> io::Result<T>) -> io::Result<T> {
+    let mut files = FILES.lock().unwrap_or_else(|e| e.into_inner());
+    match usize::try_from(file).ok().and_then(|i| files.get_mut(i)).and_then(|f| f.as_mut()) {
+        Some(open) => f(open),
+        None => Err(io::Error::other(format!("file {} is not open", file))),
+    }
+}
+
+fn file_report(file: i64, result: io::Result<()>) -> *mut c_char {
+    match result {
+        Ok(()) => ptr::null_mut(),
+        Err(e) => from_string(format!("file {}: {}", file, e)),
+    }
+}
+
+// `mode` is "r" to read, "w" to write over the file or "a" to append to it, the last two create it
+// if needed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_fs_open(path_str: *const c_char, mode: *const c_char, out: *mut i64) -> *mut c_char {
+    let path = text(path_str);
+    let path = Path::new(path.as_ref());
+    let opened = match unsafe { bytes(mode) } {
+        b"r" => File::open(path).map(|f| OpenFile::Read(BufReader::new(f))),
+        b"w" => File::create(path).map(OpenFile::Write),
+        b"a" => OpenOptions::new().create(true).append(true).open(path).map(OpenFile::Write),
+        _ => Err(io::Error::other(format!("unknown mode \"{}\", expected \"r\", \"w\" or \"a\"", text(mode)))),
+    };
+
+    report(path, opened.map(|open| {
+        let mut files = FILES.lock().unwrap_or_else(|e| e.into_inner());
+        files.push(Some(open));
+        unsafe { out.write(files.len() as i64 - 1) };
+    }))
+}
+
+// The next line without its line ending. Reading past the last one is an error.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_fs_read_line(file: i64, out: *mut *mut c_char) -> *mut c_char {
+    let read = with_file(file, |open| {
+        let OpenFile::Read(reader) = open else {
+            return Err(io::Error::other("not open for reading"));
+        };
+
+        let mut line = Vec::new();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "end of file"));
+        }
+        if line.ends_with(b"\n") {
+            line.pop();
+        }
+        if line.ends_with(b"\r") {
+            line.pop();
+        }
+        unsafe { out.write(from_bytes(&line)) };
+        Ok(())
+    });
+    file_report(file, read)
+}
+
+// True once a file open for reading has no lines left, or when it isn't one.
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_fs_eof(file: i64) -> bool {
+    with_file(file, |open| match open {
+        OpenFile::Read(reader) => Ok(reader.fill_buf()?.is_empty()),
+        OpenFile::Write(_) => Ok(true),
+    }).unwrap_or(true)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_fs_write_str(file: i64, s: *const c_char, newline: bool) -> *mut c_char {
+    let written = with_file(file, |open| {
+        let OpenFile::Write(writer) = open else {
+            return Err(io::Error::other("not open for writing"));
+        };
+        writer.write_all(unsafe { bytes(s) })?;
+        if newline {
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    });
+    file_report(file, written)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_fs_close(file: i64) -> *mut c_char {
+    let mut files = FILES.lock().unwrap_or_else(|e| e.into_inner());
+    let closed = match usize::try_from(file).ok().and_then(|i| files.get_mut(i)).and_then(|f| f.take()) {
+        Some(OpenFile::Write(mut writer)) => writer.flush(),
+        Some(OpenFile::Read(_)) => Ok(()),
+        None => Err(io::Error::other("not open")),
+    };
+    file_report(file, closed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::string::adan_str_free;
+    use crate::vector::{adan_vec_at, adan_vec_free, adan_vec_len, adan_vec_new};
+    use std::path::PathBuf;
+
+    fn s(text: &str) -> *mut c_char {
+        from_string(text.to_string())
+    }
+
+    // The error message, or None when the call worked.
+    fn error(result: *mut c_char) -> Option<String> {
+        if result.is_null() {
+            return None;
+        }
+        let copy = text(result).into_owned();
+        unsafe { adan_str_free(result) };
+        Some(copy)
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("adan_rt_fs_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn missing_files_are_errors_not_panics() {
+        let missing = scratch_dir("missing").join("nope.txt");
+        let path = s(&missing.display().to_string());
+        unsafe {
+            let mut contents = ptr::null_mut();
+            let message = error(adan_fs_read_to_string(path, &mut contents)).expect("reading a missing file fails");
+            assert!(message.starts_with(&missing.display().to_string()), "{}", message);
+            assert!(contents.is_null());
+
+            assert!(!adan_fs_exists(path));
+            assert!(error(adan_fs_remove(path)).is_some());
+
+            let mut file = -1;
+            assert!(error(adan_fs_open(path, s("r"), &mut file)).is_some());
+            assert_eq!(file, -1);
+
+            let entries = adan_vec_new(size_of::<*mut c_char>() as i64);
+            assert!(error(adan_fs_list_dir(path, entries)).is_some());
+            assert_eq!(adan_vec_len(entries), 0);
+            adan_vec_free(entries);
+            adan_str_free(path);
+        }
+    }
+
+    #[test]
+    fn files_that_arent_open_are_errors() {
+        let mut line = ptr::null_mut();
+        assert_eq!(error(unsafe { adan_fs_read_line(-1, &mut line) }).as_deref(), Some("file -1: file -1 is not open"));
+        assert_eq!(error(adan_fs_close(1 << 40)).as_deref(), Some("file 1099511627776: not open"));
+        assert!(adan_fs_eof(-1));
+    }
+
+    #[test]
+    fn written_files_read_back() {
+        let dir = scratch_dir("round_trip");
+        let file_path = s(&dir.join("report.txt").display().to_string());
+        unsafe {
+            assert_eq!(error(adan_fs_write(file_path, s("one\n"))), None);
+            assert_eq!(error(adan_fs_append(file_path, s("two\r\n"))), None);
+            assert!(adan_fs_exists(file_path));
+
+            let mut file = -1;
+            assert_eq!(error(adan_fs_open(file_path, s("r"), &mut file)), None);
+            let mut lines = Vec::new();
+            while !adan_fs_eof(file) {
+                let mut line = ptr::null_mut();
+                assert_eq!(error(adan_fs_read_line(file, &mut line)), None);
+                lines.push(text(line).into_owned());
+                adan_str_free(line);
+            }
+            assert_eq!(lines, ["one", "two"]);
+            let mut line = ptr::null_mut();
+            assert!(error(adan_fs_read_line(file, &mut line)).is_some_and(|e| e.ends_with("end of file")));
+            assert_eq!(error(adan_fs_close(file)), None);
+
+            let entries = adan_vec_new(size_of::<*mut c_char>() as i64);
+            assert_eq!(error(adan_fs_list_dir(s(&dir.display().to_string()), entries)), None);
+            assert_eq!(adan_vec_len(entries), 1);
+            assert_eq!(text(adan_vec_at(entries, 0).cast::<*mut c_char>().read_unaligned()), "report.txt");
+            adan_vec_free_strings(entries);
+            adan_vec_free(entries);
+
+            assert_eq!(error(adan_fs_remove(file_path)), None);
+            assert!(!adan_fs_exists(file_path));
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,240 @@
+use std::error::Error;
+use std::ffi::c_char;
+use std::ptr;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use reqwest::blocking::Client;
+use reqwest::Method;
+
+use crate::map::AdanMap;
+use crate::string::{bytes, from_bytes, from_string, text};
+
+// Requests wait this long for an answer before they fail.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+// One client for the whole program, so connections to the same host are reused.
+static CLIENT: OnceLock<Result<Client, String>> = OnceLock::new();
+
+fn client() -> Result<&'static Client, String> {
+    CLIENT.get_or_init(|| Client::builder().timeout(TIMEOUT).build().map_err(|e| e.to_string()))
+        .as_ref()
+        .map_err(|e| e.clone())
+}
+
+// Sends a `method` request to `url` with the optional `body` and `headers`, a Map<String, String>,
+// and leaves the response's status, headers and body in the out pointers. Headers come back as
+// `name: value` lines. Any status counts as an answer, null is returned unless there was none.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_http_request(
+    method: *const c_char,
+    url: *const c_char,
+    body: *const c_char,
+    headers: *const AdanMap,
+    status: *mut i64,
+    headers_out: *mut *mut c_char,
+    body_out: *mut *mut c_char,
+) -> *mut c_char {
+    let method = match Method::from_bytes(unsafe { bytes(method) }) {
+        Ok(method) => method,
+        Err(_) => return from_string(format!("\"{}\" is not an HTTP method", text(method))),
+    };
+    let client = match client() {
+        Ok(client) => client,
+        Err(e) => return from_string(format!("http: {}", e)),
+    };
+
+    let mut request = client.request(method, text(url).as_ref());
+    if !headers.is_null() {
+        for (name, value) in unsafe { (*headers).string_pairs() } {
+            request = request.header(name, unsafe { bytes(value) });
+        }
+    }
+    if !body.is_null() {
+        request = request.body(unsafe { bytes(body) }.to_vec());
+    }
+
+    let response = request.send().and_then(|response| {
+        let code = i64::from(response.status().as_u16());
+        let mut lines = Vec::new();
+        for (name, value) in response.headers() {
+            lines.extend_from_slice(name.as_str().as_bytes());
+            lines.extend_from_slice(b": ");
+            lines.extend_from_slice(value.as_bytes());
+            lines.push(b'\n');
+        }
+        Ok((code, lines, response.bytes()?))
+    });
+
+    match response {
+        Ok((code, lines, contents)) => {
+            unsafe {
+                status.write(code);
+                headers_out.write(from_bytes(&lines));
+                body_out.write(from_bytes(&contents));
+            }
+            ptr::null_mut()
+        }
+        Err(e) => from_string(format!("{}: {}", text(url), describe(&e))),
+    }
+}
+
+// reqwest's own message only names the step that failed, the reasons are further down the chain.
+fn describe(error: &dyn Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+// Value of the header `name` in `headers` as adan_http_request gives them back, "" when it's
+// missing. Names are compared without regard to case, like HTTP does.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_http_header(headers: *const c_char, name: *const c_char) -> *mut c_char {
+    let name = unsafe { bytes(name) };
+    let value = unsafe { bytes(headers) }
+        .split(|c| *c == b'\n')
+        .filter_map(|line| line.split_at_checked(name.len()))
+        .find(|(line_name, rest)| line_name.eq_ignore_ascii_case(name) && rest.starts_with(b": "))
+        .map_or(&[][..], |(_, rest)| &rest[2..]);
+    from_bytes(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    use crate::map::{adan_map_free, adan_map_insert, adan_map_new};
+    use crate::string::adan_str_free;
+
+    // What the server saw of a request: its request line, headers and body.
+    struct Seen {
+        request_line: String,
+        headers: Vec<String>,
+        body: Vec<u8>,
+    }
+
+    // Answers a single request on a loopback port with `reply`, and hands back what it was sent.
+    fn serve_once(reply: &'static str) -> (String, JoinHandle<Seen>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/path?q=1", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+
+            let mut headers = Vec::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let line = line.trim_end().to_string();
+                if line.is_empty() {
+                    break;
+                }
+                headers.push(line.to_ascii_lowercase());
+            }
+            let length = headers.iter()
+                .find_map(|h| h.strip_prefix("content-length: "))
+                .map_or(0, |n| n.parse().unwrap());
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+
+            reader.get_mut().write_all(reply.as_bytes()).unwrap();
+            Seen { request_line: request_line.trim_end().to_string(), headers, body }
+        });
+        (url, server)
+    }
+
+    fn s(text: &str) -> *mut c_char {
+        from_string(text.to_string())
+    }
+
+    fn owned(p: *mut c_char) -> String {
+        let copy = text(p).into_owned();
+        unsafe { adan_str_free(p) };
+        copy
+    }
+
+    // Status, headers and body of a request, or the error.
+    fn request(method: &str, url: &str, body: Option<&str>, headers: *const AdanMap) -> Result<(i64, String, String), String> {
+        let (method, url, body) = (s(method), s(url), body.map_or(ptr::null_mut(), s));
+        let (mut status, mut headers_out, mut body_out) = (0, ptr::null_mut(), ptr::null_mut());
+        let error = unsafe { adan_http_request(method, url, body, headers, &mut status, &mut headers_out, &mut body_out) };
+        unsafe {
+            adan_str_free(method);
+            adan_str_free(url);
+            adan_str_free(body);
+        }
+        if !error.is_null() {
+            return Err(owned(error));
+        }
+        Ok((status, owned(headers_out), owned(body_out)))
+    }
+
+    #[test]
+    fn get_returns_status_headers_and_body() {
+        let (url, server) = serve_once("HTTP/1.1 200 OK\r\nContent-Length: 5\r\nX-Answer: 42\r\nConnection: close\r\n\r\nhello");
+        let (status, headers, body) = request("GET", &url, None, ptr::null()).unwrap();
+        assert_eq!(status, 200);
+        assert_eq!(body, "hello");
+
+        let (headers, name) = (s(&headers), s("x-ANSWER"));
+        assert_eq!(owned(unsafe { adan_http_header(headers, name) }), "42");
+        unsafe {
+            adan_str_free(headers);
+            adan_str_free(name);
+        }
+
+        let seen = server.join().unwrap();
+        assert_eq!(seen.request_line, "GET /path?q=1 HTTP/1.1");
+        assert!(seen.body.is_empty());
+    }
+
+    #[test]
+    fn post_sends_the_body_and_headers() {
+        let (url, server) = serve_once("HTTP/1.1 201 Created\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+        let headers = adan_map_new(8, 8, 1);
+        let (name, value) = (s("X-Token"), s("secret"));
+        unsafe { adan_map_insert(headers, (&name as *const *mut c_char).cast(), (&value as *const *mut c_char).cast()) };
+
+        let (status, _, body) = request("POST", &url, Some("a=1"), headers).unwrap();
+        assert_eq!(status, 201);
+        assert_eq!(body, "");
+
+        let seen = server.join().unwrap();
+        assert_eq!(seen.request_line, "POST /path?q=1 HTTP/1.1");
+        assert!(seen.headers.contains(&"x-token: secret".to_string()));
+        assert_eq!(seen.body, b"a=1");
+        unsafe {
+            adan_map_free(headers);
+            adan_str_free(name);
+            adan_str_free(value);
+        }
+    }
+
+    #[test]
+    fn error_statuses_are_still_answers() {
+        let (url, server) = serve_once("HTTP/1.1 404 Not Found\r\nContent-Length: 4\r\nConnection: close\r\n\r\ngone");
+        assert_eq!(request("GET", &url, None, ptr::null()).unwrap(), (404, "content-length: 4\nconnection: close\n".to_string(), "gone".to_string()));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn failures_name_the_url() {
+        // Nothing listens on a port that was just given up.
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let url = format!("http://127.0.0.1:{}/", port);
+        let error = request("GET", &url, None, ptr::null()).unwrap_err();
+        assert!(error.starts_with(&format!("{}: ", url)), "{}", error);
+
+        assert_eq!(request("NOT A METHOD", &url, None, ptr::null()).unwrap_err(), "\"NOT A METHOD\" is not an HTTP method");
+    }
+}
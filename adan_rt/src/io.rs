@@ -0,0 +1,133 @@
+use std::ffi::c_char;
+use std::io::{self, BufRead, Write};
+use std::ptr;
+use std::str::FromStr;
+
+use crate::string::{bytes, from_bytes, from_string};
+
+// Output is flushed on every call, compiled programs exit through C and never flush Rust's buffers.
+fn write_to(mut out: impl Write, s: *const c_char, newline: bool) {
+    let _ = out.write_all(unsafe { bytes(s) });
+    if newline {
+        let _ = out.write_all(b"\n");
+    }
+    let _ = out.flush();
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_io_print(s: *const c_char) {
+    write_to(io::stdout().lock(), s, false);
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_io_println(s: *const c_char) {
+    write_to(io::stdout().lock(), s, true);
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_io_eprintln(s: *const c_char) {
+    write_to(io::stderr().lock(), s, true);
+}
+
+// Next line of stdin without its line ending, None at the end of input.
+fn read_line() -> Option<Vec<u8>> {
+    read_line_from(io::stdin().lock())
+}
+
+fn read_line_from(mut input: impl BufRead) -> Option<Vec<u8>> {
+    let mut line = Vec::new();
+    match input.read_until(b'\n', &mut line) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => {
+            if line.ends_with(b"\n") {
+                line.pop();
+            }
+            if line.ends_with(b"\r") {
+                line.pop();
+            }
+            Some(line)
+        }
+    }
+}
+
+// The empty string at the end of input, which adan_io_eof tells apart from an empty line.
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_io_readln() -> *mut c_char {
+    from_bytes(&read_line().unwrap_or_default())
+}
+
+// True when stdin has nothing left, waiting for more input if there's none yet.
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_io_eof() -> bool {
+    io::stdin().lock().fill_buf().map_or(true, |buffer| buffer.is_empty())
+}
+
+// Parses a line holding a single number into `out`. Null when it did, otherwise the message the
+// program panics with.
+fn read_number<T: FromStr>(line: Option<Vec<u8>>, what: &str, out: *mut T) -> *mut c_char {
+    let Some(line) = line else {
+        return from_string(format!("io.read_{}: end of input", what));
+    };
+    let text = String::from_utf8_lossy(&line);
+    match text.trim().parse() {
+        Ok(value) => {
+            unsafe { out.write(value) };
+            ptr::null_mut()
+        }
+        Err(_) => from_string(format!("io.read_{}: \"{}\" is not a valid {}", what, text, what)),
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_io_read_i64(out: *mut i64) -> *mut c_char {
+    read_number(read_line(), "i64", out)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_io_read_f64(out: *mut f64) -> *mut c_char {
+    read_number(read_line(), "f64", out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::string::{adan_str_free, text};
+
+    fn message(error: *mut c_char) -> String {
+        assert!(!error.is_null(), "expected an error");
+        let copy = text(error).into_owned();
+        unsafe { adan_str_free(error) };
+        copy
+    }
+
+    #[test]
+    fn lines_lose_their_line_ending() {
+        let mut input: &[u8] = b"one\r\n\ntwo";
+        assert_eq!(read_line_from(&mut input).as_deref(), Some(&b"one"[..]));
+        assert_eq!(read_line_from(&mut input).as_deref(), Some(&b""[..]));
+        assert_eq!(read_line_from(&mut input).as_deref(), Some(&b"two"[..]));
+        assert_eq!(read_line_from(&mut input), None);
+    }
+
+    #[test]
+    fn numbers_are_read_from_the_whole_line() {
+        let mut n = 0i64;
+        assert!(read_number(Some(b" -42 ".to_vec()), "i64", &mut n).is_null());
+        assert_eq!(n, -42);
+        let mut x = 0f64;
+        assert!(read_number(Some(b"2.5".to_vec()), "f64", &mut x).is_null());
+        assert_eq!(x, 2.5);
+    }
+
+    #[test]
+    fn bad_numbers_and_the_end_of_input_are_errors() {
+        let mut n = 7i64;
+        assert_eq!(message(read_number(Some(b"4 2".to_vec()), "i64", &mut n)), "io.read_i64: \"4 2\" is not a valid i64");
+        assert_eq!(message(read_number(Some(b"2.5".to_vec()), "i64", &mut n)), "io.read_i64: \"2.5\" is not a valid i64");
+        assert_eq!(message(read_number(None, "i64", &mut n)), "io.read_i64: end of input");
+        assert_eq!(n, 7);
+
+        let mut x = 0f64;
+        assert_eq!(message(read_number(Some(b"".to_vec()), "f64", &mut x)), "io.read_f64: \"\" is not a valid f64");
+    }
+}
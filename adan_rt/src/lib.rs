@@ -0,0 +1,23 @@
+// Runtime support linked into every compiled ADAN program.
+// Everything exported here uses the C ABI and is declared on demand by the compiler, generated
+// code never calls into libc directly.
+
+// Only compiler generated code calls these, with pointers it got from the runtime itself.
+#![allow(clippy::missing_safety_doc)]
+
+pub mod alloc;
+pub mod chars;
+pub mod env;
+pub mod fs;
+pub mod http;
+pub mod io;
+pub mod map;
+pub mod math;
+pub mod net;
+pub mod num;
+pub mod panic;
+pub mod process;
+pub mod random;
+pub mod string;
+pub mod time;
+pub mod vector;
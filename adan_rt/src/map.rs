@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+use std::ffi::c_char;
+
+use crate::string::{adan_str_dup, adan_str_free, bytes, from_bytes, static_layout, HEADER};
+use crate::vector::AdanVec;
+
+// Insertion ordered hash map of fixed size keys and values. Like vectors, the runtime only knows
+// sizes, except that string keys are compared by their contents instead of their address.
+pub struct AdanMap {
+    key_size: usize,
+    value_size: usize,
+    string_keys: bool,
+    entries: Vec<Entry>,
+    index: HashMap<Vec<u8>, usize>,
+}
+
+#[derive(Clone)]
+struct Entry {
+    key: Vec<u8>, // Key memory, or the map's own copy of the string for string keys.
+    value: Vec<u8>,
+}
+
+impl AdanMap {
+    // Keys arrive as a pointer to the key's memory, for string keys that memory holds a `char*`.
+    fn key_bytes(&self, key: *const u8) -> Vec<u8> {
+        if self.string_keys {
+            static_layout(unsafe { bytes(*(key as *const *const c_char)) })
+        } else {
+            unsafe { std::slice::from_raw_parts(key, self.key_size) }.to_vec()
+        }
+    }
+
+    fn position(&self, key: *const u8) -> Option<usize> {
+        self.index.get(&self.key_bytes(key)).copied()
+    }
+
+    // Index of the entry for `key`, added with a zeroed value if it's missing.
+    fn entry(&mut self, key: *const u8) -> usize {
+        let bytes = self.key_bytes(key);
+        if let Some(&position) = self.index.get(&bytes) {
+            return position;
+        }
+
+        self.index.insert(bytes.clone(), self.entries.len());
+        self.entries.push(Entry { key: bytes, value: vec![0; self.value_size] });
+        self.entries.len() - 1
+    }
+
+    // Keys and values of a Map<String, String> in insertion order, both still the map's.
+    pub(crate) fn string_pairs(&self) -> Vec<(&[u8], *const c_char)> {
+        self.entries.iter()
+            .map(|e| (&e.key[HEADER..e.key.len() - 1], unsafe { e.value.as_ptr().cast::<*const c_char>().read_unaligned() }))
+            .collect()
+    }
+
+    // What keys() hands back for `entry`, string keys become a new string the vector owns.
+    fn key_memory(&self, entry: &Entry) -> Vec<u8> {
+        if self.string_keys {
+            let key = &entry.key[HEADER..entry.key.len() - 1];
+            (from_bytes(key) as usize).to_ne_bytes().to_vec()
+        } else {
+            entry.key.clone()
+        }
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_map_new(key_size: i64, value_size: i64, string_keys: u8) -> *mut AdanMap {
+    Box::into_raw(Box::new(AdanMap {
+        key_size: key_size as usize,
+        value_size: value_size as usize,
+        string_keys: string_keys != 0,
+        entries: Vec::new(),
+        index: HashMap::new(),
+    }))
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_map_clone(map: *const AdanMap) -> *mut AdanMap {
+    let map = unsafe { &*map };
+    Box::into_raw(Box::new(AdanMap {
+        key_size: map.key_size,
+        value_size: map.value_size,
+        string_keys: map.string_keys,
+        entries: map.entries.clone(),
+        index: map.index.clone(),
+    }))
+}
+
+// Null is accepted so scope cleanup can free variables that were never initialized.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_map_free(map: *mut AdanMap) {
+    if !map.is_null() {
+        drop(unsafe { Box::from_raw(map) });
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_map_len(map: *const AdanMap) -> i64 {
+    unsafe { (*map).entries.len() as i64 }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_map_insert(map: *mut AdanMap, key: *const u8, value: *const u8) {
+    let map = unsafe { &mut *map };
+    let position = map.entry(key);
+    let bytes = unsafe { std::slice::from_raw_parts(value, map.value_size) };
+    map.entries[position].value.copy_from_slice(bytes);
+}
+
+// Address of the value stored for `key`, or null when the map doesn't contain it.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_map_get(map: *mut AdanMap, key: *const u8) -> *mut u8 {
+    let map = unsafe { &mut *map };
+    match map.position(key) {
+        Some(position) => map.entries[position].value.as_mut_ptr(),
+        None => std::ptr::null_mut(),
+    }
+}
+
+// Address of the value of the `index`th entry in insertion order, for code that visits every value.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_map_value_at(map: *mut AdanMap, index: i64) -> *mut u8 {
+    unsafe { (&mut (*map).entries)[index as usize].value.as_mut_ptr() }
+}
+
+// Address of the value stored for `key`, adding a zeroed one first if it's missing. Used when
+// assigning to `m[key]`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_map_entry(map: *mut AdanMap, key: *const u8) -> *mut u8 {
+    let map = unsafe { &mut *map };
+    let position = map.entry(key);
+    map.entries[position].value.as_mut_ptr()
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_map_contains(map: *const AdanMap, key: *const u8) -> bool {
+    unsafe { (*map).position(key).is_some() }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_map_remove(map: *mut AdanMap, key: *const u8) -> bool {
+    let map = unsafe { &mut *map };
+    let Some(position) = map.position(key) else {
+        return false;
+    };
+
+    let removed = map.entries.remove(position);
+    map.index.remove(&removed.key);
+    for later in map.index.values_mut().filter(|p| **p > position) {
+        *later -= 1;
+    }
+    true
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_map_clear(map: *mut AdanMap) {
+    let map = unsafe { &mut *map };
+    map.entries.clear();
+    map.index.clear();
+}
+
+// Keys in insertion order.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_map_keys(map: *const AdanMap) -> *mut AdanVec {
+    let map = unsafe { &*map };
+    let bytes: Vec<u8> = map.entries.iter().flat_map(|e| map.key_memory(e)).collect();
+    let key_size = if map.string_keys { size_of::<usize>() } else { map.key_size };
+    AdanVec::with_bytes(key_size, map.entries.len(), &bytes)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_map_values(map: *const AdanMap) -> *mut AdanVec {
+    let map = unsafe { &*map };
+    let bytes: Vec<u8> = map.entries.iter().flat_map(|e| e.value.iter().copied()).collect();
+    AdanVec::with_bytes(map.value_size, map.entries.len(), &bytes)
+}
+
+// Maps with string values own them, like vectors of strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_map_free_strings(map: *mut AdanMap) {
+    if map.is_null() {
+        return;
+    }
+
+    for entry in unsafe { &mut (*map).entries } {
+        unsafe { adan_str_free(entry.value.as_ptr().cast::<*mut c_char>().read_unaligned()) };
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_map_dup_strings(map: *mut AdanMap) {
+    for entry in unsafe { &mut (*map).entries } {
+        let value = entry.value.as_mut_ptr().cast::<*mut c_char>();
+        unsafe { value.write_unaligned(adan_str_dup(value.read_unaligned())) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::string::{from_string, text};
+    use crate::vector::{adan_vec_at, adan_vec_free, adan_vec_free_strings, adan_vec_len};
+
+    fn insert(map: *mut AdanMap, key: i64, value: i64) {
+        unsafe { adan_map_insert(map, (&key as *const i64).cast(), (&value as *const i64).cast()) };
+    }
+
+    fn get(map: *mut AdanMap, key: i64) -> Option<i64> {
+        let found = unsafe { adan_map_get(map, (&key as *const i64).cast()) };
+        (!found.is_null()).then(|| unsafe { found.cast::<i64>().read_unaligned() })
+    }
+
+    fn keys(map: *mut AdanMap) -> Vec<i64> {
+        let keys = unsafe { adan_map_keys(map) };
+        let found = (0..unsafe { adan_vec_len(keys) }).map(|i| unsafe { adan_vec_at(keys, i).cast::<i64>().read_unaligned() }).collect();
+        unsafe { adan_vec_free(keys) };
+        found
+    }
+
+    #[test]
+    fn keys_come_back_in_insertion_order() {
+        let map = adan_map_new(8, 8, 0);
+        for key in [30, 10, 20] {
+            insert(map, key, key * 2);
+        }
+        assert_eq!(keys(map), [30, 10, 20]);
+        assert_eq!((0..3).map(|i| unsafe { adan_map_value_at(map, i).cast::<i64>().read_unaligned() }).collect::<Vec<_>>(), [60, 20, 40]);
+        unsafe { adan_map_free(map) };
+    }
+
+    #[test]
+    fn insert_overwrites_in_place() {
+        let map = adan_map_new(8, 8, 0);
+        insert(map, 1, 10);
+        insert(map, 2, 20);
+        insert(map, 1, 11);
+        assert_eq!(keys(map), [1, 2]);
+        assert_eq!(get(map, 1), Some(11));
+        assert_eq!(unsafe { adan_map_len(map) }, 2);
+        unsafe { adan_map_free(map) };
+    }
+
+    #[test]
+    fn remove_keeps_the_order_of_the_rest() {
+        let map = adan_map_new(8, 8, 0);
+        for key in 0..4 {
+            insert(map, key, key);
+        }
+        assert!(unsafe { adan_map_remove(map, (&1i64 as *const i64).cast()) });
+        assert!(!unsafe { adan_map_remove(map, (&1i64 as *const i64).cast()) });
+        assert_eq!(keys(map), [0, 2, 3]);
+        // Positions after the removed entry moved down, lookups still find them.
+        assert_eq!(get(map, 3), Some(3));
+        assert_eq!(get(map, 1), None);
+
+        insert(map, 1, 5);
+        assert_eq!(keys(map), [0, 2, 3, 1]);
+        unsafe { adan_map_free(map) };
+    }
+
+    #[test]
+    fn entry_adds_a_zeroed_value() {
+        let map = adan_map_new(8, 8, 0);
+        let value = unsafe { adan_map_entry(map, (&4i64 as *const i64).cast()) };
+        assert_eq!(unsafe { value.cast::<i64>().read_unaligned() }, 0);
+        assert_eq!(get(map, 4), Some(0));
+        unsafe { adan_map_free(map) };
+    }
+
+    #[test]
+    fn string_keys_compare_by_contents() {
+        let map = adan_map_new(8, 8, 1);
+        let (first, second) = (from_string("key".to_string()), from_string("key".to_string()));
+        unsafe {
+            adan_map_insert(map, (&first as *const *mut c_char).cast(), (&1i64 as *const i64).cast());
+            adan_map_insert(map, (&second as *const *mut c_char).cast(), (&2i64 as *const i64).cast());
+            assert_eq!(adan_map_len(map), 1);
+
+            // The map keeps its own copy, so the caller's key can go.
+            adan_str_free(first);
+            adan_str_free(second);
+            let keys = adan_map_keys(map);
+            assert_eq!(text(adan_vec_at(keys, 0).cast::<*const c_char>().read_unaligned()), "key");
+            adan_vec_free_strings(keys);
+            adan_vec_free(keys);
+            adan_map_free(map);
+        }
+    }
+
+    #[test]
+    fn clear_empties_the_index_too() {
+        let map = adan_map_new(8, 8, 0);
+        insert(map, 1, 1);
+        unsafe { adan_map_clear(map) };
+        assert_eq!(get(map, 1), None);
+        insert(map, 2, 2);
+        assert_eq!(keys(map), [2]);
+        unsafe { adan_map_free(map) };
+    }
+}
@@ -0,0 +1,38 @@
+// The math functions LLVM has no intrinsic for, in both float widths. The f32 ones are suffixed
+// with `f` like libm's.
+macro_rules! libm {
+    ($($name:ident / $name_f32:ident => $method:ident),* $(,)?) => {
+        $(
+            #[unsafe(no_mangle)]
+            pub extern "C" fn $name(x: f64) -> f64 {
+                x.$method()
+            }
+
+            #[unsafe(no_mangle)]
+            pub extern "C" fn $name_f32(x: f32) -> f32 {
+                x.$method()
+            }
+        )*
+    };
+}
+
+libm! {
+    adan_math_tan / adan_math_tanf => tan,
+    adan_math_asin / adan_math_asinf => asin,
+    adan_math_acos / adan_math_acosf => acos,
+    adan_math_atan / adan_math_atanf => atan,
+    adan_math_sinh / adan_math_sinhf => sinh,
+    adan_math_cosh / adan_math_coshf => cosh,
+    adan_math_tanh / adan_math_tanhf => tanh,
+}
+
+// Angle of the point (x, y), in the quadrant the signs of both put it in.
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_math_atan2(y: f64, x: f64) -> f64 {
+    y.atan2(x)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_math_atan2f(y: f32, x: f32) -> f32 {
+    y.atan2(x)
+}
@@ -0,0 +1,361 @@
+use std::ffi::c_char;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::ptr;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::string::{bytes, from_bytes, from_string, text};
+
+// Like the fs functions, these return null when they worked and otherwise the error message,
+// which the compiler puts into the `Net_*` record it hands back.
+fn report(what: &str, result: io::Result<()>) -> *mut c_char {
+    match result {
+        Ok(()) => ptr::null_mut(),
+        Err(e) => from_string(format!("{}: {}", what, e)),
+    }
+}
+
+// Sockets are numbered by their place in this table, like open files. Streams read through a
+// buffer so net.read_line and net.read can be mixed.
+enum Socket {
+    Listener { listener: TcpListener, timeout: Option<Duration> },
+    Stream(BufReader<TcpStream>),
+    Datagram(UdpSocket),
+}
+
+static SOCKETS: Mutex<Vec<Option<Socket>>> = Mutex::new(Vec::new());
+
+// Programs are single threaded, so holding the table while a call blocks stops nothing else.
+fn with_socket<T>(socket: i64, f: impl FnOnce(&mut Socket) -> io::Result<T>) -> io::Result<T> {
+    let mut sockets = SOCKETS.lock().unwrap_or_else(|e| e.into_inner());
+    match usize::try_from(socket).ok().and_then(|i| sockets.get_mut(i)).and_then(|s| s.as_mut()) {
+        Some(open) => f(open),
+        None => Err(io::Error::other("not open")),
+    }
+}
+
+fn add(socket: Socket, out: *mut i64) {
+    let mut sockets = SOCKETS.lock().unwrap_or_else(|e| e.into_inner());
+    sockets.push(Some(socket));
+    unsafe { out.write(sockets.len() as i64 - 1) };
+}
+
+// A read or write that runs out of time fails with WouldBlock on Unix, which says nothing useful.
+fn socket_report(socket: i64, result: io::Result<()>) -> *mut c_char {
+    let result = result.map_err(|e| match e.kind() {
+        io::ErrorKind::WouldBlock => io::Error::new(io::ErrorKind::TimedOut, "timed out"),
+        _ => e,
+    });
+    report(&format!("socket {}", socket), result)
+}
+
+fn wrong_kind(expected: &str) -> io::Error {
+    io::Error::other(format!("not a {}", expected))
+}
+
+// `addr` is a host and port like "127.0.0.1:8080" or "localhost:80", port 0 picks a free one.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_net_tcp_listen(addr: *const c_char, out: *mut i64) -> *mut c_char {
+    let addr = text(addr);
+    let listened = TcpListener::bind(addr.as_ref()).map(|listener| add(Socket::Listener { listener, timeout: None }, out));
+    report(&addr, listened)
+}
+
+// Waits for the next connection to `listener`, no longer than its timeout if it has one.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_net_tcp_accept(listener: i64, out: *mut i64) -> *mut c_char {
+    let accepted = with_socket(listener, |open| {
+        let Socket::Listener { listener, timeout } = open else {
+            return Err(wrong_kind("TCP listener"));
+        };
+        let Some(timeout) = *timeout else {
+            return listener.accept().map(|(stream, _)| stream);
+        };
+
+        // Listeners have no timeout of their own, so it polls until the deadline.
+        let deadline = Instant::now() + timeout;
+        listener.set_nonblocking(true)?;
+        let accepted = loop {
+            match listener.accept() {
+                Ok((stream, _)) => break Ok(stream),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock && Instant::now() < deadline => thread::sleep(Duration::from_millis(1)),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break Err(io::Error::new(io::ErrorKind::TimedOut, "no connection before the timeout")),
+                Err(e) => break Err(e),
+            }
+        };
+        listener.set_nonblocking(false)?;
+        let stream = accepted?;
+        stream.set_nonblocking(false)?;
+        Ok(stream)
+    });
+    socket_report(listener, accepted.map(|stream| add(Socket::Stream(BufReader::new(stream)), out)))
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_net_tcp_connect(addr: *const c_char, out: *mut i64) -> *mut c_char {
+    let addr = text(addr);
+    let connected = TcpStream::connect(addr.as_ref()).map(|stream| add(Socket::Stream(BufReader::new(stream)), out));
+    report(&addr, connected)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_net_udp_bind(addr: *const c_char, out: *mut i64) -> *mut c_char {
+    let addr = text(addr);
+    let bound = UdpSocket::bind(addr.as_ref()).map(|socket| add(Socket::Datagram(socket), out));
+    report(&addr, bound)
+}
+
+// The address the socket is bound to, to find the port a listener on port 0 got. "" when it has
+// none.
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_net_local_addr(socket: i64) -> *mut c_char {
+    let addr = with_socket(socket, |open| match open {
+        Socket::Listener { listener, .. } => listener.local_addr(),
+        Socket::Stream(reader) => reader.get_ref().local_addr(),
+        Socket::Datagram(socket) => socket.local_addr(),
+    });
+    from_string(addr.map(|a| a.to_string()).unwrap_or_default())
+}
+
+// Whatever is available on the stream, at most `max` bytes. "" means the other side closed it.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_net_read(socket: i64, max: i64, out: *mut *mut c_char) -> *mut c_char {
+    let read = with_socket(socket, |open| {
+        let Socket::Stream(reader) = open else {
+            return Err(wrong_kind("TCP stream"));
+        };
+        let mut buffer = vec![0; max.max(0) as usize];
+        let n = reader.read(&mut buffer)?;
+        unsafe { out.write(from_bytes(&buffer[..n])) };
+        Ok(())
+    });
+    socket_report(socket, read)
+}
+
+// The next line without its line ending. Reading past the last one is an error.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_net_read_line(socket: i64, out: *mut *mut c_char) -> *mut c_char {
+    let read = with_socket(socket, |open| {
+        let Socket::Stream(reader) = open else {
+            return Err(wrong_kind("TCP stream"));
+        };
+
+        let mut line = Vec::new();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "end of stream"));
+        }
+        if line.ends_with(b"\n") {
+            line.pop();
+        }
+        if line.ends_with(b"\r") {
+            line.pop();
+        }
+        unsafe { out.write(from_bytes(&line)) };
+        Ok(())
+    });
+    socket_report(socket, read)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_net_write(socket: i64, data: *const c_char) -> *mut c_char {
+    let written = with_socket(socket, |open| match open {
+        Socket::Stream(reader) => reader.get_mut().write_all(unsafe { bytes(data) }),
+        _ => Err(wrong_kind("TCP stream")),
+    });
+    socket_report(socket, written)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_net_send_to(socket: i64, data: *const c_char, addr: *const c_char) -> *mut c_char {
+    let sent = with_socket(socket, |open| match open {
+        Socket::Datagram(udp) => udp.send_to(unsafe { bytes(data) }, text(addr).as_ref()).map(|_| ()),
+        _ => Err(wrong_kind("UDP socket")),
+    });
+    socket_report(socket, sent)
+}
+
+// The next datagram, cut to `max` bytes, and the address it came from.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_net_recv_from(socket: i64, max: i64, out: *mut *mut c_char, from: *mut *mut c_char) -> *mut c_char {
+    let received = with_socket(socket, |open| {
+        let Socket::Datagram(udp) = open else {
+            return Err(wrong_kind("UDP socket"));
+        };
+        let mut buffer = vec![0; max.max(0) as usize];
+        let (n, sender): (usize, SocketAddr) = udp.recv_from(&mut buffer)?;
+        unsafe {
+            out.write(from_bytes(&buffer[..n]));
+            from.write(from_string(sender.to_string()));
+        }
+        Ok(())
+    });
+    socket_report(socket, received)
+}
+
+// Reads, writes and accepts on `socket` fail once they've waited `ms` milliseconds, 0 waits
+// forever again.
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_net_set_timeout(socket: i64, ms: i64) -> *mut c_char {
+    let timeout = (ms > 0).then(|| Duration::from_millis(ms as u64));
+    let set = with_socket(socket, |open| match open {
+        Socket::Listener { timeout: listener_timeout, .. } => {
+            *listener_timeout = timeout;
+            Ok(())
+        }
+        Socket::Stream(reader) => {
+            reader.get_ref().set_read_timeout(timeout)?;
+            reader.get_ref().set_write_timeout(timeout)
+        }
+        Socket::Datagram(udp) => {
+            udp.set_read_timeout(timeout)?;
+            udp.set_write_timeout(timeout)
+        }
+    });
+    socket_report(socket, set)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_net_close(socket: i64) -> *mut c_char {
+    let mut sockets = SOCKETS.lock().unwrap_or_else(|e| e.into_inner());
+    let closed = match usize::try_from(socket).ok().and_then(|i| sockets.get_mut(i)).and_then(|s| s.take()) {
+        Some(_) => Ok(()),
+        None => Err(io::Error::other("not open")),
+    };
+    socket_report(socket, closed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::string::adan_str_free;
+
+    fn s(text: &str) -> *mut c_char {
+        from_string(text.to_string())
+    }
+
+    fn owned(p: *mut c_char) -> String {
+        let copy = text(p).into_owned();
+        unsafe { adan_str_free(p) };
+        copy
+    }
+
+    // Turns the runtime's error convention back into a Result.
+    fn check(error: *mut c_char) -> Result<(), String> {
+        if error.is_null() { Ok(()) } else { Err(owned(error)) }
+    }
+
+    fn open(f: unsafe extern "C" fn(*const c_char, *mut i64) -> *mut c_char, addr: &str) -> i64 {
+        let (addr, mut socket) = (s(addr), -1);
+        check(unsafe { f(addr, &mut socket) }).unwrap();
+        unsafe { adan_str_free(addr) };
+        socket
+    }
+
+    fn write(socket: i64, data: &str) {
+        let data = s(data);
+        check(unsafe { adan_net_write(socket, data) }).unwrap();
+        unsafe { adan_str_free(data) };
+    }
+
+    fn read(socket: i64, max: i64) -> Result<String, String> {
+        let mut out = ptr::null_mut();
+        check(unsafe { adan_net_read(socket, max, &mut out) })?;
+        Ok(owned(out))
+    }
+
+    fn read_line(socket: i64) -> Result<String, String> {
+        let mut out = ptr::null_mut();
+        check(unsafe { adan_net_read_line(socket, &mut out) })?;
+        Ok(owned(out))
+    }
+
+    // A listener, a client connected to it and the server's side of that connection.
+    fn connected_pair() -> (i64, i64, i64) {
+        let listener = open(adan_net_tcp_listen, "127.0.0.1:0");
+        let client = open(adan_net_tcp_connect, &owned(adan_net_local_addr(listener)));
+        let mut server = -1;
+        check(unsafe { adan_net_tcp_accept(listener, &mut server) }).unwrap();
+        (listener, client, server)
+    }
+
+    #[test]
+    fn tcp_round_trip() {
+        let (listener, client, server) = connected_pair();
+        write(client, "hello\r\nsecond line\nrest");
+        assert_eq!(read_line(server).unwrap(), "hello");
+        assert_eq!(read_line(server).unwrap(), "second line");
+        // Lines and plain reads share one buffer.
+        assert_eq!(read(server, 64).unwrap(), "rest");
+
+        write(server, "reply");
+        assert_eq!(read(client, 3).unwrap(), "rep");
+        assert_eq!(read(client, 64).unwrap(), "ly");
+
+        check(adan_net_close(client)).unwrap();
+        assert_eq!(read(server, 64).unwrap(), "");
+        assert_eq!(read_line(server), Err(format!("socket {}: end of stream", server)));
+        for socket in [server, listener] {
+            check(adan_net_close(socket)).unwrap();
+        }
+        assert_eq!(check(adan_net_close(client)), Err(format!("socket {}: not open", client)));
+    }
+
+    #[test]
+    fn udp_send_and_receive() {
+        let (receiver, sender) = (open(adan_net_udp_bind, "127.0.0.1:0"), open(adan_net_udp_bind, "127.0.0.1:0"));
+        let (data, to) = (s("ping"), adan_net_local_addr(receiver));
+        check(unsafe { adan_net_send_to(sender, data, to) }).unwrap();
+
+        let (mut out, mut from) = (ptr::null_mut(), ptr::null_mut());
+        check(unsafe { adan_net_recv_from(receiver, 64, &mut out, &mut from) }).unwrap();
+        assert_eq!(owned(out), "ping");
+        assert_eq!(owned(from), owned(adan_net_local_addr(sender)));
+        unsafe {
+            adan_str_free(data);
+            adan_str_free(to);
+        }
+        for socket in [receiver, sender] {
+            check(adan_net_close(socket)).unwrap();
+        }
+    }
+
+    #[test]
+    fn reads_time_out() {
+        let (listener, client, server) = connected_pair();
+        check(adan_net_set_timeout(server, 20)).unwrap();
+        assert_eq!(read(server, 64), Err(format!("socket {}: timed out", server)));
+
+        // Data that arrives afterwards is still read, and 0 waits forever again.
+        write(client, "late");
+        check(adan_net_set_timeout(server, 0)).unwrap();
+        assert_eq!(read(server, 64).unwrap(), "late");
+
+        let udp = open(adan_net_udp_bind, "127.0.0.1:0");
+        check(adan_net_set_timeout(udp, 20)).unwrap();
+        let (mut out, mut from) = (ptr::null_mut(), ptr::null_mut());
+        assert_eq!(check(unsafe { adan_net_recv_from(udp, 64, &mut out, &mut from) }), Err(format!("socket {}: timed out", udp)));
+        for socket in [listener, client, server, udp] {
+            check(adan_net_close(socket)).unwrap();
+        }
+    }
+
+    #[test]
+    fn accept_times_out() {
+        let listener = open(adan_net_tcp_listen, "127.0.0.1:0");
+        check(adan_net_set_timeout(listener, 20)).unwrap();
+        let mut stream = -1;
+        assert_eq!(check(unsafe { adan_net_tcp_accept(listener, &mut stream) }), Err(format!("socket {}: no connection before the timeout", listener)));
+        check(adan_net_close(listener)).unwrap();
+    }
+
+    #[test]
+    fn sockets_of_the_wrong_kind_are_refused() {
+        let listener = open(adan_net_tcp_listen, "127.0.0.1:0");
+        assert_eq!(read(listener, 64), Err(format!("socket {}: not a TCP stream", listener)));
+        let mut stream = -1;
+        assert_eq!(check(unsafe { adan_net_tcp_accept(-1, &mut stream) }), Err("socket -1: not open".to_string()));
+        check(adan_net_close(listener)).unwrap();
+    }
+}
@@ -0,0 +1,10 @@
+// Float remainder with the sign of the dividend, what `x mod y` means on floats.
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_fmod(lhs: f64, rhs: f64) -> f64 {
+    lhs % rhs
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_fmodf(lhs: f32, rhs: f32) -> f32 {
+    lhs % rhs
+}
@@ -0,0 +1,43 @@
+use std::ffi::c_char;
+use std::io::{self, Write};
+use std::process;
+use std::sync::Mutex;
+
+use crate::string::bytes;
+
+// Exit status of a program that panicked, apart from 0 and from the 1 an ADAN program can
+// return itself, so scripts can tell a failed check from an ordinary error.
+pub const PANIC_STATUS: i32 = 101;
+
+pub type Cleanup = unsafe extern "C" fn();
+
+static CLEANUPS: Mutex<Vec<Cleanup>> = Mutex::new(Vec::new());
+
+// Registers `cleanup` to run when the program panics, the last one registered runs first.
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_on_panic(cleanup: Cleanup) {
+    CLEANUPS.lock().unwrap_or_else(|e| e.into_inner()).push(cleanup);
+}
+
+fn next_cleanup() -> Option<Cleanup> {
+    CLEANUPS.lock().unwrap_or_else(|e| e.into_inner()).pop()
+}
+
+// Reports a failed check, assertion or anything else the program can't go on from as
+// `file:line:column: panic: msg` on stderr, runs the registered cleanup and exits with PANIC_STATUS.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_panic(msg: *const c_char, file: *const c_char, line: u32, column: u32) -> ! {
+    let _ = io::stdout().flush();
+
+    let (msg, file) = unsafe { (bytes(msg), bytes(file)) };
+    let _ = writeln!(io::stderr().lock(), "{}:{}:{}: panic: {}", String::from_utf8_lossy(file), line, column, String::from_utf8_lossy(msg));
+
+    // Each cleanup is taken off the list before it runs, so one that panics again picks up
+    // with the rest instead of running itself twice.
+    while let Some(cleanup) = next_cleanup() {
+        unsafe { cleanup() };
+    }
+
+    let _ = io::stdout().flush();
+    process::exit(PANIC_STATUS);
+}
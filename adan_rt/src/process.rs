@@ -0,0 +1,112 @@
+use std::ffi::{c_char, OsStr};
+use std::io::{self, Write};
+use std::process::{self, Command};
+
+use crate::string::{bytes, from_bytes, from_string, text};
+use crate::vector::AdanVec;
+
+// The program's command line as a new vector of strings, starting with the program itself.
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_process_args() -> *mut AdanVec {
+    let args: Vec<*mut c_char> = std::env::args_os().map(|arg| from_bytes(arg.as_encoded_bytes())).collect();
+    AdanVec::from_strings(&args)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_process_pid() -> i64 {
+    i64::from(process::id())
+}
+
+// Ends the program with `code` right away, like returning it from main would but from anywhere.
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_process_exit(code: i64) -> ! {
+    let _ = io::stdout().flush();
+    process::exit(code as i32)
+}
+
+// Runs `cmd` with the strings in `args` (null for none) and waits for it. Its exit status and
+// everything it wrote go to the out pointers, stdin is left empty. The status is -1 when it was
+// killed by a signal. Null when it ran, otherwise why it couldn't be started.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_process_run(cmd: *const c_char, args: *const AdanVec, status: *mut i64, stdout: *mut *mut c_char, stderr: *mut *mut c_char) -> *mut c_char {
+    let args = if args.is_null() { Vec::new() } else { unsafe { (*args).strings() } };
+    let mut command = Command::new(unsafe { OsStr::from_encoded_bytes_unchecked(bytes(cmd)) });
+    command.args(args.into_iter().map(|arg| unsafe { OsStr::from_encoded_bytes_unchecked(bytes(arg)) }));
+
+    match command.output() {
+        Ok(output) => {
+            unsafe {
+                status.write(output.status.code().map_or(-1, i64::from));
+                stdout.write(from_bytes(&output.stdout));
+                stderr.write(from_bytes(&output.stderr));
+            }
+            std::ptr::null_mut()
+        }
+        Err(e) => from_string(format!("{}: {}", text(cmd), e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::string::adan_str_free;
+    use crate::vector::{adan_vec_free, adan_vec_free_strings};
+    use std::ptr;
+
+    struct Output {
+        status: i64,
+        stdout: String,
+        stderr: String,
+    }
+
+    fn run(cmd: &str, args: &[&str]) -> Result<Output, String> {
+        let strings: Vec<*mut c_char> = args.iter().map(|arg| from_string(arg.to_string())).collect();
+        let args = AdanVec::from_strings(&strings);
+        let cmd = from_string(cmd.to_string());
+        let (mut status, mut stdout, mut stderr) = (0, ptr::null_mut(), ptr::null_mut());
+        let owned = |s: *mut c_char| {
+            let copy = text(s).into_owned();
+            unsafe { adan_str_free(s) };
+            copy
+        };
+        let error = unsafe { adan_process_run(cmd, args, &mut status, &mut stdout, &mut stderr) };
+        unsafe {
+            adan_vec_free_strings(args);
+            adan_vec_free(args);
+            adan_str_free(cmd);
+        }
+
+        if !error.is_null() {
+            return Err(owned(error));
+        }
+        Ok(Output { status, stdout: owned(stdout), stderr: owned(stderr) })
+    }
+
+    #[test]
+    fn run_captures_status_and_output() {
+        let output = run("sh", &["-c", "echo out; echo err >&2; exit 3"]).unwrap();
+        assert_eq!((output.status, output.stdout.as_str(), output.stderr.as_str()), (3, "out\n", "err\n"));
+        let output = run("sh", &["-c", "printf '%s|' \"$@\"", "sh", "a b", ""]).unwrap();
+        assert_eq!(output.stdout, "a b||");
+    }
+
+    #[test]
+    fn run_without_arguments_and_killed_commands() {
+        let (mut status, mut stdout, mut stderr) = (0, ptr::null_mut(), ptr::null_mut());
+        let cmd = from_string("true".to_string());
+        unsafe {
+            assert!(adan_process_run(cmd, ptr::null(), &mut status, &mut stdout, &mut stderr).is_null());
+            assert_eq!(status, 0);
+            adan_str_free(stdout);
+            adan_str_free(stderr);
+            adan_str_free(cmd);
+        }
+        assert_eq!(run("sh", &["-c", "kill -9 $$"]).unwrap().status, -1);
+    }
+
+    #[test]
+    fn commands_that_dont_exist_are_errors() {
+        let error = run("adan-no-such-command", &[]).err().expect("the command can't start");
+        assert!(error.starts_with("adan-no-such-command: "), "{}", error);
+    }
+}
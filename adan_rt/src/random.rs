@@ -0,0 +1,138 @@
+use std::ptr;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// xoshiro256**, small and fast with a period far beyond anything a program will use. The same
+// seed gives the same numbers on every platform, so simulations can be replayed.
+struct Xoshiro256 {
+    state: [u64; 4],
+}
+
+impl Xoshiro256 {
+    // The state is filled with splitmix64, which also turns seeds like 0 and 1 into good states.
+    fn seeded(seed: u64) -> Self {
+        let mut x = seed;
+        let mut next = || {
+            x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        };
+        Xoshiro256 { state: [next(), next(), next(), next()] }
+    }
+
+    fn next(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    // Uniform in 0..n without modulo bias, Lemire's multiply and reject.
+    fn below(&mut self, n: u64) -> u64 {
+        let threshold = n.wrapping_neg() % n;
+        loop {
+            let wide = u128::from(self.next()) * u128::from(n);
+            if wide as u64 >= threshold {
+                return (wide >> 64) as u64;
+            }
+        }
+    }
+}
+
+// Seeded from the clock until the program calls adan_random_seed.
+static GENERATOR: Mutex<Option<Xoshiro256>> = Mutex::new(None);
+
+fn with_generator<T>(f: impl FnOnce(&mut Xoshiro256) -> T) -> T {
+    let mut generator = GENERATOR.lock().unwrap_or_else(|e| e.into_inner());
+    let generator = generator.get_or_insert_with(|| {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64);
+        Xoshiro256::seeded(nanos)
+    });
+    f(generator)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_random_seed(seed: i64) {
+    *GENERATOR.lock().unwrap_or_else(|e| e.into_inner()) = Some(Xoshiro256::seeded(seed as u64));
+}
+
+// Uniform in low..=high, the compiler has checked that low <= high.
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_random_int(low: i64, high: i64) -> i64 {
+    let span = high.wrapping_sub(low) as u64;
+    let offset = with_generator(|g| if span == u64::MAX { g.next() } else { g.below(span + 1) });
+    low.wrapping_add(offset as i64)
+}
+
+// Uniform in [0, 1), from the top 53 bits so every value is a multiple of 2^-53.
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_random_float() -> f64 {
+    with_generator(|g| (g.next() >> 11) as f64 / (1u64 << 53) as f64)
+}
+
+// Fisher-Yates over `count` elements of `elem_size` bytes laid out one after the other, which is
+// how both arrays and vectors keep them.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_random_shuffle(items: *mut u8, elem_size: i64, count: i64) {
+    let elem_size = elem_size as usize;
+    with_generator(|g| {
+        for i in (1..count.max(0) as u64).rev() {
+            let j = g.below(i + 1);
+            if i != j {
+                unsafe { ptr::swap_nonoverlapping(items.add(i as usize * elem_size), items.add(j as usize * elem_size), elem_size) };
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_seed_always_gives_the_same_numbers() {
+        // Pinned, so a change to the generator or the seeding breaks replays loudly.
+        let mut g = Xoshiro256::seeded(42);
+        assert_eq!([g.next(), g.next(), g.next()], [1_546_998_764_402_558_742, 6_990_951_692_964_543_102, 12_544_586_762_248_559_009]);
+        let (mut a, mut b) = (Xoshiro256::seeded(0), Xoshiro256::seeded(1));
+        assert_ne!(a.next(), b.next());
+    }
+
+    #[test]
+    fn below_stays_below() {
+        let mut g = Xoshiro256::seeded(7);
+        for n in [1, 2, 3, 10, u64::MAX] {
+            for _ in 0..1000 {
+                assert!(g.below(n) < n);
+            }
+        }
+    }
+
+    // The only test that touches the global generator, tests run in parallel.
+    #[test]
+    fn reseeding_replays_ints_floats_and_shuffles() {
+        let draw = || {
+            adan_random_seed(2026);
+            let ints: Vec<i64> = (0..100).map(|_| adan_random_int(-5, 5)).collect();
+            let floats: Vec<f64> = (0..100).map(|_| adan_random_float()).collect();
+            let mut items: Vec<i32> = (0..20).collect();
+            unsafe { adan_random_shuffle(items.as_mut_ptr().cast(), size_of::<i32>() as i64, items.len() as i64) };
+            (ints, floats, items, adan_random_int(i64::MIN, i64::MAX))
+        };
+        let (ints, floats, items, wide) = draw();
+        assert!(ints.iter().all(|x| (-5..=5).contains(x)));
+        assert!(floats.iter().all(|x| (0.0..1.0).contains(x)));
+        let mut sorted = items.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..20).collect::<Vec<_>>());
+        assert_eq!(draw(), (ints, floats, items, wide));
+    }
+}
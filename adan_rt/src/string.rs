@@ -0,0 +1,416 @@
+use std::ffi::c_char;
+use std::ptr;
+
+use crate::alloc::{adan_alloc, adan_free};
+use crate::vector::AdanVec;
+
+// ADAN strings point at their bytes, which are NUL terminated and preceded by a header holding
+// their length. Literals are constant data marked STATIC, strings built at runtime live in
+// `adan_alloc` blocks and go back through `adan_str_free`. Null is the empty string.
+#[repr(C)]
+struct Header {
+    len: i64,
+    kind: i64,
+}
+
+pub(crate) const HEADER: usize = size_of::<Header>();
+const HEAP: i64 = 0;
+const STATIC: i64 = -1;
+
+fn header(s: *const c_char) -> Header {
+    unsafe { (s as *const u8).sub(HEADER).cast::<Header>().read_unaligned() }
+}
+
+pub(crate) unsafe fn bytes<'a>(s: *const c_char) -> &'a [u8] {
+    if s.is_null() {
+        return &[];
+    }
+    unsafe { std::slice::from_raw_parts(s as *const u8, header(s).len as usize) }
+}
+
+// Copies `bytes` into a new runtime string.
+pub(crate) fn from_bytes(bytes: &[u8]) -> *mut c_char {
+    let block = adan_alloc((HEADER + bytes.len() + 1) as i64);
+    unsafe {
+        block.cast::<Header>().write(Header { len: bytes.len() as i64, kind: HEAP });
+        let data = block.add(HEADER);
+        ptr::copy_nonoverlapping(bytes.as_ptr(), data, bytes.len());
+        data as *mut c_char
+    }
+}
+
+// Header, bytes and NUL of a string kept in memory someone else owns, like a map's copies of its
+// keys. The string itself starts HEADER bytes in, and freeing it does nothing.
+pub(crate) fn static_layout(bytes: &[u8]) -> Vec<u8> {
+    let mut layout = Vec::with_capacity(HEADER + bytes.len() + 1);
+    layout.extend_from_slice(&(bytes.len() as i64).to_ne_bytes());
+    layout.extend_from_slice(&STATIC.to_ne_bytes());
+    layout.extend_from_slice(bytes);
+    layout.push(0);
+    layout
+}
+
+pub(crate) fn from_string(s: String) -> *mut c_char {
+    from_bytes(s.as_bytes())
+}
+
+pub(crate) fn text<'a>(s: *const c_char) -> std::borrow::Cow<'a, str> {
+    String::from_utf8_lossy(unsafe { bytes(s) })
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_str_len(s: *const c_char) -> i64 {
+    unsafe { bytes(s) }.len() as i64
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_str_eq(lhs: *const c_char, rhs: *const c_char) -> bool {
+    unsafe { bytes(lhs) == bytes(rhs) }
+}
+
+// Byte wise ordering: negative, zero or positive like strcmp.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_str_cmp(lhs: *const c_char, rhs: *const c_char) -> i32 {
+    match unsafe { bytes(lhs).cmp(bytes(rhs)) } {
+        std::cmp::Ordering::Less => -1,
+        std::cmp::Ordering::Equal => 0,
+        std::cmp::Ordering::Greater => 1,
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_str_dup(s: *const c_char) -> *mut c_char {
+    from_bytes(unsafe { bytes(s) })
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_str_concat(lhs: *const c_char, rhs: *const c_char) -> *mut c_char {
+    let joined = unsafe { [bytes(lhs), bytes(rhs)] }.concat();
+    from_bytes(&joined)
+}
+
+// The `count` strings at `pieces` as one, which is how interpolated literals are built.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_str_join(pieces: *const *const c_char, count: i64) -> *mut c_char {
+    let pieces = unsafe { std::slice::from_raw_parts(pieces, count as usize) };
+    let joined: Vec<u8> = pieces.iter().flat_map(|p| unsafe { bytes(*p) }.iter().copied()).collect();
+    from_bytes(&joined)
+}
+
+// Fills each `{}` in `format` with the next of the `count` strings at `pieces` and stores the result
+// in `out`. The format is often only known at runtime, so a count that doesn't match is reported
+// here: null on success, otherwise the error and `out` is untouched.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_str_format(format: *const c_char, pieces: *const *const c_char, count: i64, out: *mut *mut c_char) -> *mut c_char {
+    let (format, pieces) = unsafe { (bytes(format), std::slice::from_raw_parts(pieces, count as usize)) };
+    let placeholders = format.windows(2).filter(|w| *w == b"{}").count();
+    if placeholders != pieces.len() {
+        return from_string(format!("format has {} {{}} but {} arguments", placeholders, pieces.len()));
+    }
+
+    let mut rest = format;
+    let mut formatted = Vec::with_capacity(rest.len());
+    for piece in pieces {
+        let p = position(rest, b"{}").expect("placeholders were counted above");
+        formatted.extend_from_slice(&rest[..p]);
+        formatted.extend_from_slice(unsafe { bytes(*piece) });
+        rest = &rest[p + 2..];
+    }
+
+    formatted.extend_from_slice(rest);
+    unsafe { out.write(from_bytes(&formatted)) };
+    ptr::null_mut()
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_str_from_char(c: u8) -> *mut c_char {
+    from_bytes(&[c])
+}
+
+// Literals are never freed, so every string can go through here.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_str_free(s: *mut c_char) {
+    if s.is_null() || header(s).kind == STATIC {
+        return;
+    }
+    unsafe { adan_free((s as *mut u8).sub(HEADER)) };
+}
+
+// s[low..high] with both ends included. The compiler checks the bounds, when its checks are off
+// the slice is cut to the bytes there are rather than panicking in here.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_str_slice(s: *const c_char, low: i64, high: i64) -> *mut c_char {
+    let bytes = unsafe { bytes(s) };
+    let (start, end) = crate::vector::clamp_range(low, high, bytes.len());
+    from_bytes(&bytes[start..end])
+}
+
+fn position(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    if needle.is_empty() {
+        return Some(0);
+    }
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_str_contains(s: *const c_char, needle: *const c_char) -> bool {
+    unsafe { position(bytes(s), bytes(needle)) }.is_some()
+}
+
+// Index of the first occurrence of `needle`, or -1.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_str_find(s: *const c_char, needle: *const c_char) -> i64 {
+    unsafe { position(bytes(s), bytes(needle)) }.map_or(-1, |p| p as i64)
+}
+
+// Replaces every occurrence of `from`, an empty `from` leaves the string as it is.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_str_replace(s: *const c_char, from: *const c_char, to: *const c_char) -> *mut c_char {
+    let (mut rest, from, to) = unsafe { (bytes(s), bytes(from), bytes(to)) };
+    if from.is_empty() {
+        return from_bytes(rest);
+    }
+
+    let mut replaced = Vec::with_capacity(rest.len());
+    while let Some(p) = position(rest, from) {
+        replaced.extend_from_slice(&rest[..p]);
+        replaced.extend_from_slice(to);
+        rest = &rest[p + from.len()..];
+    }
+    replaced.extend_from_slice(rest);
+    from_bytes(&replaced)
+}
+
+// Vector of the pieces between separators, an empty separator splits into single characters.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_str_split(s: *const c_char, separator: *const c_char) -> *mut AdanVec {
+    let (mut rest, separator) = unsafe { (bytes(s), bytes(separator)) };
+    let mut pieces: Vec<*mut c_char> = Vec::new();
+    if separator.is_empty() {
+        pieces.extend(rest.iter().map(|c| from_bytes(&[*c])));
+    } else {
+        while let Some(p) = position(rest, separator) {
+            pieces.push(from_bytes(&rest[..p]));
+            rest = &rest[p + separator.len()..];
+        }
+        pieces.push(from_bytes(rest));
+    }
+
+    AdanVec::from_strings(&pieces)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_str_trim(s: *const c_char) -> *mut c_char {
+    from_bytes(unsafe { bytes(s) }.trim_ascii())
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_str_to_upper(s: *const c_char) -> *mut c_char {
+    from_bytes(&unsafe { bytes(s) }.to_ascii_uppercase())
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_str_to_lower(s: *const c_char) -> *mut c_char {
+    from_bytes(&unsafe { bytes(s) }.to_ascii_lowercase())
+}
+
+// T'Image(x), the shortest text that T'Value reads back to the same x.
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_str_image_i64(x: i64) -> *mut c_char {
+    from_string(x.to_string())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_str_image_u64(x: u64) -> *mut c_char {
+    from_string(x.to_string())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_str_image_f32(x: f32) -> *mut c_char {
+    from_string(x.to_string())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_str_image_f64(x: f64) -> *mut c_char {
+    from_string(x.to_string())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_str_image_bool(x: bool) -> *mut c_char {
+    from_bytes(if x { b"True" } else { b"False" })
+}
+
+// T'Value(s) stores what `s` spells into `out`, ignoring surrounding whitespace. False when it
+// isn't a valid T.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_str_value_i64(s: *const c_char, out: *mut i64) -> bool {
+    parse_into(s, out)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_str_value_u64(s: *const c_char, out: *mut u64) -> bool {
+    parse_into(s, out)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_str_value_f64(s: *const c_char, out: *mut f64) -> bool {
+    parse_into(s, out)
+}
+
+// Any capitalization, like Ada.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_str_value_bool(s: *const c_char, out: *mut bool) -> bool {
+    let value = match text(s).trim().to_ascii_lowercase().as_str() {
+        "true" => true,
+        "false" => false,
+        _ => return false,
+    };
+    unsafe { out.write(value) };
+    true
+}
+
+fn parse_into<T: std::str::FromStr>(s: *const c_char, out: *mut T) -> bool {
+    match text(s).trim().parse() {
+        Ok(value) => {
+            unsafe { out.write(value) };
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector::{adan_vec_free, adan_vec_free_strings};
+
+    // Strings are bytes, indexes and lengths count bytes and "é" is two of them.
+    const TEXT: &str = "né, où";
+
+    fn s(text: &str) -> *mut c_char {
+        from_string(text.to_string())
+    }
+
+    fn owned(result: *mut c_char) -> String {
+        let copy = text(result).into_owned();
+        unsafe { adan_str_free(result) };
+        copy
+    }
+
+    #[test]
+    fn slice_counts_bytes() {
+        let t = s(TEXT);
+        unsafe {
+            assert_eq!(adan_str_len(t), 8);
+            assert_eq!(owned(adan_str_slice(t, 1, 2)), "é");
+            assert_eq!(owned(adan_str_slice(t, 5, 7)), "où");
+            // high < low is the empty string, even right after the end.
+            assert_eq!(owned(adan_str_slice(t, 8, 7)), "");
+            adan_str_free(t);
+        }
+    }
+
+    #[test]
+    fn slice_out_of_bounds_is_cut_to_the_string() {
+        // What --checks=off lets through.
+        let t = s("abc");
+        unsafe {
+            assert_eq!(owned(adan_str_slice(t, 1, 10)), "bc");
+            assert_eq!(owned(adan_str_slice(t, -5, 0)), "a");
+            assert_eq!(owned(adan_str_slice(t, 7, 9)), "");
+            assert_eq!(owned(adan_str_slice(t, 0, i64::MAX)), "abc");
+            adan_str_free(t);
+        }
+    }
+
+    #[test]
+    fn find_gives_the_byte_offset() {
+        let (t, needle, missing, empty) = (s(TEXT), s("où"), s("ou"), s(""));
+        unsafe {
+            assert_eq!(adan_str_find(t, needle), 5);
+            assert_eq!(adan_str_find(t, missing), -1);
+            assert_eq!(adan_str_find(t, empty), 0);
+            assert!(adan_str_contains(t, needle));
+            for p in [t, needle, missing, empty] {
+                adan_str_free(p);
+            }
+        }
+    }
+
+    #[test]
+    fn split_on_multibyte_separators() {
+        let (t, separator) = (s("aébéc"), s("é"));
+        unsafe {
+            let pieces = adan_str_split(t, separator);
+            let found: Vec<_> = (*pieces).strings().into_iter().map(|p| text(p).into_owned()).collect();
+            assert_eq!(found, ["a", "b", "c"]);
+            adan_vec_free_strings(pieces);
+            adan_vec_free(pieces);
+            adan_str_free(t);
+            adan_str_free(separator);
+        }
+    }
+
+    #[test]
+    fn split_keeps_empty_pieces_at_the_edges() {
+        let (t, separator) = (s(",a,,"), s(","));
+        unsafe {
+            let pieces = adan_str_split(t, separator);
+            let found: Vec<_> = (*pieces).strings().into_iter().map(|p| text(p).into_owned()).collect();
+            assert_eq!(found, ["", "a", "", ""]);
+            adan_vec_free_strings(pieces);
+            adan_vec_free(pieces);
+            adan_str_free(t);
+            adan_str_free(separator);
+        }
+    }
+
+    #[test]
+    fn replace_every_occurrence() {
+        let (t, from, to, empty) = (s("ééé"), s("é"), s("e"), s(""));
+        unsafe {
+            assert_eq!(owned(adan_str_replace(t, from, to)), "eee");
+            assert_eq!(owned(adan_str_replace(t, from, empty)), "");
+            assert_eq!(owned(adan_str_replace(t, empty, to)), "ééé");
+            for p in [t, from, to, empty] {
+                adan_str_free(p);
+            }
+        }
+    }
+
+    #[test]
+    fn case_changes_leave_non_ascii_alone() {
+        let t = s("Émile");
+        unsafe {
+            assert_eq!(owned(adan_str_to_upper(t)), "ÉMILE");
+            assert_eq!(owned(adan_str_to_lower(t)), "Émile");
+            adan_str_free(t);
+        }
+    }
+
+    #[test]
+    fn format_checks_the_counts() {
+        let pieces = [s("a") as *const c_char, s("b")];
+        let (format, short) = (s("{}-{}"), s("{}"));
+        let mut out = ptr::null_mut();
+        unsafe {
+            assert!(adan_str_format(format, pieces.as_ptr(), 2, &mut out).is_null());
+            assert_eq!(owned(out), "a-b");
+            assert_eq!(owned(adan_str_format(short, pieces.as_ptr(), 2, &mut out)), "format has 1 {} but 2 arguments");
+            for p in pieces.into_iter().chain([format as *const c_char, short]) {
+                adan_str_free(p as *mut c_char);
+            }
+        }
+    }
+
+    #[test]
+    fn literals_and_null_are_never_freed() {
+        let layout = static_layout(b"lit");
+        let literal = unsafe { layout.as_ptr().add(HEADER) } as *mut c_char;
+        unsafe {
+            adan_str_free(literal);
+            adan_str_free(ptr::null_mut());
+        }
+        assert_eq!(text(literal), "lit");
+    }
+}
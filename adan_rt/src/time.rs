@@ -0,0 +1,92 @@
+use std::ffi::c_char;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::string::from_string;
+
+// Durations are i64 nanoseconds so programs can add and subtract them like any number.
+static START: OnceLock<Instant> = OnceLock::new();
+
+// Nanoseconds on a monotonic clock that starts with the first call, only differences mean
+// anything.
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_time_now() -> i64 {
+    START.get_or_init(Instant::now).elapsed().as_nanos() as i64
+}
+
+// Milliseconds since 1970-01-01T00:00:00Z, negative before it.
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_time_unix_ms() -> i64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(since) => since.as_millis() as i64,
+        Err(e) => -(e.duration().as_millis() as i64),
+    }
+}
+
+// A negative time doesn't sleep at all.
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_time_sleep(ms: i64) {
+    thread::sleep(Duration::from_millis(ms.max(0) as u64));
+}
+
+// Year, month and day of the `days`th day after 1970-01-01 in the proleptic Gregorian calendar,
+// Howard Hinnant's civil_from_days.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+// A Unix time in milliseconds as 2026-10-18T09:30:00.250Z, always in UTC.
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_time_iso8601(unix_ms: i64) -> *mut c_char {
+    const DAY_MS: i64 = 86_400_000;
+    let (year, month, day) = civil_from_days(unix_ms.div_euclid(DAY_MS));
+    let ms_of_day = unix_ms.rem_euclid(DAY_MS);
+    let (hour, minute, second, ms) = (ms_of_day / 3_600_000, ms_of_day / 60_000 % 60, ms_of_day / 1000 % 60, ms_of_day % 1000);
+    from_string(format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z", year, month, day, hour, minute, second, ms))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::string::{adan_str_free, text};
+
+    fn iso8601(unix_ms: i64) -> String {
+        let formatted = adan_time_iso8601(unix_ms);
+        let copy = text(formatted).into_owned();
+        unsafe { adan_str_free(formatted) };
+        copy
+    }
+
+    #[test]
+    fn iso8601_is_utc_with_milliseconds() {
+        assert_eq!(iso8601(0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(iso8601(1_792_315_800_250), "2026-10-18T09:30:00.250Z");
+        assert_eq!(iso8601(951_782_400_000), "2000-02-29T00:00:00.000Z");
+    }
+
+    #[test]
+    fn iso8601_before_the_epoch() {
+        assert_eq!(iso8601(-1), "1969-12-31T23:59:59.999Z");
+        // 1900 isn't a leap year, the day after February 28th is March 1st.
+        assert_eq!(iso8601(-2_203_891_200_000), "1900-03-01T00:00:00.000Z");
+        assert_eq!(iso8601(-2_203_891_200_001), "1900-02-28T23:59:59.999Z");
+    }
+
+    #[test]
+    fn the_monotonic_clock_only_goes_forward() {
+        let before = adan_time_now();
+        adan_time_sleep(2);
+        adan_time_sleep(-5);
+        assert!(adan_time_now() - before >= 2_000_000);
+    }
+}
@@ -0,0 +1,300 @@
+use std::ffi::c_char;
+use std::ptr;
+
+use crate::string::{adan_str_dup, adan_str_free};
+
+// Growable vector of fixed size elements. The compiler knows the element type, the runtime only
+// knows its size, so elements are copied in and out as raw bytes.
+pub struct AdanVec {
+    elem_size: usize,
+    len: usize,
+    data: Vec<u8>,
+}
+
+impl AdanVec {
+    pub(crate) fn with_bytes(elem_size: usize, len: usize, bytes: &[u8]) -> *mut AdanVec {
+        Box::into_raw(Box::new(AdanVec { elem_size, len, data: bytes.to_vec() }))
+    }
+
+    // A new vector owning `strings`.
+    pub(crate) fn from_strings(strings: &[*mut c_char]) -> *mut AdanVec {
+        let memory: Vec<u8> = strings.iter().flat_map(|p| (*p as usize).to_ne_bytes()).collect();
+        AdanVec::with_bytes(size_of::<*mut c_char>(), strings.len(), &memory)
+    }
+
+    fn element(&mut self, index: usize) -> *mut u8 {
+        unsafe { self.data.as_mut_ptr().add(index * self.elem_size) }
+    }
+
+    // Elements of a vector of strings, which stay the vector's.
+    pub(crate) fn strings(&self) -> Vec<*const c_char> {
+        self.data.chunks_exact(size_of::<*const c_char>())
+            .map(|chunk| unsafe { chunk.as_ptr().cast::<*const c_char>().read_unaligned() })
+            .collect()
+    }
+}
+
+// `low..high` with both ends included as a range of a sequence of `len` items. The compiler
+// checks the bounds before calling the runtime, when its checks are off a bad range is cut to the
+// items there are instead of panicking in the runtime.
+pub(crate) fn clamp_range(low: i64, high: i64, len: usize) -> (usize, usize) {
+    let start = low.clamp(0, len as i64);
+    let end = high.saturating_add(1).clamp(start, len as i64);
+    (start as usize, end as usize)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn adan_vec_new(elem_size: i64) -> *mut AdanVec {
+    AdanVec::with_bytes(elem_size as usize, 0, &[])
+}
+
+// Builds a vector from `count` elements laid out like an array, used for `[1, 2, 3]` literals.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_vec_from(elem_size: i64, items: *const u8, count: i64) -> *mut AdanVec {
+    let byte_len = elem_size as usize * count as usize;
+    if byte_len == 0 {
+        return AdanVec::with_bytes(elem_size as usize, count as usize, &[]);
+    }
+
+    let bytes = unsafe { std::slice::from_raw_parts(items, byte_len) };
+    AdanVec::with_bytes(elem_size as usize, count as usize, bytes)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_vec_clone(vec: *const AdanVec) -> *mut AdanVec {
+    let vec = unsafe { &*vec };
+    AdanVec::with_bytes(vec.elem_size, vec.len, &vec.data)
+}
+
+// Null is accepted so scope cleanup can free variables that were never initialized.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_vec_free(vec: *mut AdanVec) {
+    if !vec.is_null() {
+        drop(unsafe { Box::from_raw(vec) });
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_vec_len(vec: *const AdanVec) -> i64 {
+    unsafe { (*vec).len as i64 }
+}
+
+// The compiler checks `index` against the length before calling any of the functions below. With
+// its checks off, insert appends past the end and remove leaves a zeroed element in `out`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_vec_at(vec: *mut AdanVec, index: i64) -> *mut u8 {
+    unsafe { (*vec).element(index as usize) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_vec_push(vec: *mut AdanVec, item: *const u8) {
+    let vec = unsafe { &mut *vec };
+    let bytes = unsafe { std::slice::from_raw_parts(item, vec.elem_size) };
+    vec.data.extend_from_slice(bytes);
+    vec.len += 1;
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_vec_pop(vec: *mut AdanVec, out: *mut u8) {
+    let vec = unsafe { &mut *vec };
+    // Like remove, an empty vector gives a zeroed element when checks are off.
+    if vec.len == 0 {
+        unsafe { ptr::write_bytes(out, 0, vec.elem_size) };
+        return;
+    }
+    vec.len -= 1;
+    unsafe { ptr::copy_nonoverlapping(vec.element(vec.len), out, vec.elem_size) };
+    vec.data.truncate(vec.len * vec.elem_size);
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_vec_insert(vec: *mut AdanVec, index: i64, item: *const u8) {
+    let vec = unsafe { &mut *vec };
+    let offset = index.clamp(0, vec.len as i64) as usize * vec.elem_size;
+    let bytes = unsafe { std::slice::from_raw_parts(item, vec.elem_size) };
+    vec.data.splice(offset..offset, bytes.iter().copied());
+    vec.len += 1;
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_vec_remove(vec: *mut AdanVec, index: i64, out: *mut u8) {
+    let vec = unsafe { &mut *vec };
+    if index < 0 || index as usize >= vec.len {
+        unsafe { ptr::write_bytes(out, 0, vec.elem_size) };
+        return;
+    }
+    let offset = index as usize * vec.elem_size;
+    unsafe { ptr::copy_nonoverlapping(vec.element(index as usize), out, vec.elem_size) };
+    vec.data.drain(offset..offset + vec.elem_size);
+    vec.len -= 1;
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_vec_clear(vec: *mut AdanVec) {
+    let vec = unsafe { &mut *vec };
+    vec.data.clear();
+    vec.len = 0;
+}
+
+// Copies elements `low..high` (inclusive) into a new vector, `high < low` gives an empty one.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_vec_slice(vec: *const AdanVec, low: i64, high: i64) -> *mut AdanVec {
+    let vec = unsafe { &*vec };
+    let (start, end) = clamp_range(low, high, vec.len);
+    AdanVec::with_bytes(vec.elem_size, end - start, &vec.data[start * vec.elem_size..end * vec.elem_size])
+}
+
+// Vectors of strings own their elements, the compiler calls these when it frees, clears or
+// copies one.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_vec_free_strings(vec: *mut AdanVec) {
+    if vec.is_null() {
+        return;
+    }
+
+    let vec = unsafe { &mut *vec };
+    for i in 0..vec.len {
+        unsafe { adan_str_free(vec.element(i).cast::<*mut c_char>().read_unaligned()) };
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn adan_vec_dup_strings(vec: *mut AdanVec) {
+    let vec = unsafe { &mut *vec };
+    for i in 0..vec.len {
+        let element = vec.element(i).cast::<*mut c_char>();
+        unsafe { element.write_unaligned(adan_str_dup(element.read_unaligned())) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push(vec: *mut AdanVec, x: i64) {
+        unsafe { adan_vec_push(vec, (&x as *const i64).cast()) };
+    }
+
+    fn items(vec: *mut AdanVec) -> Vec<i64> {
+        (0..unsafe { adan_vec_len(vec) }).map(|i| unsafe { adan_vec_at(vec, i).cast::<i64>().read_unaligned() }).collect()
+    }
+
+    #[test]
+    fn push_grows_past_the_first_allocation() {
+        let vec = adan_vec_new(8);
+        for x in 0..1000 {
+            push(vec, x);
+        }
+        assert_eq!(items(vec), (0..1000).collect::<Vec<_>>());
+        unsafe { adan_vec_free(vec) };
+    }
+
+    #[test]
+    fn insert_and_remove_shift_the_rest() {
+        let vec = adan_vec_new(8);
+        for x in [1, 3] {
+            push(vec, x);
+        }
+        let mut out = 0i64;
+        unsafe {
+            adan_vec_insert(vec, 0, (&0i64 as *const i64).cast());
+            adan_vec_insert(vec, 2, (&2i64 as *const i64).cast());
+            // The end is a valid insert position, one past the last element.
+            adan_vec_insert(vec, 4, (&4i64 as *const i64).cast());
+            assert_eq!(items(vec), [0, 1, 2, 3, 4]);
+
+            adan_vec_remove(vec, 1, (&mut out as *mut i64).cast());
+            assert_eq!(out, 1);
+            adan_vec_pop(vec, (&mut out as *mut i64).cast());
+            assert_eq!(out, 4);
+            assert_eq!(items(vec), [0, 2, 3]);
+            adan_vec_free(vec);
+        }
+    }
+
+    #[test]
+    fn slice_includes_both_ends() {
+        let vec = adan_vec_new(8);
+        for x in 0..5 {
+            push(vec, x);
+        }
+        unsafe {
+            let middle = adan_vec_slice(vec, 1, 3);
+            assert_eq!(items(middle), [1, 2, 3]);
+            // high < low is the empty range, even right after the last element.
+            let empty = adan_vec_slice(vec, 5, 4);
+            assert_eq!(items(empty), []);
+            for v in [middle, empty, vec] {
+                adan_vec_free(v);
+            }
+        }
+    }
+
+    #[test]
+    fn out_of_bounds_ranges_are_cut_to_the_vector() {
+        // What --checks=off lets through.
+        let vec = adan_vec_new(8);
+        for x in 0..3 {
+            push(vec, x);
+        }
+        let mut out = -1i64;
+        unsafe {
+            let tail = adan_vec_slice(vec, 1, 10);
+            assert_eq!(items(tail), [1, 2]);
+            let none = adan_vec_slice(vec, 5, 9);
+            assert_eq!(items(none), []);
+
+            adan_vec_insert(vec, 10, (&3i64 as *const i64).cast());
+            assert_eq!(items(vec), [0, 1, 2, 3]);
+            adan_vec_remove(vec, 4, (&mut out as *mut i64).cast());
+            assert_eq!(out, 0);
+            adan_vec_remove(vec, -1, (&mut out as *mut i64).cast());
+            assert_eq!(items(vec), [0, 1, 2, 3]);
+
+            adan_vec_clear(vec);
+            out = -1;
+            adan_vec_pop(vec, (&mut out as *mut i64).cast());
+            assert_eq!(out, 0);
+            assert_eq!(items(vec), []);
+            for v in [tail, none, vec] {
+                adan_vec_free(v);
+            }
+        }
+    }
+
+    #[test]
+    fn clear_keeps_the_element_size() {
+        let vec = adan_vec_new(8);
+        push(vec, 7);
+        unsafe { adan_vec_clear(vec) };
+        assert_eq!(items(vec), []);
+        push(vec, 9);
+        assert_eq!(items(vec), [9]);
+        unsafe { adan_vec_free(vec) };
+    }
+
+    #[test]
+    fn copies_of_string_vectors_own_their_strings() {
+        let vec = AdanVec::from_strings(&[crate::string::from_string("a".to_string())]);
+        unsafe {
+            let copy = adan_vec_clone(vec);
+            adan_vec_dup_strings(copy);
+            let (original, copied) = ((*vec).strings()[0], (*copy).strings()[0]);
+            assert_ne!(original, copied);
+
+            adan_vec_free_strings(vec);
+            adan_vec_free(vec);
+            assert_eq!(crate::string::text(copied), "a");
+            adan_vec_free_strings(copy);
+            adan_vec_free(copy);
+        }
+    }
+
+    #[test]
+    fn null_vectors_are_ignored_when_freed() {
+        unsafe {
+            adan_vec_free_strings(ptr::null_mut());
+            adan_vec_free(ptr::null_mut());
+        }
+    }
+}
@@ -54,3 +54,32 @@ pub fn initialize() {
     }
 }
 */
+
+use clap::{Arg, Command};
+use std::path::PathBuf;
+
+// What the compiler was asked to do, read from the command line.
+pub struct Options {
+    pub input: PathBuf,
+    pub checks: bool, // Runtime checks, `pragma Suppress` turns them off more selectively.
+}
+
+pub fn initialize() -> Options {
+    let matches = Command::new("adan")
+        .about("THE ADAN CLI: Written by the Cappucina Team.")
+        .arg(Arg::new("file")
+            .help("ADAN source file to compile")
+            .default_value("./examples/hello_adan.adn"))
+        .arg(Arg::new("checks")
+            .long("checks")
+            .value_name("on|off")
+            .value_parser(["on", "off"])
+            .default_value("on")
+            .help("Check for overflow, division by zero, out of range values and bad indices at runtime"))
+        .get_matches();
+
+    Options {
+        input: PathBuf::from(matches.get_one::<String>("file").expect("file has a default")),
+        checks: matches.get_one::<String>("checks").is_some_and(|c| c == "on"),
+    }
+}
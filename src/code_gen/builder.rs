@@ -5,43 +5,101 @@ use inkwell::{
     builder::Builder,
     context::Context,
     module::Module,
-    values::{BasicValueEnum, PointerValue},
-    types::{FloatType, IntType, ArrayType, StructType, PointerType, BasicTypeEnum},
+    values::{BasicMetadataValueEnum, BasicValueEnum, PointerValue},
+    types::{FloatType, IntType, PointerType, BasicMetadataTypeEnum, BasicType, BasicTypeEnum},
 };
-use crate::parser::ast::FunctionDecl;
+use crate::parser::ast::{FunctionDecl, TypeName};
+use crate::code_gen::managed;
+use crate::code_gen::types::{type_display, TypeDecl};
+use crate::native::map;
 use inkwell::AddressSpace;
-use crate::lexer::token::Types;
+use crate::lexer::token::{Span, Types};
 
 pub struct CodeGenContext<'ctx> {
     pub context: &'ctx Context,
     pub builder: Builder<'ctx>,
     pub module: Module<'ctx>,
 
+    pub f32_type: FloatType<'ctx>,
     pub f64_type: FloatType<'ctx>,
     pub bool_type: IntType<'ctx>,
     pub i8_type: IntType<'ctx>,
     pub i32_type: IntType<'ctx>,
     pub i64_type: IntType<'ctx>,
+    // LLVM integers have no sign, unsigned types are told apart by their TypeName (is_unsigned).
     pub u8_type: IntType<'ctx>,
     pub u32_type: IntType<'ctx>,
     pub u64_type: IntType<'ctx>,
-    pub array_of_i32: ArrayType<'ctx>,
-    pub my_object_type: StructType<'ctx>,
     pub string_type: PointerType<'ctx>,
 
     pub variables: HashMap<String, PointerValue<'ctx>>,
+    pub variable_types: HashMap<String, TypeName>,
+    pub types: HashMap<String, TypeDecl>,
+    pub enum_literals: HashMap<String, (String, u64)>,
+    pub known_variants: HashMap<String, String>, // Record path -> variant proven by an enclosing `case`.
+    pub owned_values: Vec<(PointerValue<'ctx>, TypeName)>, // Vector, map, string and string-holding record or array slots of the current function, freed on return.
     pub modules: HashMap<String, ModuleValue<'ctx>>,
+
+    pub source_name: String,
+    pub checks: bool,               // `--checks=off` turns every suppressible check off.
+    pub suppressed: Vec<Check>,     // Checks turned off by `pragma Suppress` in the enclosing blocks.
+    pub return_type: TypeName,      // Declared return type of the function being generated.
+    pub call_span: Span,            // Location of the native call being generated, for the checks it builds.
+}
+
+// Runtime checks that can be turned off for code that has been proven not to need them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Check {
+    Overflow,
+    Division,
+    Range,
+    Index,
+    Discriminant,
+}
+
+impl Check {
+    // Checks named by a `pragma Suppress`, using Ada's names.
+    pub fn from_name(name: &str) -> Option<&'static [Check]> {
+        match name {
+            "Overflow_Check" => Some(&[Check::Overflow]),
+            "Division_Check" => Some(&[Check::Division]),
+            "Range_Check" => Some(&[Check::Range]),
+            "Index_Check" => Some(&[Check::Index]),
+            "Discriminant_Check" => Some(&[Check::Discriminant]),
+            "All_Checks" => Some(&[Check::Overflow, Check::Division, Check::Range, Check::Index, Check::Discriminant]),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone)]
 pub enum NativeFunc<'ctx> {
     AdanFunction(FunctionDecl),
-    NativeFn(fn(&mut CodeGenContext<'ctx>, Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx>),
+    NativeFn(fn(&mut CodeGenContext<'ctx>, Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx>, Vec<NativeParam>),
+}
+
+// A parameter of a native function. Calls are checked against these and their arguments converted
+// like an assignment before the native gets them, so a native can count on its arguments.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NativeParam {
+    Of(TypeName),
+    Optional(TypeName), // May be left off, only after the others.
+    AnyVector,
+    AnyMap,
+    // Calls the FCall codegen generates itself from the static types of the arguments, which it
+    // checks there (io printing, math, random.choice...).
+    Generated,
+}
+
+impl NativeParam {
+    pub const STRING: NativeParam = NativeParam::Of(TypeName::Builtin(Types::String));
+    pub const I64: NativeParam = NativeParam::Of(TypeName::Builtin(Types::i64));
+    pub const CHAR: NativeParam = NativeParam::Of(TypeName::Builtin(Types::Char));
 }
 
 pub struct ModuleValue<'ctx> {
     pub functions: HashMap<String, NativeFunc<'ctx>>,
-    pub variables: HashMap<String, PointerValue<'ctx>>,
+    pub variables: HashMap<String, (BasicValueEnum<'ctx>, TypeName)>, // Constants like math.PI and their type.
 }
 
 impl<'ctx> ModuleValue<'ctx> {
@@ -59,6 +117,7 @@ impl<'ctx> CodeGenContext<'ctx> {
             context,
             builder,
             module,
+            f32_type: context.f32_type(),
             f64_type: context.f64_type(),
             bool_type: context.bool_type(),
             i8_type: context.i8_type(),
@@ -68,17 +127,22 @@ impl<'ctx> CodeGenContext<'ctx> {
             u32_type: context.i32_type(),
             u64_type: context.i64_type(),
             string_type: context.i8_type().ptr_type(AddressSpace::from(0)),
-            array_of_i32: context.i32_type().array_type(10),
-            my_object_type: context.struct_type(&[
-                context.i32_type().into(),
-                context.i8_type().ptr_type(AddressSpace::from(0)).into()
-            ], false),
             variables: HashMap::new(),
+            variable_types: HashMap::new(),
+            types: HashMap::new(),
+            enum_literals: HashMap::new(),
+            known_variants: HashMap::new(),
+            owned_values: Vec::new(),
             modules: HashMap::new(),
+            source_name: name.to_string(),
+            checks: true,
+            suppressed: Vec::new(),
+            return_type: TypeName::Builtin(Types::f64),
+            call_span: Span::default(),
         }
     }
 
-    pub fn register_native_fn(&mut self, module_name: &str, fn_name: &str, func: fn(&mut CodeGenContext<'ctx>, Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx>) {
+    pub fn register_native_fn(&mut self, module_name: &str, fn_name: &str, params: &[NativeParam], func: fn(&mut CodeGenContext<'ctx>, Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx>) {
         let module = self.modules.entry(module_name.to_string())
             .or_insert(ModuleValue {
                 functions: HashMap::new(),
@@ -87,7 +151,41 @@ impl<'ctx> CodeGenContext<'ctx> {
 
         //println!("Registered function '{}' in module '{}'", fn_name, module_name);
 
-        module.functions.insert(fn_name.to_string(), NativeFunc::NativeFn(func));
+        module.functions.insert(fn_name.to_string(), NativeFunc::NativeFn(func, params.to_vec()));
+    }
+
+    pub fn register_native_var(&mut self, module_name: &str, name: &str, value: BasicValueEnum<'ctx>, var_type: TypeName) {
+        let module = self.modules.entry(module_name.to_string())
+            .or_insert(ModuleValue {
+                functions: HashMap::new(),
+                variables: HashMap::new(),
+            });
+
+        module.variables.insert(name.to_string(), (value, var_type));
+    }
+
+    // Value and type of a module constant such as `math.PI`.
+    pub fn module_variable(&self, path: &str) -> Option<(BasicValueEnum<'ctx>, TypeName)> {
+        let (module_name, name) = path.rsplit_once('.')?;
+        self.modules.get(module_name)?.variables.get(name).cloned()
+    }
+
+    pub fn register_adan_fn(&mut self, module_name: &str, declaration: FunctionDecl) {
+        let module = self.modules.entry(module_name.to_string())
+            .or_insert(ModuleValue {
+                functions: HashMap::new(),
+                variables: HashMap::new(),
+            });
+
+        module.functions.insert(declaration.name.clone(), NativeFunc::AdanFunction(declaration));
+    }
+
+    // What a call to the ADAN function `name` evaluates to, f64 unless it declares otherwise.
+    pub fn function_return_type(&self, name: &str) -> Option<TypeName> {
+        match self.modules.get("")?.get_function(name)? {
+            NativeFunc::AdanFunction(declaration) => Some(declaration.return_type.clone().unwrap_or(TypeName::Builtin(Types::f64))),
+            NativeFunc::NativeFn(..) => None,
+        }
     }
 
     pub fn load_native_modules(&mut self, native_dir: &str) {
@@ -99,9 +197,8 @@ impl<'ctx> CodeGenContext<'ctx> {
             if path.is_file() && path.extension().map(|s| s == "rs").unwrap_or(false) {
                 let module_name = path.file_stem().unwrap().to_string_lossy();
 
-                match module_name.as_ref() {
-                    "io" => crate::native::io::register_native(self),
-                    _ => {}
+                if let Some(module) = crate::native::module(&module_name) {
+                    (module.register)(self);
                 }
             }
         }
@@ -116,6 +213,43 @@ impl<'ctx> CodeGenContext<'ctx> {
         builder.build_alloca(self.f64_type, name)
     }
 
+    // Allocas in the entry block are reserved once per call instead of once per loop iteration,
+    // and dominate every return so cleanup code can always load them.
+    pub fn build_entry_alloca(&self, ty: BasicTypeEnum<'ctx>, name: &str, initial: Option<BasicValueEnum<'ctx>>) -> Result<PointerValue<'ctx>, String> {
+        let function = self.builder.get_insert_block().and_then(|b| b.get_parent()).ok_or("alloca outside of a function")?;
+        let entry = function.get_first_basic_block().ok_or("function has no entry block")?;
+        let builder = self.context.create_builder();
+        match entry.get_first_instruction() {
+            Some(first) => builder.position_before(&first),
+            None => builder.position_at_end(entry),
+        }
+
+        let pointer = builder.build_alloca(ty, name)
+            .map_err(|e| format!("alloca for '{}' failed: {:?}", name, e))?;
+        if let Some(value) = initial {
+            builder.build_store(pointer, value)
+                .map_err(|e| format!("store for '{}' failed: {:?}", name, e))?;
+        }
+        Ok(pointer)
+    }
+
+    // Calls `name` from the adan_rt runtime, declaring it from the argument types on first use.
+    pub fn call_runtime(&self, name: &str, return_type: Option<BasicTypeEnum<'ctx>>, args: &[BasicValueEnum<'ctx>]) -> Result<Option<BasicValueEnum<'ctx>>, String> {
+        let func = self.module.get_function(name).unwrap_or_else(|| {
+            let param_types: Vec<BasicMetadataTypeEnum> = args.iter().map(|a| a.get_type().into()).collect();
+            let fn_type = match return_type {
+                Some(t) => t.fn_type(&param_types, false),
+                None => self.context.void_type().fn_type(&param_types, false),
+            };
+            self.module.add_function(name, fn_type, None)
+        });
+
+        let metadata_args: Vec<BasicMetadataValueEnum> = args.iter().map(|a| (*a).into()).collect();
+        let call_site = self.builder.build_call(func, &metadata_args, "")
+            .map_err(|e| format!("call {} failed: {:?}", name, e))?;
+        Ok(return_type.map(|_| unsafe { std::mem::transmute::<_, BasicValueEnum>(call_site.try_as_basic_value()) }))
+    }
+
     pub fn build_return(&self, value: Option<BasicValueEnum<'ctx>>) {
         match value {
             Some(v) => { let _ = self.builder.build_return(Some(&v)); },
@@ -123,21 +257,86 @@ impl<'ctx> CodeGenContext<'ctx> {
         };
     }
 
-    pub fn get_llvm_type(&self, var_type: Types) -> BasicTypeEnum<'ctx> {
-        match var_type {
+    pub fn check_enabled(&self, check: Check) -> bool {
+        self.checks && !self.suppressed.contains(&check)
+    }
+
+    // A constant string laid out like the runtime's (adan_rt/src/string.rs): length, a kind of -1
+    // so freeing it does nothing, then the NUL terminated bytes the string value points at.
+    pub fn build_string_literal(&self, s: &str) -> PointerValue<'ctx> {
+        let bytes = self.context.const_string(s.as_bytes(), true);
+        let header = [self.i64_type.const_int(s.len() as u64, false), self.i64_type.const_all_ones()];
+        let literal = self.context.const_struct(&[header[0].into(), header[1].into(), bytes.into()], false);
+
+        let global = self.module.add_global(literal.get_type(), None, "str");
+        global.set_initializer(&literal);
+        global.set_constant(true);
+        global.set_unnamed_addr(true);
+        let indices = [self.i32_type.const_zero(), self.i32_type.const_int(2, false)];
+        unsafe { global.as_pointer_value().const_gep(literal.get_type(), &indices) }
+    }
+
+    // Reports a failed runtime check at `span` and terminates the current block.
+    pub fn build_runtime_failure(&self, message: &str, span: Span) -> Result<(), String> {
+        let msg = self.build_string_literal(message);
+        self.build_panic(msg.into(), span)
+    }
+
+    // Calls the runtime's adan_panic with `message` (any string value) and the source location of
+    // `span`. It never returns, so the current block ends here.
+    pub fn build_panic(&self, message: BasicValueEnum<'ctx>, span: Span) -> Result<(), String> {
+        let file = self.build_string_literal(&self.source_name);
+
+        self.call_runtime("adan_panic", None, &[
+            message,
+            file.into(),
+            self.i32_type.const_int(span.line as u64, false).into(),
+            self.i32_type.const_int(span.column as u64, false).into(),
+        ])?;
+        self.builder.build_unreachable().map_err(|e| format!("unreachable failed: {:?}", e))?;
+        Ok(())
+    }
+
+    pub fn get_llvm_type(&self, var_type: &TypeName) -> Result<BasicTypeEnum<'ctx>, String> {
+        let builtin = match var_type {
+            TypeName::Builtin(t) => *t,
+            TypeName::Array { element, .. } | TypeName::Vector(element) | TypeName::Map { value: element, .. } if managed::is_managed(element) => {
+                return Err(format!("{} can't hold a {}, it would never be freed", type_display(var_type), type_display(element)));
+            }
+            TypeName::Map { key, .. } if !map::valid_key(self, key) => {
+                return Err(format!("{} can't be a map key, use a String or a discrete type", type_display(key)));
+            }
+            // Vectors and maps are a pointer to the runtime's heap allocated storage.
+            TypeName::Vector(_) | TypeName::Map { .. } => return Ok(self.context.ptr_type(AddressSpace::from(0)).into()),
+            TypeName::Array { element, first, last } => {
+                let length = (last - first + 1).max(0);
+                return Ok(self.get_llvm_type(element)?.array_type(length as u32).into());
+            }
+            TypeName::Named(name) => match self.types.get(name) {
+                Some(TypeDecl::Record { .. }) => {
+                    return self.context.get_struct_type(name)
+                        .map(|t| t.into())
+                        .ok_or_else(|| format!("Record '{}' has no LLVM type", name));
+                }
+                _ => self.resolve_builtin(var_type)?,
+            },
+        };
+
+        Ok(match builtin {
             Types::i8 => self.i8_type.into(),
             Types::i32 => self.i32_type.into(),
             Types::i64 => self.i64_type.into(),
             Types::u8 => self.u8_type.into(),
             Types::u32 => self.u32_type.into(),
             Types::u64 => self.u64_type.into(),
-            Types::f32 => self.context.f32_type().into(),
+            Types::f32 => self.f32_type.into(),
             Types::f64 => self.f64_type.into(),
             Types::Boolean => self.bool_type.into(),
             Types::Char => self.i8_type.into(),
             Types::String => self.string_type.into(),
-            Types::Array => self.array_of_i32.into(),
-            Types::Object => self.my_object_type.into(),
-        }
+            Types::Array => return Err("Array needs an element type and a length, e.g. Array<i32, 10>".to_string()),
+            Types::Vector => return Err("Vector needs an element type, e.g. Vector<i32>".to_string()),
+            Types::Map => return Err("Map needs a key and a value type, e.g. Map<String, i64>".to_string()),
+        })
     }
 }
@@ -1,50 +1,768 @@
-use crate::parser::ast::{Expr, Literal, Operation};
-use crate::code_gen::builder::CodeGenContext;
+use crate::parser::ast::{Expr, Interpolation, Literal, Operation, TypeName, Variant};
+use crate::code_gen::builder::{Check, CodeGenContext};
 use crate::code_gen::statements::{codegen_function, NativeRegisterFn};
-use crate::code_gen::builder::NativeFunc;
+use crate::code_gen::builder::{NativeFunc, NativeParam};
+use crate::code_gen::types::{build_check, build_range_check, cast_value, coerce_value, convert_explicit, convert_to_type, static_type, type_display, type_of_value, widest_type, FieldLocation};
+use crate::lexer::token::{Span, Types};
+use crate::code_gen::managed;
+use crate::native::{io, map, math, random, string, vector};
+use inkwell::intrinsics::Intrinsic;
 use inkwell::values::*;
+use inkwell::types::BasicType;
 use inkwell::AddressSpace;
 use std::collections::HashMap;
 
+// f32 operands stay single precision, the runtime has a remainder for each width.
 fn build_float_mod<'ctx>(ctx: &mut CodeGenContext<'ctx>, lhs: FloatValue<'ctx>, rhs: FloatValue<'ctx>) -> Result<FloatValue<'ctx>, String> {
-    let f64_type = ctx.context.f64_type();
-    let fmod_fn = ctx.module.get_function("fmod").unwrap_or_else(|| {
-        let fn_type = f64_type.fn_type(&[f64_type.into(), f64_type.into()], false);
-        ctx.module.add_function("fmod", fn_type, None)
-    });
+    let float_type = lhs.get_type();
+    let name = if float_type == ctx.f32_type { "adan_fmodf" } else { "adan_fmod" };
+    let result = ctx.call_runtime(name, Some(float_type.into()), &[lhs.into(), rhs.into()])?
+        .ok_or_else(|| format!("{} returned nothing", name))?;
+    Ok(result.into_float_value())
+}
+
+// a + b, a - b and a * b, failing when the result doesn't fit the operands' type. Constant operands
+// are folded, so a provable overflow is a compile error.
+fn build_int_arith<'ctx>(ctx: &CodeGenContext<'ctx>, op: &Operation, lhs: IntValue<'ctx>, rhs: IntValue<'ctx>, unsigned: bool, span: Span) -> Result<IntValue<'ctx>, String> {
+    let (signed_name, unsigned_name, fold): (&str, &str, fn(i128, i128) -> i128) = match op {
+        Operation::Add => ("llvm.sadd.with.overflow", "llvm.uadd.with.overflow", |a, b| a + b),
+        Operation::Subtract => ("llvm.ssub.with.overflow", "llvm.usub.with.overflow", |a, b| a - b),
+        Operation::Multiply => ("llvm.smul.with.overflow", "llvm.umul.with.overflow", |a, b| a * b),
+        _ => return Err(format!("Unsupported int binary op {:?}", op)),
+    };
+    let intrinsic_name = if unsigned { unsigned_name } else { signed_name };
+
+    let int_type = lhs.get_type();
+    let bits = int_type.get_bit_width();
+    let constant = |v: IntValue<'ctx>| match unsigned {
+        true => v.get_zero_extended_constant().map(|c| c as i128),
+        false => v.get_sign_extended_constant().map(|c| c as i128),
+    };
+    if let (Some(l), Some(r)) = (constant(lhs), constant(rhs)) {
+        let result = fold(l, r);
+        let (min, max) = match unsigned {
+            true => (0, (1i128 << bits) - 1),
+            false => (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1),
+        };
+        if bits > 1 && (result < min || result > max) {
+            return Err(format!("{}:{}:{}: integer overflow, {} doesn't fit in {} bits", ctx.source_name, span.line, span.column, result, bits));
+        }
+        return Ok(int_type.const_int(result as u64, !unsigned));
+    }
+
+    if !ctx.check_enabled(Check::Overflow) {
+        return match op {
+            Operation::Add => ctx.builder.build_int_add(lhs, rhs, "addtmp"),
+            Operation::Subtract => ctx.builder.build_int_sub(lhs, rhs, "subtmp"),
+            _ => ctx.builder.build_int_mul(lhs, rhs, "multmp"),
+        }.map_err(|e| e.to_string());
+    }
+
+    // llvm.[su]*.with.overflow returns { result, overflowed }.
+    let intrinsic = Intrinsic::find(intrinsic_name).ok_or_else(|| format!("{} is missing", intrinsic_name))?;
+    let func = intrinsic.get_declaration(&ctx.module, &[int_type.into()])
+        .ok_or_else(|| format!("{} has no declaration for i{}", intrinsic_name, bits))?;
+    let call_site = ctx.builder.build_call(func, &[lhs.into(), rhs.into()], "checked")
+        .map_err(|e| format!("call {} failed: {:?}", intrinsic_name, e))?;
+    let pair = unsafe { std::mem::transmute::<_, BasicValueEnum>(call_site.try_as_basic_value()) }.into_struct_value();
+
+    let result = ctx.builder.build_extract_value(pair, 0, "result")
+        .map_err(|e| format!("extract result failed: {:?}", e))?;
+    let overflowed = ctx.builder.build_extract_value(pair, 1, "overflowed")
+        .map_err(|e| format!("extract overflow flag failed: {:?}", e))?;
+    let fits = ctx.builder.build_not(overflowed.into_int_value(), "fits")
+        .map_err(|e| format!("overflow check not failed: {:?}", e))?;
+    build_check(ctx, fits, "integer overflow", span)?;
+
+    Ok(result.into_int_value())
+}
+
+// Arithmetic on a modular type wraps around at `modulus`. A modulus as wide as the base type
+// (mod 2**8 in a u8) wraps by itself, others are computed at twice the width and reduced.
+fn build_modular_arith<'ctx>(ctx: &CodeGenContext<'ctx>, op: &Operation, lhs: IntValue<'ctx>, rhs: IntValue<'ctx>, modulus: u128) -> Result<IntValue<'ctx>, String> {
+    let int_type = lhs.get_type();
+    let bits = int_type.get_bit_width();
+    if modulus == 1u128 << bits {
+        return match op {
+            Operation::Add => ctx.builder.build_int_add(lhs, rhs, "addtmp"),
+            Operation::Subtract => ctx.builder.build_int_sub(lhs, rhs, "subtmp"),
+            _ => ctx.builder.build_int_mul(lhs, rhs, "multmp"),
+        }.map_err(|e| e.to_string());
+    }
+
+    let wide = ctx.context.custom_width_int_type(bits * 2);
+    let modulus = wide.const_int(modulus as u64, false);
+    let widen = |v: IntValue<'ctx>| ctx.builder.build_int_z_extend(v, wide, "mod_wide").map_err(|e| e.to_string());
+    let (l, r) = (widen(lhs)?, widen(rhs)?);
+    let result = match op {
+        Operation::Add => ctx.builder.build_int_add(l, r, "addtmp"),
+        // Operands are below the modulus, so l + (modulus - r) never goes below zero.
+        Operation::Subtract => ctx.builder.build_int_sub(modulus, r, "mod_neg")
+            .and_then(|neg| ctx.builder.build_int_add(l, neg, "subtmp")),
+        _ => ctx.builder.build_int_mul(l, r, "multmp"),
+    }.map_err(|e| e.to_string())?;
+
+    ctx.builder.build_int_unsigned_rem(result, modulus, "wrapped")
+        .and_then(|wrapped| ctx.builder.build_int_truncate(wrapped, int_type, "modtmp"))
+        .map_err(|e| e.to_string())
+}
+
+// a / b and a mod b, failing on a zero divisor and, for signed types, on MIN / -1, which overflows.
+fn build_int_division<'ctx>(ctx: &CodeGenContext<'ctx>, op: &Operation, lhs: IntValue<'ctx>, rhs: IntValue<'ctx>, unsigned: bool, span: Span) -> Result<IntValue<'ctx>, String> {
+    let int_type = lhs.get_type();
+    let divisor = rhs.get_sign_extended_constant();
+    if divisor == Some(0) {
+        return Err(format!("{}:{}:{}: division by zero", ctx.source_name, span.line, span.column));
+    }
+
+    let compare = |predicate: inkwell::IntPredicate, lhs: IntValue<'ctx>, rhs: IntValue<'ctx>, name: &str| ctx.builder.build_int_compare(predicate, lhs, rhs, name)
+        .map_err(|e| format!("division check compare failed: {:?}", e));
+
+    if divisor.is_none() && ctx.check_enabled(Check::Division) {
+        let nonzero = compare(inkwell::IntPredicate::NE, rhs, int_type.const_zero(), "nonzero")?;
+        build_check(ctx, nonzero, "division by zero", span)?;
+    }
+    // Only a divisor of -1 can overflow, and only for the smallest value of the type.
+    if !unsigned && divisor.is_none_or(|d| d == -1) && int_type.get_bit_width() > 1 && ctx.check_enabled(Check::Overflow) {
+        let min = int_type.const_int(1u64 << (int_type.get_bit_width() - 1), false);
+        let is_min = compare(inkwell::IntPredicate::EQ, lhs, min, "is_min")?;
+        let minus_one = compare(inkwell::IntPredicate::EQ, rhs, int_type.const_all_ones(), "minus_one")?;
+        let overflows = ctx.builder.build_and(is_min, minus_one, "div_overflows")
+            .and_then(|o| ctx.builder.build_not(o, "div_fits"))
+            .map_err(|e| format!("division overflow check failed: {:?}", e))?;
+        build_check(ctx, overflows, "integer overflow", span)?;
+    }
+
+    match (op, unsigned) {
+        (Operation::Divide, false) => ctx.builder.build_int_signed_div(lhs, rhs, "divtmp"),
+        (Operation::Divide, true) => ctx.builder.build_int_unsigned_div(lhs, rhs, "divtmp"),
+        (_, false) => ctx.builder.build_int_signed_rem(lhs, rhs, "modtmp"),
+        (_, true) => ctx.builder.build_int_unsigned_rem(lhs, rhs, "modtmp"),
+    }.map_err(|e| e.to_string())
+}
+
+// Booleans compare as unsigned too, so False < True.
+fn build_int_comparison<'ctx>(ctx: &CodeGenContext<'ctx>, op: &Operation, lhs: IntValue<'ctx>, rhs: IntValue<'ctx>, unsigned: bool) -> Result<IntValue<'ctx>, String> {
+    use inkwell::IntPredicate::*;
+    let unsigned = unsigned || lhs.get_type().get_bit_width() == 1;
+    let predicate = match (op, unsigned) {
+        (Operation::Equal, _) => EQ,
+        (Operation::NotEqual, _) => NE,
+        (Operation::Greater, false) => SGT,
+        (Operation::Greater, true) => UGT,
+        (Operation::Lesser, false) => SLT,
+        (Operation::Lesser, true) => ULT,
+        (Operation::Gequal, false) => SGE,
+        (Operation::Gequal, true) => UGE,
+        (Operation::Lequal, false) => SLE,
+        (Operation::Lequal, true) => ULE,
+        _ => return Err(format!("Unsupported int comparison {:?}", op)),
+    };
+    ctx.builder.build_int_compare(predicate, lhs, rhs, "cmptmp").map_err(|e| e.to_string())
+}
+
+fn is_comparison(op: &Operation) -> bool {
+    matches!(op, Operation::Equal | Operation::NotEqual | Operation::Greater | Operation::Lesser | Operation::Gequal | Operation::Lequal)
+}
+
+// Ordered predicates, so any comparison with a NaN is false, except that a NaN /= anything.
+fn build_float_comparison<'ctx>(ctx: &CodeGenContext<'ctx>, op: &Operation, lhs: FloatValue<'ctx>, rhs: FloatValue<'ctx>) -> Result<IntValue<'ctx>, String> {
+    use inkwell::FloatPredicate::*;
+    let predicate = match op {
+        Operation::Equal => OEQ,
+        Operation::NotEqual => UNE,
+        Operation::Greater => OGT,
+        Operation::Lesser => OLT,
+        Operation::Gequal => OGE,
+        Operation::Lequal => OLE,
+        _ => return Err(format!("Unsupported float comparison {:?}", op)),
+    };
+    ctx.builder.build_float_compare(predicate, lhs, rhs, "fcmptmp").map_err(|e| e.to_string())
+}
+
+// a = b for any type that isn't managed. Arrays and records compare element by element, and
+// a variant part only once both sides are known to hold the same variant, so a payload is never
+// read as the wrong one.
+fn build_equality<'ctx>(ctx: &CodeGenContext<'ctx>, lhs: BasicValueEnum<'ctx>, rhs: BasicValueEnum<'ctx>, ty: &TypeName) -> Result<IntValue<'ctx>, String> {
+    let all = |acc: IntValue<'ctx>, eq: IntValue<'ctx>| ctx.builder.build_and(acc, eq, "all_eq")
+        .map_err(|e| format!("equality and failed: {:?}", e));
+
+    match (lhs, rhs) {
+        (BasicValueEnum::IntValue(l), BasicValueEnum::IntValue(r)) => build_int_comparison(ctx, &Operation::Equal, l, r, false),
+        (BasicValueEnum::FloatValue(l), BasicValueEnum::FloatValue(r)) => build_float_comparison(ctx, &Operation::Equal, l, r),
+        (BasicValueEnum::PointerValue(l), BasicValueEnum::PointerValue(r)) => {
+            Ok(vector::runtime_value(ctx, "adan_str_eq", ctx.bool_type.into(), &[l.into(), r.into()])?.into_int_value())
+        }
+        (BasicValueEnum::ArrayValue(l), BasicValueEnum::ArrayValue(r)) => {
+            let element = match ty {
+                TypeName::Array { element, .. } => element.as_ref().clone(),
+                _ => match type_of_value(&lhs) {
+                    TypeName::Array { element, .. } => *element,
+                    other => other,
+                },
+            };
+            let mut equal = ctx.bool_type.const_int(1, false);
+            for i in 0..l.get_type().len() {
+                let (l_elem, r_elem) = (extract(ctx, l.into(), i)?, extract(ctx, r.into(), i)?);
+                equal = all(equal, build_equality(ctx, l_elem, r_elem, &element)?)?;
+            }
+            Ok(equal)
+        }
+        (BasicValueEnum::StructValue(l), BasicValueEnum::StructValue(r)) => {
+            let TypeName::Named(name) = ty else {
+                return Err(format!("Cannot compare {}", type_display(ty)));
+            };
+            let fields = ctx.record_fields(name)?;
+            let mut equal = ctx.bool_type.const_int(1, false);
+            for (i, field) in fields.iter().enumerate() {
+                let (l_field, r_field) = (extract(ctx, l.into(), i as u32)?, extract(ctx, r.into(), i as u32)?);
+                equal = all(equal, build_equality(ctx, l_field, r_field, &field.field_type)?)?;
+            }
+            match ctx.variant_part(name) {
+                Some(part) => build_variant_equality(ctx, (l, r), equal, ty, fields.len() as u32, &part.variants),
+                None => Ok(equal),
+            }
+        }
+        _ => Err(format!("Cannot compare {}", type_display(ty))),
+    }
+}
+
+fn extract<'ctx>(ctx: &CodeGenContext<'ctx>, aggregate: AggregateValueEnum<'ctx>, index: u32) -> Result<BasicValueEnum<'ctx>, String> {
+    ctx.builder.build_extract_value(aggregate, index, "cmp_elem")
+        .map_err(|e| format!("extract for comparison failed: {:?}", e))
+}
+
+// Runs when the fixed fields, discriminant included, compared equal in `fixed_equal`, and then
+// compares the payloads as the variant the discriminant selects.
+fn build_variant_equality<'ctx>(ctx: &CodeGenContext<'ctx>, (lhs, rhs): (StructValue<'ctx>, StructValue<'ctx>), fixed_equal: IntValue<'ctx>, ty: &TypeName, payload: u32, variants: &[Variant]) -> Result<IntValue<'ctx>, String> {
+    let func = ctx.builder.get_insert_block().and_then(|b| b.get_parent()).ok_or("comparison outside of a function")?;
+    let result = ctx.build_entry_alloca(ctx.bool_type.into(), "record_eq", None)?;
+    ctx.builder.build_store(result, fixed_equal)
+        .map_err(|e| format!("store record equality failed: {:?}", e))?;
+
+    let variants_block = ctx.context.append_basic_block(func, "variant_eq");
+    let merge = ctx.context.append_basic_block(func, "record_eq_cont");
+    ctx.builder.build_conditional_branch(fixed_equal, variants_block, merge)
+        .map_err(|e| format!("conditional branch failed: {:?}", e))?;
+    ctx.builder.position_at_end(variants_block);
 
-    let call_site = ctx.builder
-        .build_call(fmod_fn, &[lhs.into(), rhs.into()], "fmodtmp")
-        .map_err(|e| format!("call fmod failed: {:?}", e))?;
-    let valkind = unsafe { std::mem::transmute::<_, BasicValueEnum>(call_site.try_as_basic_value()) };
-    Ok(valkind.into_float_value())
+    // Variants are overlaid onto the payload in memory, so it's read back through a slot.
+    let mut payloads = Vec::new();
+    for (side, name) in [(lhs, "payload_l"), (rhs, "payload_r")] {
+        let value = extract(ctx, side.into(), payload)?;
+        let slot = ctx.build_entry_alloca(value.get_type(), name, None)?;
+        ctx.builder.build_store(slot, value)
+            .map_err(|e| format!("store payload failed: {:?}", e))?;
+        payloads.push(slot);
+    }
+
+    let discriminant = extract(ctx, lhs.into(), 0)?.into_int_value();
+    let mut cases = Vec::new();
+    for variant in variants {
+        let (_, position) = ctx.enum_literal(&variant.choice).ok_or_else(|| format!("Unknown variant '{}'", variant.choice))?;
+        let block = ctx.context.append_basic_block(func, &format!("variant_{}_eq", variant.choice));
+        cases.push((discriminant.get_type().const_int(position, false), block));
+    }
+    ctx.builder.build_switch(discriminant, merge, &cases)
+        .map_err(|e| format!("variant switch failed: {:?}", e))?;
+
+    for (variant, (_, block)) in variants.iter().zip(&cases) {
+        ctx.builder.position_at_end(*block);
+        let variant_type = ctx.variant_struct_type(ty, &variant.choice)?;
+        let load = |slot: PointerValue<'ctx>| ctx.builder.build_load(variant_type, slot, "variant")
+            .map_err(|e| format!("load variant failed: {:?}", e));
+        let (l_variant, r_variant) = (load(payloads[0])?.into_struct_value(), load(payloads[1])?.into_struct_value());
+
+        let mut equal = ctx.bool_type.const_int(1, false);
+        for (i, field) in variant.fields.iter().enumerate() {
+            let (l_field, r_field) = (extract(ctx, l_variant.into(), i as u32)?, extract(ctx, r_variant.into(), i as u32)?);
+            let field_equal = build_equality(ctx, l_field, r_field, &field.field_type)?;
+            equal = ctx.builder.build_and(equal, field_equal, "all_eq")
+                .map_err(|e| format!("equality and failed: {:?}", e))?;
+        }
+        ctx.builder.build_store(result, equal)
+            .map_err(|e| format!("store record equality failed: {:?}", e))?;
+        ctx.builder.build_unconditional_branch(merge)
+            .map_err(|e| format!("unconditional branch failed: {:?}", e))?;
+    }
+
+    ctx.builder.position_at_end(merge);
+    ctx.builder.build_load(ctx.bool_type, result, "record_eq")
+        .map(|v| v.into_int_value())
+        .map_err(|e| format!("load record equality failed: {:?}", e))
+}
+
+// Literals and expressions made only of literals, which have no type until they meet one.
+fn is_universal(expr: &Expr) -> bool {
+    match expr {
+        Expr::Literal(Literal::Integer(n)) => *n <= i64::MAX as i128,
+        Expr::Literal(Literal::Number(_)) => true,
+        Expr::Unary { op: Operation::Negate, right, .. } => is_universal(right),
+        Expr::Binary { left, op: Operation::Add | Operation::Subtract | Operation::Multiply | Operation::Divide | Operation::Modulo, right, .. } => {
+            is_universal(left) && is_universal(right)
+        }
+        _ => false,
+    }
+}
+
+// A literal operand takes the type of the other one, so `x + 1` works for any numeric `x`.
+// Otherwise both sides must have the same type already, mixing them takes an explicit conversion.
+fn unify_operands<'ctx>(ctx: &CodeGenContext<'ctx>, (left, l_val): (&Expr, BasicValueEnum<'ctx>), (right, r_val): (&Expr, BasicValueEnum<'ctx>), span: Span) -> Result<(BasicValueEnum<'ctx>, BasicValueEnum<'ctx>), String> {
+    let operand_type = |expr: &Expr, value: &BasicValueEnum<'ctx>| static_type(ctx, expr).unwrap_or_else(|| type_of_value(value));
+    let (l_type, r_type) = (operand_type(left, &l_val), operand_type(right, &r_val));
+    let same_base = ctx.resolve_builtin(&l_type).ok() == ctx.resolve_builtin(&r_type).ok();
+    if l_val.get_type() == r_val.get_type() && (same_base || is_universal(left) || is_universal(right)) {
+        return Ok((l_val, r_val));
+    }
+
+    match (is_universal(left), is_universal(right)) {
+        (true, false) => Ok((convert_to_type(ctx, l_val, None, &ctx.arithmetic_type(r_type), span)?, r_val)),
+        (false, true) => Ok((l_val, convert_to_type(ctx, r_val, None, &ctx.arithmetic_type(l_type), span)?)),
+        // `1 + 2.5` is a real.
+        (true, true) => Ok((coerce_value(ctx, l_val, ctx.f64_type.into())?, coerce_value(ctx, r_val, ctx.f64_type.into())?)),
+        (false, false) => Err(format!("{}:{}:{}: operands have different types, {} and {}, convert one of them explicitly",
+            ctx.source_name, span.line, span.column, type_display(&l_type), type_display(&r_type))),
+    }
+}
+
+// Address and declared type of a variable or a `record.field` path.
+pub fn codegen_path<'ctx>(ctx: &CodeGenContext<'ctx>, path: &str, span: Span) -> Result<(PointerValue<'ctx>, Option<TypeName>), String> {
+    let mut parts = path.split('.');
+    let root = parts.next().unwrap_or_default();
+    let mut pointer = *ctx.variables
+        .get(root)
+        .ok_or_else(|| format!("Variable not declared: {}", root))?;
+    let mut place_type = ctx.variable_types.get(root).cloned();
+    let mut prefix = root.to_string();
+
+    for field in parts {
+        let record_type = place_type.ok_or_else(|| format!("'{}' has no field '{}'", prefix, field))?;
+        let (location, field_type) = ctx.record_field(&record_type, field)?;
+        let struct_type = ctx.get_llvm_type(&record_type)?.into_struct_type();
+
+        pointer = match location {
+            FieldLocation::Direct(index) => ctx.builder
+                .build_struct_gep(struct_type, pointer, index, field)
+                .map_err(|e| format!("field access '{}' failed: {:?}", path, e))?,
+            FieldLocation::Variant { payload, choice, index } => {
+                if ctx.known_variants.get(&prefix) != Some(&choice) && ctx.check_enabled(Check::Discriminant) {
+                    build_discriminant_check(ctx, &record_type, pointer, &choice, field, span)?;
+                }
+
+                let payload_ptr = ctx.builder
+                    .build_struct_gep(struct_type, pointer, payload, "payload")
+                    .map_err(|e| format!("variant access '{}' failed: {:?}", path, e))?;
+                ctx.builder
+                    .build_struct_gep(ctx.variant_struct_type(&record_type, &choice)?, payload_ptr, index, field)
+                    .map_err(|e| format!("field access '{}' failed: {:?}", path, e))?
+            }
+        };
+        prefix = format!("{}.{}", prefix, field);
+        place_type = Some(field_type);
+    }
+
+    Ok((pointer, place_type))
+}
+
+// Address and declared type of an assignable expression, a variable path or an array element.
+// An index as an i64, unsigned indices are zero extended.
+pub fn codegen_index<'ctx>(ctx: &mut CodeGenContext<'ctx>, index: &Expr, registry: &HashMap<String, NativeRegisterFn<'ctx>>) -> Result<IntValue<'ctx>, String> {
+    let unsigned = static_type(ctx, index).is_some_and(|t| ctx.is_unsigned(&t));
+    let value = codegen_expressions(ctx, index, registry)?;
+    Ok(cast_value(ctx, value, unsigned, ctx.i64_type.into(), false)?.into_int_value())
+}
+
+pub fn codegen_place<'ctx>(ctx: &mut CodeGenContext<'ctx>, expr: &Expr, registry: &HashMap<String, NativeRegisterFn<'ctx>>) -> Result<(PointerValue<'ctx>, Option<TypeName>), String> {
+    match expr {
+        Expr::Variable { var_name, span, .. } => codegen_path(ctx, var_name, *span),
+        Expr::Index { base, index, span } => {
+            let (base_ptr, base_type) = codegen_place(ctx, base, registry)?;
+            if let Some(TypeName::Map { key, value }) = base_type {
+                let handle = ctx.builder.build_load(vector::handle_type(ctx), base_ptr, "map")
+                    .map_err(|e| format!("load map failed: {:?}", e))?;
+                let key_value = codegen_expressions(ctx, index, registry)?;
+                let key_ptr = vector::spill_item(ctx, &(key_value, static_type(ctx, index)), &key, *span)?;
+                return Ok((map::entry_ptr(ctx, handle, key_ptr)?, Some(*value)));
+            }
+            if let Some(TypeName::Vector(element)) = base_type {
+                let handle = ctx.builder.build_load(vector::handle_type(ctx), base_ptr, "vec")
+                    .map_err(|e| format!("load vector failed: {:?}", e))?;
+                let index_value = codegen_index(ctx, index, registry)?;
+                let element_ptr = vector::element_ptr(ctx, handle, index_value, "index out of bounds", *span)?;
+                return Ok((element_ptr, Some(*element)));
+            }
+            if let Some(TypeName::Builtin(Types::String)) = base_type {
+                return Err(format!("{}:{}:{}: strings can't be changed in place", ctx.source_name, span.line, span.column));
+            }
+            let Some(TypeName::Array { element, first, last }) = base_type else {
+                return Err(format!("Only arrays, vectors and maps can be indexed, got {:?}", base_type));
+            };
+
+            let index_source = static_type(ctx, index);
+            let index_value = codegen_index(ctx, index, registry)?;
+
+            // An index whose type already lies within the array's range needs no check.
+            let proven = index_source
+                .and_then(|t| ctx.static_bounds(&t))
+                .is_some_and(|(low, high)| low >= first as i128 && high <= last as i128);
+            if !proven {
+                let array_name = match base.as_ref() {
+                    Expr::Variable { var_name, .. } => var_name.as_str(),
+                    _ => "array",
+                };
+                let failure = format!("index out of bounds for '{}' ({}..{})", array_name, first, last);
+                build_range_check(ctx, index_value, (first, last), false, &failure, Check::Index, *span)?;
+            }
+
+            let array_type = ctx.get_llvm_type(&TypeName::Array { element: element.clone(), first, last })?;
+            let offset = ctx.builder.build_int_sub(index_value, ctx.i64_type.const_int(first as u64, true), "index_offset")
+                .map_err(|e| format!("index offset failed: {:?}", e))?;
+            let element_ptr = unsafe {
+                ctx.builder.build_in_bounds_gep(array_type, base_ptr, &[ctx.i64_type.const_zero(), offset], "elem_ptr")
+            }.map_err(|e| format!("element access failed: {:?}", e))?;
+
+            Ok((element_ptr, Some(*element)))
+        }
+        _ => Err(format!("Expression is not assignable: {:?}", expr)),
+    }
+}
+
+// s[i] is the byte at position i, counting from 0 like vectors.
+fn codegen_string_index<'ctx>(ctx: &CodeGenContext<'ctx>, string: PointerValue<'ctx>, index: IntValue<'ctx>, span: Span) -> Result<BasicValueEnum<'ctx>, String> {
+    if ctx.check_enabled(Check::Index) {
+        let len = vector::runtime_value(ctx, "adan_str_len", ctx.i64_type.into(), &[string.into()])?.into_int_value();
+        let in_bounds = ctx.builder.build_int_compare(inkwell::IntPredicate::ULT, index, len, "in_bounds")
+            .map_err(|e| format!("bounds check compare failed: {:?}", e))?;
+        build_check(ctx, in_bounds, "string index out of bounds", span)?;
+    }
+
+    let char_ptr = unsafe { ctx.builder.build_in_bounds_gep(ctx.i8_type, string, &[index], "char_ptr") }
+        .map_err(|e| format!("string index failed: {:?}", e))?;
+    ctx.builder.build_load(ctx.i8_type, char_ptr, "char")
+        .map_err(|e| format!("char load failed: {:?}", e))
+}
+
+// T'Pos(x) and T'Val(n) go between a Char or enumeration value and its position.
+fn codegen_attribute_call<'ctx>(ctx: &mut CodeGenContext<'ctx>, prefix: &TypeName, attribute: &str, args: &[Expr], span: Span, registry: &HashMap<String, NativeRegisterFn<'ctx>>) -> Result<BasicValueEnum<'ctx>, String> {
+    let location = format!("{}:{}:{}", ctx.source_name, span.line, span.column);
+    if matches!(attribute, "Image" | "Value") {
+        let [arg] = args else {
+            return Err(format!("{}: {}'{} takes one argument, got {}", location, type_display(prefix), attribute, args.len()));
+        };
+        return codegen_text_attribute(ctx, prefix, attribute, arg, span, registry);
+    }
+
+    let positions = match ctx.resolve_builtin(prefix) {
+        Ok(Types::Char) => Some((0, u8::MAX as i64)),
+        _ if ctx.enum_literals_of(prefix).is_some() => ctx.range_bounds(prefix),
+        _ => None,
+    };
+    let Some((low, high)) = positions else {
+        return Err(format!("{}: '{} needs a Char or enumeration type, got {}", location, attribute, type_display(prefix)));
+    };
+    let [arg] = args else {
+        return Err(format!("{}: {}'{} takes one argument, got {}", location, type_display(prefix), attribute, args.len()));
+    };
+
+    let value = codegen_expressions(ctx, arg, registry)?;
+    let source = static_type(ctx, arg);
+    match attribute {
+        "Pos" => {
+            let value = convert_to_type(ctx, value, source.as_ref(), prefix, span)?;
+            cast_value(ctx, value, true, ctx.i64_type.into(), false)
+        }
+        "Val" => {
+            let source_unsigned = source.as_ref().is_some_and(|t| ctx.is_unsigned(t));
+            let BasicValueEnum::IntValue(position) = cast_value(ctx, value, source_unsigned, ctx.i64_type.into(), false)? else {
+                return Err(format!("{}: {}'Val takes an integer position", location, type_display(prefix)));
+            };
+            let failure = format!("position out of range for '{}' ({}..{})", type_display(prefix), low, high);
+            build_range_check(ctx, position, (low, high), false, &failure, Check::Range, span)?;
+            cast_value(ctx, position.into(), false, ctx.get_llvm_type(prefix)?, true)
+        }
+        _ => Err(format!("{}: unknown attribute '{}' for {}", location, attribute, type_display(prefix))),
+    }
+}
+
+// T'Image(x) and T'Value(s) go between a value and its text.
+fn codegen_text_attribute<'ctx>(ctx: &mut CodeGenContext<'ctx>, prefix: &TypeName, attribute: &str, arg: &Expr, span: Span, registry: &HashMap<String, NativeRegisterFn<'ctx>>) -> Result<BasicValueEnum<'ctx>, String> {
+    let value = codegen_expressions(ctx, arg, registry)?;
+    let source = static_type(ctx, arg);
+    if attribute == "Image" {
+        return string::image(ctx, prefix, value, source.as_ref(), span);
+    }
+
+    let text = convert_to_type(ctx, value, source.as_ref(), &TypeName::Builtin(Types::String), span)?;
+    let result = string::value(ctx, prefix, text, span)?;
+    string::free_temporary(ctx, arg, value)?;
+    Ok(result)
+}
+
+// a & b joins two Strings, either of which may also be a Char.
+fn codegen_concat<'ctx>(ctx: &mut CodeGenContext<'ctx>, left: &Expr, right: &Expr, span: Span, registry: &HashMap<String, NativeRegisterFn<'ctx>>) -> Result<BasicValueEnum<'ctx>, String> {
+    let mut operands = Vec::new();
+    for operand in [left, right] {
+        let value = codegen_expressions(ctx, operand, registry)?;
+        let operand_type = static_type(ctx, operand).unwrap_or_else(|| type_of_value(&value));
+        match ctx.resolve_builtin(&operand_type) {
+            Ok(Types::String) => operands.push((value, string::is_temporary(ctx, operand))),
+            Ok(Types::Char) => operands.push((string::from_char(ctx, value)?, true)),
+            _ => return Err(format!("{}:{}:{}: & joins Strings and Chars, got {}", ctx.source_name, span.line, span.column, type_display(&operand_type))),
+        }
+    }
+
+    let joined = string::concat(ctx, operands[0].0, operands[1].0)?;
+    for (value, temporary) in operands {
+        if temporary {
+            string::free(ctx, value)?;
+        }
+    }
+    Ok(joined)
+}
+
+// "x = {x}" renders each value with its type's 'Image, Strings as they are, and joins the pieces.
+fn codegen_interpolation<'ctx>(ctx: &mut CodeGenContext<'ctx>, parts: &[Interpolation], span: Span, registry: &HashMap<String, NativeRegisterFn<'ctx>>) -> Result<BasicValueEnum<'ctx>, String> {
+    let mut pieces = Vec::new();
+    let mut temporaries = Vec::new();
+    for part in parts {
+        let expr = match part {
+            Interpolation::Text(text) => {
+                pieces.push(ctx.build_string_literal(text).into());
+                continue;
+            }
+            Interpolation::Value(expr) => expr,
+        };
+
+        let value = codegen_expressions(ctx, expr, registry)?;
+        let value_type = static_type(ctx, expr).unwrap_or_else(|| type_of_value(&value));
+        if string::is_string(ctx, &value_type) {
+            if string::is_temporary(ctx, expr) {
+                temporaries.push(value);
+            }
+            pieces.push(value);
+            continue;
+        }
+
+        let image = string::image(ctx, &value_type, value, Some(&value_type), span)?;
+        temporaries.push(image);
+        pieces.push(image);
+    }
+
+    let joined = string::join(ctx, &pieces)?;
+    for temporary in temporaries {
+        string::free(ctx, temporary)?;
+    }
+    Ok(joined)
+}
+
+// Strings kept in an array, map or record literal are copied, unless they were built just for it.
+// So are records and arrays holding strings.
+// Like codegen_expressions, but a literal `[...]` or `{...}` builds its elements right away in the
+// element types of `expected`, the type it's about to be converted into.
+pub fn codegen_expected<'ctx>(ctx: &mut CodeGenContext<'ctx>, expr: &Expr, expected: &TypeName, registry: &HashMap<String, NativeRegisterFn<'ctx>>) -> Result<BasicValueEnum<'ctx>, String> {
+    match (expr, expected) {
+        (Expr::ArrayLiteral { elements, span }, TypeName::Array { element, .. } | TypeName::Vector(element)) => {
+            codegen_array_literal(ctx, elements, Some(element), *span, registry)
+        }
+        (Expr::MapLiteral { entries, span }, TypeName::Map { key, value }) => {
+            codegen_map_literal(ctx, entries, Some((key, value)), *span, registry)
+        }
+        _ => codegen_expressions(ctx, expr, registry),
+    }
+}
+
+// Generates the items of a literal, in the `expected` type when there is one.
+fn codegen_items<'ctx>(ctx: &mut CodeGenContext<'ctx>, items: &[&Expr], expected: Option<&TypeName>, registry: &HashMap<String, NativeRegisterFn<'ctx>>) -> Result<Vec<(BasicValueEnum<'ctx>, Option<TypeName>)>, String> {
+    let mut values = Vec::new();
+    for item in items {
+        let value = match expected {
+            Some(expected) => codegen_expected(ctx, item, expected, registry)?,
+            None => codegen_expressions(ctx, item, registry)?,
+        };
+        values.push((owned_element(ctx, item, value)?, static_type(ctx, item)));
+    }
+    Ok(values)
+}
+
+// `[a, b, c]` is an array of the declared element type, or else of the widest element type.
+// `[]` is an empty array of any type.
+fn codegen_array_literal<'ctx>(ctx: &mut CodeGenContext<'ctx>, elements: &[Expr], element: Option<&TypeName>, span: Span, registry: &HashMap<String, NativeRegisterFn<'ctx>>) -> Result<BasicValueEnum<'ctx>, String> {
+    let values = codegen_items(ctx, &elements.iter().collect::<Vec<_>>(), element, registry)?;
+    let element_type = match element {
+        Some(element) => element.clone(),
+        None => widest_type(ctx, &values, span)?.unwrap_or(TypeName::Builtin(Types::i64)),
+    };
+
+    let mut array: AggregateValueEnum<'ctx> = ctx.get_llvm_type(&element_type)?.array_type(values.len() as u32).get_undef().into();
+    for (i, (value, source)) in values.into_iter().enumerate() {
+        let value = convert_to_type(ctx, value, source.as_ref(), &element_type, span)?;
+        array = ctx.builder
+            .build_insert_value(array, value, i as u32, "elem")
+            .map_err(|e| format!("insert element failed: {:?}", e))?;
+    }
+
+    Ok(array.into_array_value().into())
+}
+
+// `{ k -> v }` takes its key and value types the way array literals take their element type, `{}`
+// is an empty map of any type. It stays an array of pairs until it's converted into the map type
+// it initializes.
+fn codegen_map_literal<'ctx>(ctx: &mut CodeGenContext<'ctx>, entries: &[(Expr, Expr)], types: Option<(&TypeName, &TypeName)>, span: Span, registry: &HashMap<String, NativeRegisterFn<'ctx>>) -> Result<BasicValueEnum<'ctx>, String> {
+    let keys = codegen_items(ctx, &entries.iter().map(|(key, _)| key).collect::<Vec<_>>(), types.map(|(key, _)| key), registry)?;
+    let values = codegen_items(ctx, &entries.iter().map(|(_, value)| value).collect::<Vec<_>>(), types.map(|(_, value)| value), registry)?;
+    let (key_type, value_type) = match types {
+        Some((key, value)) => (key.clone(), value.clone()),
+        None => (
+            widest_type(ctx, &keys, span)?.unwrap_or(TypeName::Builtin(Types::i64)),
+            widest_type(ctx, &values, span)?.unwrap_or(TypeName::Builtin(Types::i64)),
+        ),
+    };
+    let pair_type = ctx.context.struct_type(&[ctx.get_llvm_type(&key_type)?, ctx.get_llvm_type(&value_type)?], false);
+
+    let mut array: AggregateValueEnum<'ctx> = pair_type.array_type(entries.len() as u32).get_undef().into();
+    for (i, ((key, key_source), (value, value_source))) in keys.into_iter().zip(values).enumerate() {
+        let key = convert_to_type(ctx, key, key_source.as_ref(), &key_type, span)?;
+        let value = convert_to_type(ctx, value, value_source.as_ref(), &value_type, span)?;
+        let mut pair: AggregateValueEnum<'ctx> = pair_type.get_undef().into();
+        pair = ctx.builder.build_insert_value(pair, key, 0, "key")
+            .map_err(|e| format!("insert map key failed: {:?}", e))?;
+        pair = ctx.builder.build_insert_value(pair, value, 1, "value")
+            .map_err(|e| format!("insert map value failed: {:?}", e))?;
+        array = ctx.builder.build_insert_value(array, pair.into_struct_value(), i as u32, "entry")
+            .map_err(|e| format!("insert map entry failed: {:?}", e))?;
+    }
+
+    Ok(array.into_array_value().into())
+}
+
+fn owned_element<'ctx>(ctx: &CodeGenContext<'ctx>, expr: &Expr, value: BasicValueEnum<'ctx>) -> Result<BasicValueEnum<'ctx>, String> {
+    match static_type(ctx, expr) {
+        Some(ty) if managed::holds_strings(ctx, &ty) => managed::take_ownership(ctx, value, &ty, expr),
+        _ => Ok(value),
+    }
+}
+
+// Variable a place like `r.name` or `a[i]` lives in.
+fn root_variable(place: &Expr) -> Option<&str> {
+    match place {
+        Expr::Variable { var_name, .. } => var_name.split('.').next(),
+        Expr::Index { base, .. } => root_variable(base),
+        _ => None,
+    }
+}
+
+// Fails at runtime unless the record at `record_ptr` currently holds the `choice` variant.
+fn build_discriminant_check<'ctx>(ctx: &CodeGenContext<'ctx>, record_type: &TypeName, record_ptr: PointerValue<'ctx>, choice: &str, field: &str, span: Span) -> Result<(), String> {
+    let TypeName::Named(record) = record_type else {
+        return Err(format!("{:?} has no discriminant", record_type));
+    };
+    let part = ctx.variant_part(record).ok_or_else(|| format!("Record '{}' has no discriminant", record))?;
+    let (_, expected) = ctx.enum_literal(choice).ok_or_else(|| format!("Unknown variant '{}'", choice))?;
+
+    let struct_type = ctx.get_llvm_type(record_type)?.into_struct_type();
+    let disc_ptr = ctx.builder.build_struct_gep(struct_type, record_ptr, 0, "disc_ptr")
+        .map_err(|e| format!("discriminant access failed: {:?}", e))?;
+    let disc = ctx.builder.build_load(ctx.i32_type, disc_ptr, "disc")
+        .map_err(|e| format!("discriminant load failed: {:?}", e))?
+        .into_int_value();
+    let matches = ctx.builder.build_int_compare(inkwell::IntPredicate::EQ, disc, ctx.i32_type.const_int(expected, false), "disc_ok")
+        .map_err(|e| format!("discriminant compare failed: {:?}", e))?;
+
+    let func = ctx.builder.get_insert_block().and_then(|b| b.get_parent()).ok_or("Discriminant check outside of a function")?;
+    let fail_block = ctx.context.append_basic_block(func, "disc_fail");
+    let ok_block = ctx.context.append_basic_block(func, "disc_ok");
+    ctx.builder.build_conditional_branch(matches, ok_block, fail_block)
+        .map_err(|e| format!("discriminant branch failed: {:?}", e))?;
+
+    ctx.builder.position_at_end(fail_block);
+    ctx.build_runtime_failure(&format!("discriminant check failed: '{}' only exists when {} = {}", field, part.discriminant, choice), span)?;
+
+    ctx.builder.position_at_end(ok_block);
+    Ok(())
+}
+
+// assert(condition) and assert(condition, message) panic at the call when the condition is false.
+// The message is only built on the failing path.
+fn codegen_assert<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: &[Expr], span: Span, registry: &HashMap<String, NativeRegisterFn<'ctx>>) -> Result<BasicValueEnum<'ctx>, String> {
+    let (condition, message) = match args {
+        [condition] => (condition, None),
+        [condition, message] => (condition, Some(message)),
+        _ => return Err(format!("assert expects a condition and an optional message, got {} arguments", args.len())),
+    };
+
+    let value = codegen_expressions(ctx, condition, registry)?;
+    let holds = convert_to_type(ctx, value, static_type(ctx, condition).as_ref(), &TypeName::Builtin(Types::Boolean), span)?.into_int_value();
+
+    let func = ctx.builder.get_insert_block().and_then(|b| b.get_parent()).ok_or("assert outside of a function")?;
+    let fail_block = ctx.context.append_basic_block(func, "assert_fail");
+    let ok_block = ctx.context.append_basic_block(func, "assert_ok");
+    ctx.builder.build_conditional_branch(holds, ok_block, fail_block)
+        .map_err(|e| format!("assert branch failed: {:?}", e))?;
+
+    ctx.builder.position_at_end(fail_block);
+    match message {
+        Some(message) => {
+            let text = codegen_expressions(ctx, message, registry)?;
+            if !text.is_pointer_value() {
+                return Err("assert message must be a String".to_string());
+            }
+            ctx.build_panic(text, span)?;
+        }
+        None => ctx.build_runtime_failure("assertion failed", span)?,
+    }
+
+    ctx.builder.position_at_end(ok_block);
+    Ok(holds.into())
+}
+
+// The value given for `field` in an aggregate, which must appear exactly once.
+fn aggregate_field<'a>(fields: &'a [(String, Expr)], field: &str, type_name: &str) -> Result<&'a Expr, String> {
+    let mut matching = fields.iter().filter(|(name, _)| name == field);
+    let (_, value) = matching.next()
+        .ok_or_else(|| format!("Missing field '{}' in aggregate of '{}'", field, type_name))?;
+    if matching.next().is_some() {
+        return Err(format!("Field '{}' given twice in aggregate of '{}'", field, type_name));
+    }
+    Ok(value)
 }
 
 pub fn codegen_expressions<'ctx>(ctx: &mut CodeGenContext<'ctx>, expr: &Expr, registry: &HashMap<String, NativeRegisterFn<'ctx>>) -> Result<BasicValueEnum<'ctx>, String> {
     match expr {
         Expr::Literal(lit) => match lit {
+            // Untyped until they meet their context, see unify_operands and convert_to_type.
+            // Past i64::MAX they can only be u64, see static_type.
+            Literal::Integer(n) => Ok(ctx.i64_type.const_int(*n as u64, true).into()),
             Literal::Number(n) => Ok(ctx.context.f64_type().const_float(*n).into()),
+            // Chars are bytes, so only ASCII characters fit in one.
+            Literal::Char(c) if c.is_ascii() => Ok(ctx.i8_type.const_int(*c as u64, false).into()),
+            Literal::Char(c) => Err(format!("Char literal '{}' isn't ASCII, write it in a String instead", c)),
             Literal::Bool(b) => Ok(ctx.context.bool_type().const_int(*b as u64, false).into()),
             Literal::Nil => Ok(ctx.context.ptr_type(AddressSpace::from(0)).const_null().into()),
-            Literal::String(s) => {
-                let array = ctx.context.const_string(s.as_bytes(), true);
-                let global = ctx.module.add_global(array.get_type(), None, "str");
-
-                global.set_initializer(&array);
-                Ok(global.as_pointer_value().into())
-            }
-            _ => Err(format!("Literal kind not implemented: {:?}", lit)),
+            Literal::String(s) => Ok(ctx.build_string_literal(s).into()),
         },
 
-        Expr::Unary { op, right } => {
+        Expr::Unary { op, right, span } => {
             match op {
-                Operation::Negate => {
-                    let r = codegen_expressions(ctx, right, registry)?.into_float_value();
-                    let fv = ctx.builder
-                        .build_float_neg(r, "negtmp")
-                        .map_err(|e| format!("float neg failed: {:?}", e))?;
-                    Ok(fv.into())
-                }
+                Operation::Negate => match codegen_expressions(ctx, right, registry)? {
+                    // -MIN doesn't fit, so integers negate as a checked 0 - x, modular ones wrap.
+                    BasicValueEnum::IntValue(r) => {
+                        let operand_type = static_type(ctx, right);
+                        let zero = r.get_type().const_zero();
+                        match operand_type.as_ref().and_then(|t| ctx.modulus(t)) {
+                            Some(modulus) => Ok(build_modular_arith(ctx, &Operation::Subtract, zero, r, modulus)?.into()),
+                            None => {
+                                let unsigned = operand_type.is_some_and(|t| ctx.is_unsigned(&t));
+                                Ok(build_int_arith(ctx, &Operation::Subtract, zero, r, unsigned, *span)?.into())
+                            }
+                        }
+                    }
+                    r => {
+                        let fv = ctx.builder
+                            .build_float_neg(r.into_float_value(), "negtmp")
+                            .map_err(|e| format!("float neg failed: {:?}", e))?;
+                        Ok(fv.into())
+                    }
+                },
                 Operation::Not => {
                     let r = codegen_expressions(ctx, right, registry)?.into_int_value();
                     let iv = ctx.builder
@@ -56,18 +774,54 @@ pub fn codegen_expressions<'ctx>(ctx: &mut CodeGenContext<'ctx>, expr: &Expr, re
             }
         }
 
-        Expr::Assign { name, value } => {
-            let val = codegen_expressions(ctx, value, registry)?;
-            let var_pointer = ctx.variables
-                .get(name)
-                .ok_or_else(|| format!("Variable not declared: {}", name))?;
+        Expr::Assign { target, value, span } => {
+            let path = match target.as_ref() {
+                Expr::Variable { var_name, .. } => Some(var_name.as_str()),
+                _ => None,
+            };
+            if path.is_some_and(|p| ctx.discriminant_owner(p).is_some()) {
+                return Err(format!("Discriminant '{}' cannot be assigned on its own, assign the whole record instead", path.unwrap_or_default()));
+            }
+
+            let in_container = match target.as_ref() {
+                Expr::Index { base, .. } => matches!(static_type(ctx, base), Some(TypeName::Vector(_) | TypeName::Map { .. })),
+                _ => false,
+            };
+            let val = match static_type(ctx, target) {
+                Some(target_type) => codegen_expected(ctx, value, &target_type, registry)?,
+                None => codegen_expressions(ctx, value, registry)?,
+            };
+            let (var_pointer, var_type) = codegen_place(ctx, target, registry)?;
+            if let Some(path) = path {
+                ctx.forget_variants(path);
+            }
+            if let Some(owned_type) = var_type.as_ref().filter(|t| managed::is_owned(ctx, t)) {
+                let val = convert_to_type(ctx, val, static_type(ctx, value).as_ref(), owned_type, *span)?;
+                let val = managed::take_ownership(ctx, val, owned_type, value)?;
+                // Variables, the records and arrays in them and container elements own what they
+                // held. A loop variable or an `in out` parameter holds someone else's strings.
+                let owns_old = in_container || root_variable(target)
+                    .and_then(|root| ctx.variables.get(root))
+                    .is_some_and(|root_slot| ctx.owned_values.iter().any(|(slot, _)| slot == root_slot));
+                if managed::is_managed(owned_type) || owns_old {
+                    managed::replace(ctx, var_pointer, val, owned_type)?;
+                } else {
+                    ctx.builder.build_store(var_pointer, val)
+                        .map_err(|e| format!("store assign failed: {:?}", e))?;
+                }
+                return Ok(val);
+            }
+            let val = match var_type {
+                Some(var_type) => convert_to_type(ctx, val, static_type(ctx, value).as_ref(), &var_type, *span)?,
+                None => val,
+            };
             ctx.builder
-                .build_store(*var_pointer, val)
+                .build_store(var_pointer, val)
                 .map_err(|e| format!("store assign failed: {:?}", e))?;
             Ok(val)
         }
 
-        Expr::FCall { callee, args } => {
+        Expr::FCall { callee, args, span } => {
             let parts: Vec<&str> = callee.split('.').collect();
             let (module_name, func_name) = if parts.len() > 1 {
                 (parts[..parts.len() - 1].join("."), parts.last().unwrap())
@@ -75,6 +829,47 @@ pub fn codegen_expressions<'ctx>(ctx: &mut CodeGenContext<'ctx>, expr: &Expr, re
                 ("".to_string(), &parts[0])
             };
 
+            // `v.push(x)`, `m.insert(k, x)` and `s.trim()` on vector, map and string variables.
+            if let Some(receiver_type) = ctx.place_type(&module_name).filter(|t| managed::is_managed(t) || string::is_string(ctx, t)) {
+                let (slot, _) = codegen_path(ctx, &module_name, *span)?;
+                let mut values = Vec::new();
+                for arg in args {
+                    let value = codegen_expressions(ctx, arg, registry)?;
+                    values.push((value, static_type(ctx, arg)));
+                }
+                let temporaries: Vec<BasicValueEnum<'ctx>> = values.iter().map(|(value, _)| *value).collect();
+
+                let result = match receiver_type {
+                    TypeName::Map { key, value } => map::codegen_method(ctx, slot, &key, &value, func_name, values, *span)?,
+                    TypeName::Vector(element) => vector::codegen_method(ctx, slot, &element, func_name, values, *span)?,
+                    _ => {
+                        let receiver = ctx.builder.build_load(ctx.string_type, slot, "string")
+                            .map_err(|e| format!("load string failed: {:?}", e))?;
+                        string::codegen_method(ctx, receiver, func_name, &values, *span)?
+                    }
+                };
+                for (arg, value) in args.iter().zip(temporaries) {
+                    string::free_temporary(ctx, arg, value)?;
+                }
+                return Ok(result);
+            }
+
+            // `Percent(x)` converts into a declared type.
+            if module_name.is_empty() && ctx.types.contains_key(*func_name) {
+                if args.len() != 1 {
+                    return Err(format!("Conversion to '{}' takes exactly one argument", func_name));
+                }
+
+                let value = codegen_expressions(ctx, &args[0], registry)?;
+                let source = static_type(ctx, &args[0]);
+                return convert_explicit(ctx, value, source.as_ref(), &TypeName::Named(func_name.to_string()), *span);
+            }
+
+            let user_assert = ctx.modules.get("").is_some_and(|m| m.get_function("assert").is_some());
+            if module_name.is_empty() && *func_name == "assert" && !user_assert {
+                return codegen_assert(ctx, args, *span, registry);
+            }
+
             if !ctx.modules.contains_key(&module_name) {
                 if let Some(register_fn) = registry.get(&module_name) {
                     register_fn(ctx);
@@ -85,46 +880,383 @@ pub fn codegen_expressions<'ctx>(ctx: &mut CodeGenContext<'ctx>, expr: &Expr, re
             let func_opt = ctx.modules.get(&module_name).and_then(|m| m.get_function(func_name)).cloned();
             match func_opt {
                 Some(NativeFunc::AdanFunction(adan_func)) => {
-                    let llvm_fn = codegen_function(ctx, &adan_func, registry).map_err(|e| format!("codegen ADAN function failed: {:?}", e))?;
-                    let arg_vals: Vec<BasicValueEnum<'ctx>> = args.iter().map(|a| codegen_expressions(ctx, a, registry)).collect::<Result<_, _>>()?;
+                    let llvm_fn = match ctx.module.get_function(&adan_func.name) {
+                        Some(f) => f,
+                        None => codegen_function(ctx, &adan_func, registry).map_err(|e| format!("codegen ADAN function failed: {:?}", e))?,
+                    };
+                    if args.len() != adan_func.params.len() {
+                        return Err(format!("Function '{}' expects {} arguments, got {}", adan_func.name, adan_func.params.len(), args.len()));
+                    }
+
+                    let mut arg_vals: Vec<BasicValueEnum<'ctx>> = Vec::new();
+                    let mut temporaries = Vec::new();
+                    for (arg, param) in args.iter().zip(&adan_func.params) {
+                        if param.by_reference {
+                            if !matches!(arg, Expr::Variable { .. } | Expr::Index { .. }) {
+                                return Err(format!("'in out' parameter '{}' of '{}' needs a variable", param.name, adan_func.name));
+                            }
+
+                            let (pointer, arg_type) = codegen_place(ctx, arg, registry)?;
+                            if let Expr::Variable { var_name, .. } = arg {
+                                ctx.forget_variants(var_name);
+                            }
+                            if arg_type.as_ref() != Some(&param.param_type) {
+                                return Err(format!("'in out' parameter '{}' of '{}' expects {:?}, got {:?}", param.name, adan_func.name, param.param_type, arg_type));
+                            }
+                            arg_vals.push(pointer.into());
+                            continue;
+                        }
+
+                        let value = codegen_expected(ctx, arg, &param.param_type, registry)?;
+                        let source = static_type(ctx, arg);
+                        arg_vals.push(convert_to_type(ctx, value, source.as_ref(), &param.param_type, *span)?);
+                        temporaries.push((arg, value));
+                    }
                     let metadata_args: Vec<BasicMetadataValueEnum> = arg_vals.iter().map(|v| (*v).into()).collect();
                     let call_site = ctx.builder.build_call(llvm_fn, &metadata_args, "calltmp").map_err(|e| format!("call failed: {:?}", e))?;
                     let valkind = unsafe { std::mem::transmute::<_, BasicValueEnum>(call_site.try_as_basic_value()) };
+                    // The function copied any String it keeps.
+                    for (arg, value) in temporaries {
+                        string::free_temporary(ctx, arg, value)?;
+                    }
                     Ok(valkind)
                 }
         
-                Some(NativeFunc::NativeFn(native_fn)) => {
-                    let arg_vals: Vec<BasicValueEnum<'ctx>> = args.iter().map(|a| codegen_expressions(ctx, a, registry)).collect::<Result<_, _>>()?;
-                    Ok(native_fn(ctx, arg_vals))
+                // Printing and math go by the static type of each argument, which natives don't get.
+                Some(NativeFunc::NativeFn(..)) if module_name == "io" && io::PRINT_FUNCTIONS.contains(func_name) => {
+                    if let [Expr::Literal(Literal::String(format)), rest @ ..] = args.as_slice() {
+                        let placeholders = format.matches("{}").count();
+                        if !rest.is_empty() && placeholders != rest.len() {
+                            return Err(format!("{}:{}:{}: io.{} format has {} {{}} but {} arguments", ctx.source_name, span.line, span.column, func_name, placeholders, rest.len()));
+                        }
+                    }
+
+                    let mut values = Vec::new();
+                    for arg in args {
+                        values.push((codegen_expressions(ctx, arg, registry)?, static_type(ctx, arg)));
+                    }
+                    io::codegen_print(ctx, func_name, &values, *span)?;
+                    for (arg, (value, _)) in args.iter().zip(values) {
+                        string::free_temporary(ctx, arg, value)?;
+                    }
+                    Ok(ctx.i64_type.const_zero().into())
+                }
+
+                // vector.clear(v) and map.clear(m) are the methods under another name, which know
+                // whether there are strings to free.
+                Some(NativeFunc::NativeFn(..)) if matches!(module_name.as_str(), "vector" | "map") && *func_name == "clear" => {
+                    let [arg] = args.as_slice() else {
+                        return Err(format!("{}:{}:{}: {}.clear expects one argument, got {}", ctx.source_name, span.line, span.column, module_name, args.len()));
+                    };
+                    let (slot, ty) = codegen_place(ctx, arg, registry)?;
+                    match ty {
+                        Some(TypeName::Vector(element)) if module_name == "vector" => vector::codegen_method(ctx, slot, &element, "clear", Vec::new(), *span),
+                        Some(TypeName::Map { key, value }) if module_name == "map" => map::codegen_method(ctx, slot, &key, &value, "clear", Vec::new(), *span),
+                        other => Err(format!("{}:{}:{}: {}.clear expects a {} variable, got {}", ctx.source_name, span.line, span.column, module_name, module_name, other.as_ref().map(type_display).unwrap_or_else(|| "an expression".to_string()))),
+                    }
+                }
+
+                // random.choice and random.shuffle need to know whether they got an array or a
+                // vector, and shuffle works on the variable itself.
+                Some(NativeFunc::NativeFn(..)) if module_name == "random" && matches!(*func_name, "choice" | "shuffle") => {
+                    let [arg] = args.as_slice() else {
+                        return Err(format!("{}:{}:{}: random.{} expects one argument, got {}", ctx.source_name, span.line, span.column, func_name, args.len()));
+                    };
+                    if *func_name == "shuffle" {
+                        let (items, ty) = codegen_place(ctx, arg, registry)?;
+                        random::shuffle(ctx, items, ty, *span)?;
+                        return Ok(ctx.i64_type.const_zero().into());
+                    }
+                    let items = codegen_expressions(ctx, arg, registry)?;
+                    random::choice(ctx, items, static_type(ctx, arg), *span)
+                }
+
+                Some(NativeFunc::NativeFn(..)) if module_name == "math" => {
+                    let mut values = Vec::new();
+                    for arg in args {
+                        values.push((codegen_expressions(ctx, arg, registry)?, static_type(ctx, arg)));
+                    }
+                    math::codegen_call(ctx, func_name, &values, *span)
+                }
+
+                Some(NativeFunc::NativeFn(native_fn, params)) => {
+                    let at = format!("{}:{}:{}", ctx.source_name, span.line, span.column);
+                    let required = params.iter().filter(|p| !matches!(p, NativeParam::Optional(_))).count();
+                    if args.len() < required || args.len() > params.len() {
+                        let expected = match required == params.len() {
+                            true => required.to_string(),
+                            false => format!("{} to {}", required, params.len()),
+                        };
+                        return Err(format!("{}: {}.{} expects {} arguments, got {}", at, module_name, func_name, expected, args.len()));
+                    }
+
+                    let mut arg_vals = Vec::new();
+                    let mut temporaries = Vec::new();
+                    let mut copies = Vec::new();
+                    for (arg, param) in args.iter().zip(&params) {
+                        let value = match param {
+                            NativeParam::Of(ty) | NativeParam::Optional(ty) => codegen_expected(ctx, arg, ty, registry)?,
+                            _ => codegen_expressions(ctx, arg, registry)?,
+                        };
+                        let source = static_type(ctx, arg);
+                        let converted = match (param, &source) {
+                            (NativeParam::Of(ty) | NativeParam::Optional(ty), _) => {
+                                let converted = convert_to_type(ctx, value, source.as_ref(), ty, *span)?;
+                                // A literal made into a vector or a map is only for the call.
+                                if value.is_array_value() && managed::is_managed(ty) {
+                                    copies.push((converted, ty.clone()));
+                                }
+                                converted
+                            }
+                            (NativeParam::AnyVector, Some(TypeName::Vector(_))) | (NativeParam::AnyMap, Some(TypeName::Map { .. })) => value,
+                            (NativeParam::AnyVector | NativeParam::AnyMap, _) => {
+                                let expected = if *param == NativeParam::AnyVector { "a vector" } else { "a map" };
+                                let got = source.as_ref().map(type_display).unwrap_or_else(|| "an expression".to_string());
+                                return Err(format!("{}: {}.{} expects {}, got {}", at, module_name, func_name, expected, got));
+                            }
+                            (NativeParam::Generated, _) => value,
+                        };
+                        arg_vals.push(converted);
+                        temporaries.push((arg, value));
+                    }
+
+                    ctx.call_span = *span;
+                    let result = native_fn(ctx, arg_vals);
+                    for (copy, ty) in copies {
+                        managed::free(ctx, copy, &ty)?;
+                    }
+                    for (arg, value) in temporaries {
+                        string::free_temporary(ctx, arg, value)?;
+                    }
+                    Ok(result)
                 }
         
                 None => Err(format!("Function '{}' not defined in module '{}'", func_name, module_name)),
             }
         }
 
-        Expr::Variable { var_name, var_type } => {
-            let var_pointer = ctx.variables
-                .get(var_name)
-                .ok_or_else(|| format!("Variable not declared: {}", var_name))?;
+        Expr::ArrayLiteral { elements, span } => codegen_array_literal(ctx, elements, None, *span, registry),
+
+        Expr::MapLiteral { entries, span } => codegen_map_literal(ctx, entries, None, *span, registry),
+
+        Expr::Index { base, index, span } => {
+            if let Some(TypeName::Builtin(Types::String)) = static_type(ctx, base) {
+                let s = codegen_expressions(ctx, base, registry)?.into_pointer_value();
+                let index_value = codegen_index(ctx, index, registry)?;
+                let byte = codegen_string_index(ctx, s, index_value, *span)?;
+                string::free_temporary(ctx, base, s.into())?;
+                return Ok(byte);
+            }
+
+            // Reading a missing key is an error, only assigning to `m[key]` adds it.
+            if let Some(TypeName::Map { key, value }) = static_type(ctx, base) {
+                let handle = codegen_expressions(ctx, base, registry)?;
+                let key_value = codegen_expressions(ctx, index, registry)?;
+                let key_ptr = vector::spill_item(ctx, &(key_value, static_type(ctx, index)), &key, *span)?;
+                let value_ptr = map::value_ptr(ctx, handle, key_ptr, *span)?;
+                return ctx.builder.build_load(ctx.get_llvm_type(&value)?, value_ptr, "map_value")
+                    .map_err(|e| format!("map value load failed: {:?}", e));
+            }
+
+            let (pointer, element_type) = codegen_place(ctx, expr, registry)?;
+            let llvm_type = ctx.get_llvm_type(&element_type.ok_or("Unknown array element type")?)?;
+            ctx.builder.build_load(llvm_type, pointer, "elem")
+                .map_err(|e| format!("element load failed: {:?}", e))
+        }
+
+        Expr::Slice { base, low, high, span } => {
+            let base_type = static_type(ctx, base);
+            if base_type.as_ref().is_some_and(|t| string::is_string(ctx, t)) {
+                let s = codegen_expressions(ctx, base, registry)?;
+                let low = codegen_index(ctx, low, registry)?;
+                let high = codegen_index(ctx, high, registry)?;
+                let sliced = string::slice(ctx, s, low, high, *span)?;
+                string::free_temporary(ctx, base, s)?;
+                return Ok(sliced);
+            }
+            let Some(TypeName::Vector(element)) = base_type else {
+                return Err(format!("{}:{}:{}: only vectors and strings can be sliced", ctx.source_name, span.line, span.column));
+            };
+            let handle = codegen_expressions(ctx, base, registry)?;
+            let low = codegen_index(ctx, low, registry)?;
+            let high = codegen_index(ctx, high, registry)?;
+            let sliced = vector::slice(ctx, handle, &element, low, high, *span)?;
+
+            // `v[0..9][2..3]` slices a temporary nobody else owns.
+            if matches!(base.as_ref(), Expr::Slice { .. } | Expr::FCall { .. }) {
+                vector::free(ctx, handle, &element)?;
+            }
+            Ok(sliced)
+        }
+
+        Expr::Attribute { prefix, attribute, span } => {
+            // The prefix is either a type (Percent'First) or an object (a'Length).
+            let prefix_type = match prefix.as_ref() {
+                Expr::Variable { var_name, .. } if ctx.types.contains_key(var_name) => Some(TypeName::Named(var_name.clone())),
+                other => static_type(ctx, other),
+            };
+
+            match (&prefix_type, attribute.as_str()) {
+                (Some(TypeName::Array { first, last, .. }), "Length") => Ok(ctx.i64_type.const_int((last - first + 1).max(0) as u64, false).into()),
+                (Some(TypeName::Array { first, .. }), "First") => Ok(ctx.i64_type.const_int(*first as u64, true).into()),
+                (Some(TypeName::Array { last, .. }), "Last") => Ok(ctx.i64_type.const_int(*last as u64, true).into()),
+                (Some(TypeName::Map { .. }), "Length") => {
+                    let handle = codegen_expressions(ctx, prefix, registry)?;
+                    map::length(ctx, handle)
+                }
+                (Some(TypeName::Vector(_)), "Length" | "First" | "Last") => {
+                    if attribute == "First" {
+                        return Ok(ctx.i64_type.const_zero().into());
+                    }
+                    let handle = codegen_expressions(ctx, prefix, registry)?;
+                    let len = vector::length(ctx, handle)?;
+                    if attribute == "Length" {
+                        return Ok(len.into());
+                    }
+                    ctx.builder.build_int_sub(len, ctx.i64_type.const_int(1, false), "last")
+                        .map(|v| v.into())
+                        .map_err(|e| format!("int sub failed: {:?}", e))
+                }
+                (Some(ty), "Length" | "First" | "Last") if string::is_string(ctx, ty) => {
+                    if attribute == "First" {
+                        return Ok(ctx.i64_type.const_zero().into());
+                    }
+                    let s = codegen_expressions(ctx, prefix, registry)?;
+                    let len = string::length(ctx, s)?;
+                    string::free_temporary(ctx, prefix, s)?;
+                    if attribute == "Length" {
+                        return Ok(len.into());
+                    }
+                    ctx.builder.build_int_sub(len, ctx.i64_type.const_int(1, false), "last")
+                        .map(|v| v.into())
+                        .map_err(|e| format!("int sub failed: {:?}", e))
+                }
+                (Some(ty), "First" | "Last") if ctx.static_bounds(ty).is_some() => {
+                    let (low, high) = ctx.static_bounds(ty).unwrap_or_default();
+                    let bound = if attribute == "First" { low } else { high };
+                    Ok(ctx.get_llvm_type(ty)?.into_int_type().const_int(bound as u64, true).into())
+                }
+                (_, "Range") => Err(format!("{}:{}:{}: 'Range can only be used as the range of a for loop", ctx.source_name, span.line, span.column)),
+                (ty, _) => Err(format!(
+                    "{}:{}:{}: unknown attribute '{}' for {}",
+                    ctx.source_name, span.line, span.column, attribute,
+                    ty.as_ref().map(type_display).unwrap_or_else(|| "this expression".to_string())
+                )),
+            }
+        }
+
+        Expr::AttributeCall { prefix, attribute, args, span } => codegen_attribute_call(ctx, prefix, attribute, args, *span, registry),
+
+        Expr::Aggregate { type_name, fields, span } => {
+            let record_type = TypeName::Named(type_name.clone());
+            let declared = ctx.record_fields(type_name)?;
+
+            // The discriminant decides which variant's fields follow, so it has to be a literal.
+            let variant = match ctx.variant_part(type_name) {
+                Some(part) => {
+                    let Expr::Variable { var_name: choice, .. } = aggregate_field(fields, &part.discriminant, type_name)? else {
+                        return Err(format!("Discriminant '{}' in aggregate of '{}' must be a literal", part.discriminant, type_name));
+                    };
+                    let variant = part.variants.into_iter().find(|v| v.choice == *choice)
+                        .ok_or_else(|| format!("'{}' is not a variant of '{}'", choice, type_name))?;
+                    Some(variant)
+                }
+                None => None,
+            };
+
+            let variant_fields = variant.iter().flat_map(|v| &v.fields);
+            if let Some((unknown, _)) = fields.iter().find(|(name, _)| !declared.iter().chain(variant_fields.clone()).any(|d| d.name == *name)) {
+                return Err(format!("Record '{}' has no field '{}'", type_name, unknown));
+            }
+
+            let struct_type = ctx.get_llvm_type(&record_type)?.into_struct_type();
+            let mut aggregate: AggregateValueEnum<'ctx> = struct_type.get_undef().into();
+            for (index, field) in declared.iter().enumerate() {
+                // Like Ada, aggregates must name every field exactly once.
+                let field_expr = aggregate_field(fields, &field.name, type_name)?;
+                let value = codegen_expected(ctx, field_expr, &field.field_type, registry)?;
+                let value = owned_element(ctx, field_expr, value)?;
+                let value = convert_to_type(ctx, value, static_type(ctx, field_expr).as_ref(), &field.field_type, *span)?;
+                aggregate = ctx.builder
+                    .build_insert_value(aggregate, value, index as u32, &field.name)
+                    .map_err(|e| format!("insert field '{}' failed: {:?}", field.name, e))?;
+            }
+
+            let Some(variant) = variant else {
+                return Ok(aggregate.into_struct_value().into());
+            };
+
+            // Variant fields are written through memory, since the payload is only raw words.
+            let temp = ctx.build_entry_alloca(struct_type.into(), "aggregate", None)?;
+            ctx.builder.build_store(temp, aggregate.into_struct_value())
+                .map_err(|e| format!("store aggregate failed: {:?}", e))?;
+            let payload_ptr = ctx.builder.build_struct_gep(struct_type, temp, declared.len() as u32, "payload")
+                .map_err(|e| format!("variant access failed: {:?}", e))?;
+            let variant_type = ctx.variant_struct_type(&record_type, &variant.choice)?;
+
+            for (index, field) in variant.fields.iter().enumerate() {
+                let field_expr = aggregate_field(fields, &field.name, type_name)?;
+                let value = codegen_expected(ctx, field_expr, &field.field_type, registry)?;
+                let value = owned_element(ctx, field_expr, value)?;
+                let value = convert_to_type(ctx, value, static_type(ctx, field_expr).as_ref(), &field.field_type, *span)?;
+                let field_ptr = ctx.builder.build_struct_gep(variant_type, payload_ptr, index as u32, &field.name)
+                    .map_err(|e| format!("field access '{}' failed: {:?}", field.name, e))?;
+                ctx.builder.build_store(field_ptr, value)
+                    .map_err(|e| format!("store field '{}' failed: {:?}", field.name, e))?;
+            }
+
+            ctx.builder.build_load(struct_type, temp, "aggregate")
+                .map_err(|e| format!("load aggregate failed: {:?}", e))
+        }
+
+        Expr::Variable { var_name, var_type, span } => {
+            if !ctx.variables.contains_key(var_name) {
+                if let Some((_, position)) = ctx.enum_literal(var_name) {
+                    return Ok(ctx.i32_type.const_int(position, false).into());
+                }
+            }
+            // A variable named like the module hides its constants.
+            let root = var_name.split('.').next().unwrap_or_default();
+            if !ctx.variables.contains_key(root) {
+                if let Some((value, _)) = ctx.module_variable(var_name) {
+                    return Ok(value);
+                }
+            }
+
+            let (var_pointer, place_type) = codegen_path(ctx, var_name, *span)?;
             
-            let llvm_type = match var_type {
-                Some(t) => ctx.get_llvm_type(*t),
+            let llvm_type = match var_type.as_ref().or(place_type.as_ref()) {
+                Some(t) => ctx.get_llvm_type(t)?,
                 None => ctx.string_type.into(),
             };
 
             let loaded = ctx.builder
-                .build_load(llvm_type, *var_pointer, "loadtmp")
+                .build_load(llvm_type, var_pointer, "loadtmp")
                 .map_err(|e| format!("load failed for variable '{}': {:?}", var_name, e))?;
             
             Ok(loaded)
         }
 
-        Expr::Binary { left, op, right } => {
+        Expr::Conversion { target, value, span } => {
+            let converted = codegen_expressions(ctx, value, registry)?;
+            let source = static_type(ctx, value);
+            convert_explicit(ctx, converted, source.as_ref(), target, *span)
+        }
+
+        Expr::Binary { left, op: Operation::Concat, right, span } => codegen_concat(ctx, left, right, *span, registry),
+
+        Expr::Interpolation { parts, span } => codegen_interpolation(ctx, parts, *span, registry),
+
+        Expr::Binary { left, op, right, span } => {
             let l_val = codegen_expressions(ctx, left, registry)?;
             let r_val = codegen_expressions(ctx, right, registry)?;
+            let (l_val, r_val) = unify_operands(ctx, (left, l_val), (right, r_val), *span)?;
 
             match (l_val, r_val) {
                 (BasicValueEnum::FloatValue(lf), BasicValueEnum::FloatValue(rf)) => {
+                    if is_comparison(op) {
+                        return Ok(build_float_comparison(ctx, op, lf, rf)?.into());
+                    }
                     let res = match op {
                         Operation::Add => ctx.builder.build_float_add(lf, rf, "addtmp").map_err(|e| e.to_string())?,
                         Operation::Subtract => ctx.builder.build_float_sub(lf, rf, "subtmp").map_err(|e| e.to_string())?,
@@ -136,46 +1268,43 @@ pub fn codegen_expressions<'ctx>(ctx: &mut CodeGenContext<'ctx>, expr: &Expr, re
                     Ok(res.into())
                 }
                 (BasicValueEnum::IntValue(li), BasicValueEnum::IntValue(ri)) => {
-                    let res = match op {
-                        Operation::Add => ctx.builder.build_int_add(li, ri, "addtmp").map_err(|e| e.to_string())?,
-                        Operation::Subtract => ctx.builder.build_int_sub(li, ri, "subtmp").map_err(|e| e.to_string())?,
-                        Operation::Multiply => ctx.builder.build_int_mul(li, ri, "multmp").map_err(|e| e.to_string())?,
-                        Operation::Divide => ctx.builder.build_int_signed_div(li, ri, "divtmp").map_err(|e| e.to_string())?,
-                        Operation::Modulo => ctx.builder.build_int_signed_rem(li, ri, "modtmp").map_err(|e| e.to_string())?,
+                    let operand_type = static_type(ctx, left).or_else(|| static_type(ctx, right));
+                    let unsigned = operand_type.as_ref().is_some_and(|t| ctx.is_unsigned(t));
+                    let is_char = operand_type.as_ref().is_some_and(|t| ctx.resolve_builtin(t) == Ok(Types::Char));
+                    if is_char && matches!(op, Operation::Add | Operation::Subtract | Operation::Multiply | Operation::Divide | Operation::Modulo) {
+                        return Err(format!("{}:{}:{}: no arithmetic on Char, go through Char'Pos and Char'Val", ctx.source_name, span.line, span.column));
+                    }
+                    let res = match (op, operand_type.as_ref().and_then(|t| ctx.modulus(t))) {
+                        (Operation::Add | Operation::Subtract | Operation::Multiply, Some(modulus)) => build_modular_arith(ctx, op, li, ri, modulus)?,
+                        (Operation::Add | Operation::Subtract | Operation::Multiply, None) => build_int_arith(ctx, op, li, ri, unsigned, *span)?,
+                        (Operation::Divide | Operation::Modulo, _) => build_int_division(ctx, op, li, ri, unsigned, *span)?,
+                        (op, _) if is_comparison(op) => build_int_comparison(ctx, op, li, ri, unsigned)?,
                         _ => return Err(format!("Unsupported int binary op {:?}", op)),
                     };
                     Ok(res.into())
                 }
-                (BasicValueEnum::PointerValue(lp), BasicValueEnum::PointerValue(rp)) => {
-                    // println!("lp -> {:?}, rp -> {:?}", lp, rp);
-                    if let Operation::Equal = op {
-                        let strcmp_fn = ctx.module.get_function("strcmp").unwrap_or_else(|| {
-                            let i8_ptr_type = ctx.context.i8_type().ptr_type(AddressSpace::from(0));
-                            let fn_type = ctx.context.i32_type().fn_type(&[i8_ptr_type.into(), i8_ptr_type.into()], false);
-
-                            ctx.module.add_function("strcmp", fn_type, None)
-                        });
-
-                        let i8_ptr_type = ctx.context.i8_type().ptr_type(AddressSpace::from(0));
-                        let lp_cast = ctx.builder.build_bit_cast(lp, i8_ptr_type, "cast_lp").unwrap().into_pointer_value();
-                        let rp_cast = ctx.builder.build_bit_cast(rp, i8_ptr_type, "cast_rp").unwrap().into_pointer_value();
-
-                        let call = ctx.builder.build_call(strcmp_fn, &[lp_cast.into(), rp_cast.into()], "strcmpcall")
-                            .map_err(|e| format!("strcmp call failed: {:?}", e))?;
-
-                        let valkind = unsafe { std::mem::transmute::<_, BasicValueEnum>(call.try_as_basic_value()) };
-                        let int_res = match valkind {
-                            BasicValueEnum::IntValue(v) => v,
-                            _ => return Err("Expected IntValue from strcmp".to_string()),
-                        };
-
-                        let cond = ctx.builder.build_int_compare(inkwell::IntPredicate::EQ, int_res, ctx.context.i32_type().const_int(0, false), "strcmp_cond")
-                            .map_err(|e| format!("icmp eq failed: {:?}", e))?;
-
-                        Ok(cond.into())
-                    } else {
-                        Err(format!("Unsupported string binary operation {:?}", op))
+                (l_val, r_val) if is_comparison(op) => {
+                    let operand_type = static_type(ctx, left).or_else(|| static_type(ctx, right)).unwrap_or_else(|| type_of_value(&l_val));
+                    if managed::is_managed(&operand_type) {
+                        return Err(format!("{}:{}:{}: {} values can't be compared", ctx.source_name, span.line, span.column, type_display(&operand_type)));
                     }
+
+                    let result = match (l_val, r_val, op) {
+                        // Strings order byte by byte, a prefix comes before anything longer.
+                        (BasicValueEnum::PointerValue(lp), BasicValueEnum::PointerValue(rp), Operation::Greater | Operation::Lesser | Operation::Gequal | Operation::Lequal) => {
+                            let order = vector::runtime_value(ctx, "adan_str_cmp", ctx.i32_type.into(), &[lp.into(), rp.into()])?.into_int_value();
+                            build_int_comparison(ctx, op, order, ctx.i32_type.const_zero(), false)?
+                        }
+                        (_, _, Operation::Equal) => build_equality(ctx, l_val, r_val, &operand_type)?,
+                        (_, _, Operation::NotEqual) => {
+                            let equal = build_equality(ctx, l_val, r_val, &operand_type)?;
+                            ctx.builder.build_not(equal, "netmp").map_err(|e| format!("not op failed: {:?}", e))?
+                        }
+                        _ => return Err(format!("{}:{}:{}: {} values have no ordering", ctx.source_name, span.line, span.column, type_display(&operand_type))),
+                    };
+                    string::free_temporary(ctx, left, l_val)?;
+                    string::free_temporary(ctx, right, r_val)?;
+                    Ok(result.into())
                 }
 
                 _ => Err(format!("Type mismatch in binary operation")),
@@ -185,3 +1314,420 @@ pub fn codegen_expressions<'ctx>(ctx: &mut CodeGenContext<'ctx>, expr: &Expr, re
         _ => Err(format!("Expression variant not implemented: {:?}", expr)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::code_gen::testing::*;
+
+    const SHAPES: &str = "
+        type Kind is (Circle, Square);
+        type Shape(kind: Kind) is record {
+            case kind {
+                when Circle => radius: f64
+                when Square => side: f64
+            }
+        }
+    ";
+
+    #[test]
+    fn variant_aggregates_in_loops_reuse_one_slot() {
+        let source = format!("{}
+            program -> main: i32 {{
+                local total: f64 -> 0.0;
+                for i in 1..10 {{
+                    local s: Shape -> Shape {{ kind -> Circle, radius -> 2.0 }};
+                    total -> total + s.radius;
+                }}
+                return 0;
+            }}", SHAPES);
+        let outside = compile(&source, |ctx| allocas_outside_entry(ctx.module.get_function("main").unwrap())).unwrap();
+        assert_eq!(outside, 0);
+    }
+
+    #[test]
+    fn variant_aggregates_in_long_loops_keep_the_stack() {
+        // A slot per iteration would need far more than the default 8 MB stack.
+        run(&format!("{}
+            program -> main: i32 {{
+                local total: f64 -> 0.0;
+                for i in 1..2000000 {{
+                    local s: Shape -> Shape {{ kind -> Square, side -> 1.0 }};
+                    total -> total + s.side;
+                }}
+                return 0;
+            }}", SHAPES)).assert_succeeded();
+    }
+
+    #[test]
+    fn native_calls_check_their_arity() {
+        let error = compile_error(&in_main("include adan.native.fs;", "local s: Fs_Status -> fs.write(\"x\");"));
+        assert!(error.contains("fs.write expects 2 arguments, got 1"), "{}", error);
+        let error = compile_error(&in_main("include adan.native.env;", "env.set(\"A\");"));
+        assert!(error.contains("env.set expects 2 arguments, got 1"), "{}", error);
+        let error = compile_error(&in_main("include adan.native.http;", "local r: Http_Response -> http.get();"));
+        assert!(error.contains("http.get expects 1 to 2 arguments, got 0"), "{}", error);
+    }
+
+    #[test]
+    fn native_calls_check_their_argument_types() {
+        let error = compile_error(&in_main("include adan.native.env;", "local v: String -> env.get(42);"));
+        assert!(error.contains("expected String, got"), "{}", error);
+        let error = compile_error(&in_main("include adan.native.time;", "time.sleep(\"soon\");"));
+        assert!(error.contains("expected i64, got String"), "{}", error);
+        let error = compile_error(&in_main("include adan.native.chars;", "local b: Boolean -> chars.is_digit(65);"));
+        assert!(error.contains("Char'Pos or Char'Val"), "{}", error);
+        let error = compile_error(&in_main("include adan.native.vector;", "local n: i64 -> vector.len(\"abc\");"));
+        assert!(error.contains("vector.len expects a vector, got String"), "{}", error);
+    }
+
+    #[test]
+    fn native_calls_take_optional_and_literal_arguments() {
+        assert_compiles(&in_main("include adan.native.process;", "local o: Process_Output -> process.run(\"ls\", [\"-l\", \"/\"]);"));
+        assert_compiles(&in_main("include adan.native.process;", "local o: Process_Output -> process.run(\"ls\");"));
+        assert_compiles(&in_main("include adan.native.http;", "local r: Http_Response -> http.get(\"http://localhost\", { \"Accept\" -> \"text/plain\" });"));
+        assert_compiles(&in_main("include adan.native.time;", "local x: i32 -> 5;\ntime.sleep(x);"));
+    }
+
+    #[test]
+    fn array_literals_take_the_widest_element_type() {
+        run(&in_main("", "
+            local a: -> [1, 2.5];
+            if (a[0] /= 1.0) { return 1; }
+            if (a[1] /= 2.5) { return 1; }
+            local small: i32 -> 3;
+            local b: -> [small, 5000000000];
+            if (b[1] /= 5000000000) { return 2; }")).assert_succeeded();
+    }
+
+    #[test]
+    fn array_literals_take_the_declared_element_type() {
+        assert_compiles(&in_main("", "local a: Array<u8, 2> -> [1, 200];"));
+        assert_compiles(&in_main("", "local x: i32 -> 3;\nlocal v: Vector<i32> -> [x, 1];"));
+        assert_compiles(&in_main("", "local x: f32 -> 1.5;\nlocal a: Array<f64, 2> -> [x, 2];"));
+        let error = compile_error(&in_main("", "local a: Array<u8, 2> -> [1, 300];"));
+        assert!(error.contains("300 doesn't fit in u8"), "{}", error);
+    }
+
+    #[test]
+    fn array_literals_reject_lossy_mixes() {
+        let error = compile_error(&in_main("", "local x: i64 -> 5;\nlocal y: u64 -> 6;\nlocal a: -> [x, y];"));
+        assert!(error.contains("elements of type i64, u64 don't convert into one another"), "{}", error);
+        let error = compile_error(&in_main("", "local x: i64 -> 5;\nlocal y: f64 -> 6.0;\nlocal a: -> [x, y];"));
+        assert!(error.contains("convert them explicitly"), "{}", error);
+        let error = compile_error(&in_main("", "local a: -> [1, \"two\"];"));
+        assert!(error.contains("convert them explicitly"), "{}", error);
+    }
+
+    #[test]
+    fn map_literals_unify_their_keys_and_values() {
+        run(&in_main("", "
+            local m: -> { \"a\" -> 1, \"b\" -> 2.5 };
+            if (m[\"a\"] /= 1.0) { return 1; }
+            if (m[\"b\"] /= 2.5) { return 2; }
+            local d: Map<String, f64> -> { \"c\" -> 3 };
+            if (d[\"c\"] /= 3.0) { return 3; }")).assert_succeeded();
+        let error = compile_error(&in_main("", "local x: i64 -> 5;\nlocal m: -> { \"a\" -> x, \"b\" -> 0.5 };"));
+        assert!(error.contains("convert them explicitly"), "{}", error);
+        let error = compile_error(&in_main("", "local m: Map<String, u8> -> { \"a\" -> 256 };"));
+        assert!(error.contains("256 doesn't fit in u8"), "{}", error);
+    }
+
+    const POINT: &str = "type Point is record { x, y: f64 }";
+
+    #[test]
+    fn record_aggregates_name_every_field_once() {
+        assert_compiles(&in_main(POINT, "local p: Point -> Point { y -> 2.0, x -> 1 };"));
+        let error = compile_error(&in_main(POINT, "local p: Point -> Point { x -> 1.0 };"));
+        assert!(error.contains("Missing field 'y' in aggregate of 'Point'"), "{}", error);
+        let error = compile_error(&in_main(POINT, "local p: Point -> Point { x -> 1.0, x -> 2.0, y -> 0.0 };"));
+        assert!(error.contains("Field 'x' given twice in aggregate of 'Point'"), "{}", error);
+        let error = compile_error(&in_main(POINT, "local p: Point -> Point { x -> 1.0, y -> 2.0, z -> 3.0 };"));
+        assert!(error.contains("Record 'Point' has no field 'z'"), "{}", error);
+        let error = compile_error(&in_main(POINT, "local p: Point -> Point { x -> \"one\", y -> 2.0 };"));
+        assert!(error.contains("expected f64, got String"), "{}", error);
+    }
+
+    #[test]
+    fn records_are_copied_unless_passed_in_out() {
+        run(&format!("{}
+            program -> shift(p: in out Point) {{ p.x -> p.x + 1.0; }}
+            program -> moved(p: Point): f64 {{ p.x -> 10.0; return p.x; }}
+            program -> main: i32 {{
+                local a: Point -> Point {{ x -> 1.0, y -> 2.0 }};
+                local b: Point -> a;
+                b.x -> 5.0;
+                if (a.x /= 1.0) {{ return 1; }}
+                shift(a);
+                if (a.x /= 2.0) {{ return 2; }}
+                if (moved(a) /= 10.0) {{ return 3; }}
+                if (a.x /= 2.0) {{ return 4; }}
+                if (b.y /= 2.0) {{ return 5; }}
+                return 0;
+            }}", POINT)).assert_succeeded();
+    }
+
+    #[test]
+    fn in_out_arguments_must_be_variables_of_the_parameter_type() {
+        let shift = format!("{}\nprogram -> shift(p: in out Point) {{ p.x -> p.x + 1.0; }}", POINT);
+        let error = compile_error(&format!("{}\nprogram -> main: i32 {{ shift(Point {{ x -> 1.0, y -> 2.0 }}); return 0; }}", shift));
+        assert!(error.contains("'in out' parameter 'p' of 'shift' needs a variable"), "{}", error);
+        let error = compile_error(&format!("{}\nprogram -> main: i32 {{ local n: f64 -> 1.0; shift(n); return 0; }}", shift));
+        assert!(error.contains("'in out' parameter 'p' of 'shift' expects"), "{}", error);
+    }
+
+    // Line of the first statement in `in_main(SHAPES, ...)`.
+    fn shapes_body_line() -> u32 {
+        SHAPES.lines().count() as u32 + 2
+    }
+
+    #[test]
+    fn variant_fields_are_checked_against_the_discriminant() {
+        let body = "local s: Shape -> Shape { kind -> Circle, radius -> 1.0 };\nlocal r: f64 -> s.radius;\nlocal d: f64 -> s.side;";
+        run(&in_main(SHAPES, body)).assert_panicked(shapes_body_line() + 2, "discriminant check failed: 'side' only exists when kind = Square");
+        run_with(&in_main(SHAPES, body), false).assert_succeeded();
+        run(&in_main(SHAPES, &format!("pragma Suppress(Discriminant_Check);\n{}", body))).assert_succeeded();
+    }
+
+    #[test]
+    fn case_on_the_discriminant_proves_the_variant() {
+        let main_checks = |body: &str| compile(&in_main(SHAPES, body), |ctx| check_blocks(ctx.module.get_function("main").unwrap())).unwrap();
+        let square = "local s: Shape -> Shape { kind -> Square, side -> 2.0 };";
+        assert_eq!(main_checks(&format!("{}\ncase s.kind {{ when Square => {{ local d: f64 -> s.side; }} when others => {{ }} }}", square)), 0);
+        assert_eq!(main_checks(&format!("{}\nlocal d: f64 -> s.side;", square)), 1);
+    }
+
+    #[test]
+    fn discriminants_are_set_by_aggregates_only() {
+        let error = compile_error(&in_main(SHAPES, "local s: Shape -> Shape { kind -> Triangle, side -> 1.0 };"));
+        assert!(error.contains("'Triangle' is not a variant of 'Shape'"), "{}", error);
+        let error = compile_error(&in_main(SHAPES, "local s: Shape -> Shape { kind -> Square, radius -> 1.0 };"));
+        assert!(error.contains("Record 'Shape' has no field 'radius'"), "{}", error);
+        let error = compile_error(&in_main(SHAPES, "local s: Shape -> Shape { kind -> Square, side -> 1.0 };\ns.kind -> Circle;"));
+        assert!(error.contains("Discriminant 's.kind' cannot be assigned on its own"), "{}", error);
+    }
+
+    #[test]
+    fn array_indices_are_checked_against_the_bounds() {
+        let error = compile_error(&in_main("", "local a: Array<i32, 5> -> [1, 2, 3, 4, 5];\nlocal x: i32 -> a[5];"));
+        assert!(error.contains("index out of bounds for 'a' (0..4), got 5"), "{}", error);
+        let error = compile_error(&in_main("", "local a: array (1..3) of i32 -> [1, 2, 3];\na[0] -> 4;"));
+        assert!(error.contains("index out of bounds for 'a' (1..3), got 0"), "{}", error);
+        let error = compile_error(&in_main("", "local a: Array<i32, 3> -> [1, 2];"));
+        assert!(error.contains("Expected an array of 3 elements, got 2"), "{}", error);
+
+        let body = "local a: Array<i32, 5> -> [1, 2, 3, 4, 5];\nlocal i: i64 -> 4;\nlocal x: i32 -> a[i];\ni -> i + 1;\na[i] -> x;";
+        run(&in_main("", body)).assert_panicked(7, "index out of bounds for 'a' (0..4)");
+    }
+
+    #[test]
+    fn indices_of_the_array_range_need_no_check() {
+        let main_checks = |includes: &str, body: &str| compile(&in_main(includes, body), |ctx| check_blocks(ctx.module.get_function("main").unwrap())).unwrap();
+        let array = "local a: Array<i32, 5> -> [1, 2, 3, 4, 5];";
+        assert_eq!(main_checks("subtype Slot is i64 range 0..4;", &format!("{}\nlocal i: Slot -> 2;\nlocal x: i32 -> a[i];", array)), 0);
+        assert_eq!(main_checks("", &format!("{}\nlocal i: i64 -> 2;\nlocal x: i32 -> a[i];", array)), 1);
+        assert_eq!(main_checks("", &format!("{}\nlocal i: i64 -> 2;\npragma Suppress(Index_Check);\nlocal x: i32 -> a[i];", array)), 0);
+    }
+
+    #[test]
+    fn arrays_have_attributes_and_iterate() {
+        run(&in_main("", "
+            local a: array (2..4) of i32 -> [10, 20, 30];
+            if (a'First /= 2) { return 1; }
+            if (a'Last /= 4) { return 2; }
+            if (a'Length /= 3) { return 3; }
+            local sum: i32 -> 0;
+            for x in a { sum -> sum + x; }
+            if (sum /= 60) { return 4; }
+            for i in a'Range { sum -> sum - a[i]; }
+            if (sum /= 0) { return 5; }")).assert_succeeded();
+    }
+
+    #[test]
+    fn integer_overflow_panics() {
+        run(&in_main("", "local a: i8 -> 127;\nlocal b: i8 -> a + 1;")).assert_panicked(4, "integer overflow");
+        run(&in_main("", "local a: i32 -> -2147483647;\nlocal b: i32 -> a - 2;")).assert_panicked(4, "integer overflow");
+        run(&in_main("", "local a: i64 -> 5000000000;\nlocal b: i64 -> a * a;")).assert_panicked(4, "integer overflow");
+        let error = compile_error(&in_main("", "local a: i8 -> 100;\nlocal b: i8 -> a + 1;\nlocal c: i8 -> 100 + 100;"));
+        assert!(error.contains("test.adn:5:") && error.contains("200 doesn't fit"), "{}", error);
+    }
+
+    #[test]
+    fn division_by_zero_panics() {
+        run(&in_main("", "local z: i32 -> 0;\nlocal q: i32 -> 10 / z;")).assert_panicked(4, "division by zero");
+        run(&in_main("", "local z: i64 -> 0;\nlocal r: i64 -> 10 % z;")).assert_panicked(4, "division by zero");
+        let error = compile_error(&in_main("", "local q: i32 -> 10;\nq -> q / 0;"));
+        assert!(error.contains("test.adn:4:") && error.contains("division by zero"), "{}", error);
+    }
+
+    #[test]
+    fn dividing_the_smallest_value_by_minus_one_panics() {
+        let body = "local m: i32 -> -2147483647;\nm -> m - 1;\nlocal d: i32 -> -1;\nlocal q: i32 -> m / d;";
+        run(&in_main("", body)).assert_panicked(6, "integer overflow");
+        run(&in_main("", "local m: i8 -> -128;\nlocal d: i8 -> -1;\nlocal r: i8 -> m % d;")).assert_panicked(5, "integer overflow");
+    }
+
+    #[test]
+    fn arithmetic_checks_can_be_turned_off() {
+        let main_checks = |body: &str| compile(&in_main("", body), |ctx| check_blocks(ctx.module.get_function("main").unwrap())).unwrap();
+        assert_eq!(main_checks("local a: i32 -> 7;\nlocal b: i32 -> a + 1;"), 1);
+        assert_eq!(main_checks("pragma Suppress(Overflow_Check);\nlocal a: i32 -> 7;\nlocal b: i32 -> a + 1;"), 0);
+        assert_eq!(main_checks("pragma Suppress(Division_Check);\nlocal a: u32 -> 7;\nlocal b: u32 -> 100 / a;"), 0);
+
+        // Without checks, two's complement wraps around like it does in C.
+        run_with(&in_main("", "local a: i32 -> 2147483647;\nlocal b: i32 -> a + 1;\nif (b > 0) { return 1; }"), false).assert_succeeded();
+    }
+
+    #[test]
+    fn unsigned_operations_treat_the_top_bit_as_a_value() {
+        run(&in_main("", "
+            local a: u32 -> 4000000000;
+            local b: u32 -> 2;
+            if (a / b /= 2000000000) { return 1; }
+            if (b > a) { return 2; }
+            if (a % 7 /= 3) { return 3; }
+            local x: u8 -> 200;
+            local y: i32 -> x;
+            if (y /= 200) { return 4; }
+            local z: f64 -> a;
+            if (z /= 4000000000.0) { return 5; }")).assert_succeeded();
+        run(&in_main("", "local a: u8 -> 0;\nlocal b: u8 -> a - 1;")).assert_panicked(4, "integer overflow");
+    }
+
+    #[test]
+    fn modular_types_wrap_around() {
+        run(&in_main("type Byte is mod 2**8; type Hour is mod 24;", "
+            local b: Byte -> 250;
+            b -> b + 10;
+            if (b /= 4) { return 1; }
+            b -> b - 5;
+            if (b /= 255) { return 2; }
+            local h: Hour -> 20;
+            h -> h + 5;
+            if (h /= 1) { return 3; }
+            h -> h - 3;
+            if (h /= 22) { return 4; }
+            h -> h * 5;
+            if (h /= 14) { return 5; }")).assert_succeeded();
+    }
+
+    #[test]
+    fn negative_and_signed_values_dont_go_into_unsigned_types() {
+        let error = compile_error(&in_main("", "local u: u32 -> -1;"));
+        assert!(error.contains("-1 doesn't fit in u32"), "{}", error);
+        let error = compile_error(&in_main("", "local i: i32 -> 5;\nlocal u: u32 -> i;"));
+        assert!(error.contains("implicit conversion from i32 to u32 can lose information"), "{}", error);
+        let error = compile_error(&in_main("", "local u: u64 -> 5;\nlocal i: i64 -> u;"));
+        assert!(error.contains("implicit conversion from u64 to i64 can lose information"), "{}", error);
+    }
+
+    #[test]
+    fn literals_reach_the_ends_of_i64_and_u64() {
+        run(&in_main("", "
+            local big: u64 -> 18446744073709551615;
+            if (big /= 18446744073709551615) { return 1; }
+            if (big % 10 /= 5) { return 2; }
+            local low: i64 -> -9223372036854775808;
+            if (low > -9223372036854775807) { return 3; }")).assert_succeeded();
+        let error = compile_error(&in_main("", "local i: i64 -> 9223372036854775808;"));
+        assert!(error.contains("9223372036854775808 doesn't fit in i64"), "{}", error);
+    }
+
+    const SINGLE: &str = "include adan.native.io;
+program -> half(x: f32): f32 { return x / 2.0; }
+program -> main: i32 {
+local a: f32 -> 0.1;
+io.printf(a);
+local b: f32 -> 7.5;
+io.printf(b % 2.0);
+io.printf(half(b));
+if (a < 0.2) { } else { return 1; }
+local c: f32 -> a * 0.0;
+if (c) { return 2; }
+return 0;
+}";
+
+    #[test]
+    fn f32_values_stay_single_precision() {
+        let (signature, fmodf, fmod) = compile(SINGLE, |ctx| {
+            let half = ctx.module.get_function("half").unwrap().get_type();
+            let signature = (half.get_return_type(), half.get_param_types());
+            (signature == (Some(ctx.f32_type.into()), vec![ctx.f32_type.into()]),
+                ctx.module.get_function("adan_fmodf").is_some(), ctx.module.get_function("adan_fmod").is_some())
+        }).unwrap();
+        assert!(signature && fmodf && !fmod);
+
+        let output = run(SINGLE);
+        output.assert_succeeded();
+        assert_eq!(output.stdout, "0.1\n1.5\n3.75\n");
+    }
+
+    #[test]
+    fn chars_convert_through_pos_and_val() {
+        run(&in_main("include adan.native.chars; type Color is (Red, Green, Blue);", "
+            if (Char'Pos('A') /= 65) { return 1; }
+            if (Char'Val(97) /= 'a') { return 2; }
+            if (Char'Pos('\\n') /= 10) { return 3; }
+            if (Char'Pos('\\'') /= 39) { return 4; }
+            local s: String -> \"hey\";
+            if (s[1] /= 'e') { return 5; }
+            if (chars.to_upper(s[0]) /= 'H') { return 6; }
+            if (chars.is_digit('7')) { } else { return 7; }
+            if (Color'Pos(Green) /= 1) { return 8; }
+            if (Color'Val(2) /= Blue) { return 9; }")).assert_succeeded();
+        run(&in_main("", "local n: i64 -> 300;\nlocal c: Char -> Char'Val(n);")).assert_panicked(4, "position out of range for 'Char' (0..255)");
+    }
+
+    #[test]
+    fn chars_and_numbers_dont_mix() {
+        let error = compile_error(&in_main("", "local c: Char -> 65;"));
+        assert!(error.contains("can't use i64 as Char, convert with Char'Pos or Char'Val"), "{}", error);
+        let error = compile_error(&in_main("", "local n: i32 -> 'a';"));
+        assert!(error.contains("convert with Char'Pos or Char'Val"), "{}", error);
+        let error = compile_error(&in_main("", "local c: Char -> Char'Val(256);"));
+        assert!(error.contains("position out of range for 'Char' (0..255), got 256"), "{}", error);
+        let error = compile_error(&in_main("", "local n: i64 -> i32'Pos(5);"));
+        assert!(error.contains("'Pos needs a Char or enumeration type, got i32"), "{}", error);
+    }
+
+    #[test]
+    fn strings_order_byte_by_byte() {
+        run(&in_main("", "
+            local a: String -> \"apple\";
+            if (a < \"banana\") { } else { return 1; }
+            if (\"app\" < a) { } else { return 2; }
+            if (\"Z\" < \"a\") { } else { return 3; }
+            if (a >= \"apple\") { } else { return 4; }
+            if (a <= \"apple\") { } else { return 5; }
+            if (a > \"apples\") { return 6; }
+            if (a /= \"apple\") { return 7; }
+            if (a = \"apples\") { return 8; }")).assert_succeeded();
+    }
+
+    #[test]
+    fn every_type_compares_for_equality() {
+        run(&in_main("type Color is (Red, Green, Blue); type Point is record { x, y: f64 }", "
+            if (Red < Blue) { } else { return 1; }
+            if (Green /= Green) { return 2; }
+            local p: Point -> Point { x -> 1.0, y -> 2.0 };
+            local q: Point -> p;
+            if (p /= q) { return 3; }
+            q.y -> 3.0;
+            if (p = q) { return 4; }
+            local yes: Boolean -> p /= q;
+            if (yes) { } else { return 5; }
+            local z: f64 -> 0.0;
+            local nan: f64 -> z / z;
+            if (nan = nan) { return 6; }
+            if (nan /= nan) { } else { return 7; }
+            local n: i32 -> 0;
+            while (n /= 3) { n -> n + 1; }")).assert_succeeded();
+    }
+
+    #[test]
+    fn collections_and_records_have_no_ordering() {
+        let error = compile_error(&in_main("", "local a: Vector<i64> -> [1];\nlocal b: Vector<i64> -> [1];\nif (a = b) { }"));
+        assert!(error.contains("Vector<i64> values can't be compared"), "{}", error);
+        let error = compile_error(&in_main("type Point is record { x, y: f64 }", "local p: Point -> Point { x -> 1.0, y -> 2.0 };\nif (p < p) { }"));
+        assert!(error.contains("Point values have no ordering"), "{}", error);
+    }
+}
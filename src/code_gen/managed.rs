@@ -0,0 +1,247 @@
+use inkwell::values::{BasicValueEnum, IntValue, PointerValue};
+use inkwell::IntPredicate;
+use crate::code_gen::builder::CodeGenContext;
+use crate::code_gen::types::TypeDecl;
+use crate::native::{map, string, vector};
+use crate::parser::ast::{Expr, TypeName};
+
+// Vectors and maps live on the heap and belong to exactly one variable, which frees them when
+// it's assigned a new value or when its function returns.
+pub fn is_managed(ty: &TypeName) -> bool {
+    matches!(ty, TypeName::Vector(_) | TypeName::Map { .. })
+}
+
+// Strings are owned the same way, but unlike vectors and maps they can also be returned, compared
+// and kept in records and arrays. A record or an array holding strings owns them too: copying it
+// copies them, and it frees them when it's replaced or goes out of scope.
+pub fn is_owned(ctx: &CodeGenContext<'_>, ty: &TypeName) -> bool {
+    is_managed(ty) || holds_strings(ctx, ty)
+}
+
+// A String, or a record or array with one somewhere inside, variant fields included.
+pub fn holds_strings(ctx: &CodeGenContext<'_>, ty: &TypeName) -> bool {
+    match ty {
+        TypeName::Array { element, .. } => holds_strings(ctx, element),
+        TypeName::Named(name) => match ctx.types.get(name) {
+            Some(TypeDecl::Record { fields, variant }) => fields.iter()
+                .chain(variant.iter().flat_map(|p| &p.variants).flat_map(|v| &v.fields))
+                .any(|f| holds_strings(ctx, &f.field_type)),
+            _ => string::is_string(ctx, ty),
+        },
+        _ => string::is_string(ctx, ty),
+    }
+}
+
+pub fn clone_value<'ctx>(ctx: &CodeGenContext<'ctx>, value: BasicValueEnum<'ctx>, ty: &TypeName) -> Result<BasicValueEnum<'ctx>, String> {
+    match ty {
+        TypeName::Vector(element) => vector::clone_vector(ctx, value, element),
+        TypeName::Map { value: map_value, .. } => map::clone_map(ctx, value, map_value),
+        _ if string::is_string(ctx, ty) => string::dup(ctx, value),
+        _ if holds_strings(ctx, ty) => {
+            let slot = spill(ctx, value)?;
+            own_strings(ctx, slot, ty)?;
+            ctx.builder.build_load(value.get_type(), slot, "copy")
+                .map_err(|e| format!("load copy failed: {:?}", e))
+        }
+        _ => Ok(value),
+    }
+}
+
+pub fn free<'ctx>(ctx: &CodeGenContext<'ctx>, value: BasicValueEnum<'ctx>, ty: &TypeName) -> Result<(), String> {
+    match ty {
+        TypeName::Vector(element) => vector::free(ctx, value, element),
+        TypeName::Map { value: map_value, .. } => map::free(ctx, value, map_value),
+        _ if string::is_string(ctx, ty) => string::free(ctx, value),
+        _ if holds_strings(ctx, ty) => free_strings(ctx, spill(ctx, value)?, ty),
+        _ => Ok(()),
+    }
+}
+
+fn spill<'ctx>(ctx: &CodeGenContext<'ctx>, value: BasicValueEnum<'ctx>) -> Result<PointerValue<'ctx>, String> {
+    let slot = ctx.build_entry_alloca(value.get_type(), "owned_copy", None)?;
+    ctx.builder.build_store(slot, value)
+        .map_err(|e| format!("store owned copy failed: {:?}", e))?;
+    Ok(slot)
+}
+
+// Replaces the strings in the value of type `ty` at `ptr` with copies of their own.
+pub fn own_strings<'ctx>(ctx: &CodeGenContext<'ctx>, ptr: PointerValue<'ctx>, ty: &TypeName) -> Result<(), String> {
+    for_each_string(ctx, ptr, ty, &|slot| {
+        let s = ctx.builder.build_load(ctx.string_type, slot, "string")
+            .map_err(|e| format!("load string failed: {:?}", e))?;
+        let copy = string::dup(ctx, s)?;
+        ctx.builder.build_store(slot, copy)
+            .map_err(|e| format!("store string copy failed: {:?}", e))?;
+        Ok(())
+    })
+}
+
+// Frees the strings in the value of type `ty` at `ptr`.
+pub fn free_strings<'ctx>(ctx: &CodeGenContext<'ctx>, ptr: PointerValue<'ctx>, ty: &TypeName) -> Result<(), String> {
+    for_each_string(ctx, ptr, ty, &|slot| {
+        let s = ctx.builder.build_load(ctx.string_type, slot, "string")
+            .map_err(|e| format!("load string failed: {:?}", e))?;
+        string::free(ctx, s)
+    })
+}
+
+// Calls `f` with the address of every string in the value of type `ty` at `ptr`. Only the fields
+// of the variant the discriminant selects are visited, the others overlay them.
+fn for_each_string<'ctx>(ctx: &CodeGenContext<'ctx>, ptr: PointerValue<'ctx>, ty: &TypeName, f: &dyn Fn(PointerValue<'ctx>) -> Result<(), String>) -> Result<(), String> {
+    if string::is_string(ctx, ty) {
+        return f(ptr);
+    }
+
+    match ty {
+        TypeName::Array { element, first, last } if holds_strings(ctx, element) => {
+            let array_type = ctx.get_llvm_type(ty)?.into_array_type();
+            let count = ctx.i64_type.const_int((last - first + 1).max(0) as u64, false);
+            build_counted_loop(ctx, count, &|index| {
+                let element_ptr = unsafe { ctx.builder.build_in_bounds_gep(array_type, ptr, &[ctx.i64_type.const_zero(), index], "element") }
+                    .map_err(|e| format!("element access failed: {:?}", e))?;
+                for_each_string(ctx, element_ptr, element, f)
+            })
+        }
+        TypeName::Named(name) if holds_strings(ctx, ty) => {
+            let struct_type = ctx.get_llvm_type(ty)?.into_struct_type();
+            let fields = ctx.record_fields(name)?;
+            for (index, field) in fields.iter().enumerate().filter(|(_, f)| holds_strings(ctx, &f.field_type)) {
+                let field_ptr = ctx.builder.build_struct_gep(struct_type, ptr, index as u32, &field.name)
+                    .map_err(|e| format!("field access '{}' failed: {:?}", field.name, e))?;
+                for_each_string(ctx, field_ptr, &field.field_type, f)?;
+            }
+
+            let Some(part) = ctx.variant_part(name) else {
+                return Ok(());
+            };
+            let func = ctx.builder.get_insert_block().and_then(|b| b.get_parent()).ok_or("Strings visited outside of a function")?;
+            let merge = ctx.context.append_basic_block(func, "variant_strings_cont");
+            let disc_ptr = ctx.builder.build_struct_gep(struct_type, ptr, 0, "disc_ptr")
+                .map_err(|e| format!("discriminant access failed: {:?}", e))?;
+            let discriminant = ctx.builder.build_load(ctx.i32_type, disc_ptr, "disc")
+                .map_err(|e| format!("discriminant load failed: {:?}", e))?
+                .into_int_value();
+
+            let variants: Vec<_> = part.variants.iter().filter(|v| v.fields.iter().any(|f| holds_strings(ctx, &f.field_type))).collect();
+            let mut cases = Vec::new();
+            for variant in &variants {
+                let (_, position) = ctx.enum_literal(&variant.choice).ok_or_else(|| format!("Unknown variant '{}'", variant.choice))?;
+                let block = ctx.context.append_basic_block(func, &format!("variant_{}_strings", variant.choice));
+                cases.push((ctx.i32_type.const_int(position, false), block));
+            }
+            ctx.builder.build_switch(discriminant, merge, &cases)
+                .map_err(|e| format!("variant switch failed: {:?}", e))?;
+
+            for (variant, (_, block)) in variants.iter().zip(&cases) {
+                ctx.builder.position_at_end(*block);
+                let payload_ptr = ctx.builder.build_struct_gep(struct_type, ptr, fields.len() as u32, "payload")
+                    .map_err(|e| format!("variant access failed: {:?}", e))?;
+                let variant_type = ctx.variant_struct_type(ty, &variant.choice)?;
+                for (index, field) in variant.fields.iter().enumerate().filter(|(_, f)| holds_strings(ctx, &f.field_type)) {
+                    let field_ptr = ctx.builder.build_struct_gep(variant_type, payload_ptr, index as u32, &field.name)
+                        .map_err(|e| format!("field access '{}' failed: {:?}", field.name, e))?;
+                    for_each_string(ctx, field_ptr, &field.field_type, f)?;
+                }
+                ctx.builder.build_unconditional_branch(merge)
+                    .map_err(|e| format!("unconditional branch failed: {:?}", e))?;
+            }
+
+            ctx.builder.position_at_end(merge);
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+// Runs `body` with every index in 0..count, in order.
+pub fn build_counted_loop<'ctx>(ctx: &CodeGenContext<'ctx>, count: IntValue<'ctx>, body: &dyn Fn(IntValue<'ctx>) -> Result<(), String>) -> Result<(), String> {
+    let func = ctx.builder.get_insert_block().and_then(|b| b.get_parent()).ok_or("Loop outside of a function")?;
+    let counter = ctx.build_entry_alloca(ctx.i64_type.into(), "index", None)?;
+    ctx.builder.build_store(counter, ctx.i64_type.const_zero())
+        .map_err(|e| format!("store index failed: {:?}", e))?;
+
+    let cond_block = ctx.context.append_basic_block(func, "eachcond");
+    let body_block = ctx.context.append_basic_block(func, "eachbody");
+    let merge = ctx.context.append_basic_block(func, "eachcont");
+    ctx.builder.build_unconditional_branch(cond_block)
+        .map_err(|e| format!("unconditional branch failed: {:?}", e))?;
+
+    ctx.builder.position_at_end(cond_block);
+    let index = ctx.builder.build_load(ctx.i64_type, counter, "index")
+        .map_err(|e| format!("load index failed: {:?}", e))?
+        .into_int_value();
+    let more = ctx.builder.build_int_compare(IntPredicate::SLT, index, count, "more")
+        .map_err(|e| format!("loop compare failed: {:?}", e))?;
+    ctx.builder.build_conditional_branch(more, body_block, merge)
+        .map_err(|e| format!("conditional branch failed: {:?}", e))?;
+
+    ctx.builder.position_at_end(body_block);
+    body(index)?;
+    let next = ctx.builder.build_int_add(index, ctx.i64_type.const_int(1, false), "next")
+        .map_err(|e| format!("index increment failed: {:?}", e))?;
+    ctx.builder.build_store(counter, next)
+        .map_err(|e| format!("store index failed: {:?}", e))?;
+    ctx.builder.build_unconditional_branch(cond_block)
+        .map_err(|e| format!("unconditional branch failed: {:?}", e))?;
+
+    ctx.builder.position_at_end(merge);
+    Ok(())
+}
+
+// A value read from another variable is copied, literals, aggregates, slices and call results are
+// fresh and are moved instead.
+pub fn take_ownership<'ctx>(ctx: &CodeGenContext<'ctx>, value: BasicValueEnum<'ctx>, ty: &TypeName, source: &Expr) -> Result<BasicValueEnum<'ctx>, String> {
+    if string::is_string(ctx, ty) {
+        return match source {
+            Expr::Literal(_) => Ok(value),
+            _ if string::is_temporary(ctx, source) => Ok(value),
+            _ => string::dup(ctx, value),
+        };
+    }
+
+    match source {
+        Expr::ArrayLiteral { .. } | Expr::MapLiteral { .. } | Expr::Aggregate { .. } | Expr::Slice { .. } | Expr::FCall { .. } => Ok(value),
+        _ => clone_value(ctx, value, ty),
+    }
+}
+
+// Locals live until their function returns, blocks don't end them, so a value is only freed when
+// its variable is given a new one or on return. The slot lives in the entry block and starts out
+// zeroed, null strings and handles included, so the cleanup on return can free it on every path.
+// A declaration inside a loop body reuses the slot and frees the previous iteration's value, so
+// the loop holds on to one value per declaration rather than one per iteration.
+pub fn declare_variable<'ctx>(ctx: &mut CodeGenContext<'ctx>, name: &str, value: BasicValueEnum<'ctx>, ty: &TypeName) -> Result<PointerValue<'ctx>, String> {
+    let slot_type = value.get_type();
+    let slot = ctx.build_entry_alloca(slot_type, name, Some(slot_type.const_zero()))?;
+    ctx.owned_values.push((slot, ty.clone()));
+    replace(ctx, slot, value, ty)?;
+    Ok(slot)
+}
+
+// Stores `value` into a slot, freeing the value it held before.
+pub fn replace<'ctx>(ctx: &CodeGenContext<'ctx>, slot: PointerValue<'ctx>, value: BasicValueEnum<'ctx>, ty: &TypeName) -> Result<(), String> {
+    free_at(ctx, slot, ty)?;
+    ctx.builder.build_store(slot, value)
+        .map_err(|e| format!("store owned value failed: {:?}", e))?;
+    Ok(())
+}
+
+// Frees everything owned by the current function, right before it returns.
+pub fn free_owned(ctx: &CodeGenContext<'_>) -> Result<(), String> {
+    for (slot, ty) in &ctx.owned_values {
+        free_at(ctx, *slot, ty)?;
+    }
+    Ok(())
+}
+
+// Frees the value in `slot`, the strings of a record or an array where they are rather than
+// through a copy.
+fn free_at<'ctx>(ctx: &CodeGenContext<'ctx>, slot: PointerValue<'ctx>, ty: &TypeName) -> Result<(), String> {
+    if !is_managed(ty) && !string::is_string(ctx, ty) {
+        return free_strings(ctx, slot, ty);
+    }
+
+    let value = ctx.builder.build_load(ctx.get_llvm_type(ty)?, slot, "old_value")
+        .map_err(|e| format!("load owned value failed: {:?}", e))?;
+    free(ctx, value, ty)
+}
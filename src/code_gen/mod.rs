@@ -1,3 +1,7 @@
 pub mod builder;
 pub mod expressions;
-pub mod statements;
\ No newline at end of file
+pub mod managed;
+pub mod statements;
+pub mod types;
+#[cfg(test)]
+pub mod testing;
@@ -1,17 +1,26 @@
-use crate::parser::ast::{FunctionDecl, Statement, Expr};
-use crate::code_gen::builder::{CodeGenContext, ModuleValue};
-use crate::code_gen::expressions::codegen_expressions;
-use inkwell::values::*;
-use inkwell::types::{BasicTypeEnum, BasicMetadataTypeEnum};
+use crate::parser::ast::{FunctionDecl, Statement, Expr, Iteration, TypeName};
 use crate::lexer::token::Types;
+use crate::code_gen::builder::{Check, CodeGenContext, ModuleValue};
+use crate::code_gen::expressions::{codegen_expected, codegen_expressions, codegen_place};
+use crate::code_gen::types::{convert_to_type, declare_type, static_type, type_of_value, widest_type};
+use inkwell::values::*;
+use inkwell::types::{BasicMetadataTypeEnum, BasicType, BasicTypeEnum};
 use inkwell::AddressSpace;
 use crate::code_gen::builder::NativeFunc;
+use crate::code_gen::managed;
+use crate::native::{map, string, vector};
 use std::path::Path;
 use std::collections::HashMap;
 use std::fs;
 
 pub type NativeRegisterFn<'ctx> = fn(&mut CodeGenContext<'ctx>);
 
+// Where `for x in ...` reads its elements from.
+enum ElementSource<'ctx> {
+    Array(PointerValue<'ctx>, BasicTypeEnum<'ctx>),
+    Vector(PointerValue<'ctx>), // Slot holding the vector.
+}
+
 pub fn load_native_registry<'ctx>() -> HashMap<String, NativeRegisterFn<'ctx>> {
     let mut map: HashMap<String, NativeRegisterFn<'ctx>> = HashMap::new();
     let dir = "src/native";
@@ -20,10 +29,10 @@ pub fn load_native_registry<'ctx>() -> HashMap<String, NativeRegisterFn<'ctx>> {
             let path = entry.path();
             if path.is_file() && path.extension().map(|s| s == "rs").unwrap_or(false) {
                 if let Some(stem) = path.file_stem().map(|s| s.to_string_lossy().into_owned()) {
-                    let register_fn: NativeRegisterFn<'ctx> = match stem.as_str() {
-                        "io" => crate::native::io::register_native,
-                        _ => continue,
+                    let Some(module) = crate::native::module(&stem) else {
+                        continue;
                     };
+                    let register_fn: NativeRegisterFn<'ctx> = module.register;
 
                     map.insert(stem, register_fn);
                 }
@@ -37,120 +46,149 @@ pub fn load_native_registry<'ctx>() -> HashMap<String, NativeRegisterFn<'ctx>> {
 pub fn codegen_function<'ctx>(ctx: &mut CodeGenContext<'ctx>, declaration: &FunctionDecl, registry: &HashMap<String, NativeRegisterFn<'ctx>>) -> Result<FunctionValue<'ctx>, String> {
     let param_types: Vec<BasicMetadataTypeEnum> = declaration
         .params.iter()
-        .map(|_| ctx.f64_type.into())
-        .collect();
-    let fn_type = ctx.f64_type.fn_type(&param_types, false);
+        .map(|p| if p.by_reference {
+            Ok(ctx.context.ptr_type(AddressSpace::from(0)).into())
+        } else {
+            ctx.get_llvm_type(&p.param_type).map(|t| t.into())
+        })
+        .collect::<Result<_, _>>()?;
+    let return_type = declaration.return_type.clone().unwrap_or(TypeName::Builtin(Types::f64));
+    if managed::is_managed(&return_type) {
+        return Err(format!("Function '{}' can't return {:?}, pass it as an 'in out' parameter instead", declaration.name, return_type));
+    }
+    let fn_type = ctx.get_llvm_type(&return_type)?.fn_type(&param_types, false);
     let func = ctx.module.add_function(&declaration.name, fn_type, None);
     let entry = ctx.context.append_basic_block(func, "entry");
 
+    // Functions can be generated lazily from inside another body, so come back to it afterwards.
+    let previous_block = ctx.builder.get_insert_block();
+    let outer_values = std::mem::take(&mut ctx.owned_values);
+    let outer_return = std::mem::replace(&mut ctx.return_type, return_type);
     ctx.builder.position_at_end(entry);
-    for (i, param_name) in declaration.params.iter().enumerate() {
-        let param = func.get_nth_param(i as u32).unwrap();
-        let alloca = ctx.builder.build_alloca(ctx.f64_type, param_name)
+    for (i, param) in declaration.params.iter().enumerate() {
+        let value = func.get_nth_param(i as u32).unwrap();
+        ctx.variable_types.insert(param.name.clone(), param.param_type.clone());
+        if param.by_reference {
+            ctx.variables.insert(param.name.clone(), value.into_pointer_value());
+            continue;
+        }
+        // Vectors, maps and strings passed by value are copied, so the function can change and free its own.
+        if managed::is_owned(ctx, &param.param_type) {
+            let owned = managed::clone_value(ctx, value, &param.param_type)?;
+            let slot = managed::declare_variable(ctx, &param.name, owned, &param.param_type)?;
+            ctx.variables.insert(param.name.clone(), slot);
+            continue;
+        }
+
+        let alloca = ctx.builder.build_alloca(value.get_type(), &param.name)
             .map_err(|e| format!("alloca failed: {:?}", e))?;
 
-        ctx.builder.build_store(alloca, param)
+        ctx.builder.build_store(alloca, value)
             .map_err(|e| format!("store param failed: {:?}", e))?;
-        ctx.variables.insert(param_name.clone(), alloca);
+        ctx.variables.insert(param.name.clone(), alloca);
     }
 
-    for stmt in &declaration.body {
-        codegen_statements(ctx, stmt, registry)?;
-    }
+    codegen_block(ctx, &declaration.body, registry)?;
 
-    if func.get_last_basic_block().unwrap().get_terminator().is_none() {
-        ctx.builder.build_return(Some(&ctx.f64_type.const_float(0.0)));
+    // Loops and checks append blocks after the one the body ends in, so look at the current block.
+    if ctx.builder.get_insert_block().and_then(|b| b.get_terminator()).is_none() {
+        managed::free_owned(ctx)?;
+        let implicit = ctx.default_value(&ctx.return_type.clone())?;
+        ctx.build_return(Some(implicit));
     }
+    ctx.owned_values = outer_values;
+    ctx.return_type = outer_return;
 
     if !func.verify(true) {
         return Err("Function verification failed".to_string());
     }
 
+    if let Some(block) = previous_block {
+        ctx.builder.position_at_end(block);
+    }
+
     Ok(func)
 }
 
+// Comparisons already give an i1, other values count as true when they're non-zero or non-null.
+fn build_condition<'ctx>(ctx: &CodeGenContext<'ctx>, value: BasicValueEnum<'ctx>, name: &str) -> Result<IntValue<'ctx>, String> {
+    match value {
+        BasicValueEnum::IntValue(iv) if iv.get_type().get_bit_width() == 1 => Ok(iv),
+        BasicValueEnum::IntValue(iv) => ctx.builder.build_int_compare(inkwell::IntPredicate::NE, iv, iv.get_type().const_zero(), name),
+        BasicValueEnum::FloatValue(fv) => ctx.builder.build_float_compare(inkwell::FloatPredicate::ONE, fv, fv.get_type().const_zero(), name),
+        BasicValueEnum::PointerValue(pv) => ctx.builder.build_is_not_null(pv, name),
+        _ => return Err("Cannot use this type as a condition".into()),
+    }.map_err(|e| format!("condition compare failed: {:?}", e))
+}
+
+// A `pragma Suppress` only lasts until the end of the block it's written in.
+fn codegen_block<'ctx>(ctx: &mut CodeGenContext<'ctx>, statements: &[Statement], registry: &HashMap<String, NativeRegisterFn<'ctx>>) -> Result<(), String> {
+    let outer_suppressed = ctx.suppressed.clone();
+    let result = statements.iter().try_for_each(|s| codegen_statements(ctx, s, registry));
+    ctx.suppressed = outer_suppressed;
+    result
+}
+
 pub fn codegen_statements<'ctx>(ctx: &mut CodeGenContext<'ctx>, stmt: &Statement, registry: &HashMap<String, NativeRegisterFn<'ctx>>) -> Result<(), String> {
     match stmt {
         Statement::Expression(expr) => {
-            codegen_expressions(ctx, expr, registry)
+            let value = codegen_expressions(ctx, expr, registry)
                 .map_err(|e| format!("expr codegen failed: {:?}", e))?;
+            // Nobody keeps a String returned by a call made for its side effects.
+            string::free_temporary(ctx, expr, value)?;
             Ok(())
         },
 
-        Statement::VarDecl { name, var_type, initializer } => {
-            let llvm_type: BasicTypeEnum<'ctx> = match var_type {
-                Some(ty) => match ty {
-                    Types::i8 | Types::i32 | Types::i64 | Types::u8 | Types::u32 | Types::u64 | Types::Boolean | Types::Char =>
-                        ctx.context.i64_type().into(),
-                    Types::f32 | Types::f64 =>
-                        ctx.context.f64_type().into(),
-                    Types::String | Types::Array | Types::Object =>
-                        ctx.context.ptr_type(AddressSpace::from(0)).into(),
-                },
-                None => ctx.context.i64_type().into(),
+        Statement::VarDecl { name, var_type, initializer, span } => {
+            let value = match (initializer, var_type) {
+                (Some(e), Some(ty)) => Some(codegen_expected(ctx, e, ty, registry).map_err(|e| format!("initializer failed: {:?}", e))?),
+                (Some(e), None) => Some(codegen_expressions(ctx, e, registry).map_err(|e| format!("initializer failed: {:?}", e))?),
+                (None, _) => None,
             };
 
-            let default_val = match var_type {
-                Some(ty) => match ty {
-                    Types::i8 | Types::i32 | Types::i64 | Types::u8 | Types::u32 | Types::u64 => {
-                        llvm_type.into_int_type().const_int(0, false).into()
-                    },
-                    Types::f32 | Types::f64 => {
-                        llvm_type.into_float_type().const_float(0.0).into()
-                    },
-                    Types::Boolean => llvm_type.into_int_type().const_int(0, false).into(),
-                    Types::Char => llvm_type.into_int_type().const_int(0, false).into(),
-                    Types::String | Types::Array | Types::Object => llvm_type.into_pointer_type().const_null().into(),
-                },
-                None => llvm_type.into_int_type().const_int(0, false).into(),
+            let var_type = match (var_type, &value, initializer) {
+                (Some(ty), _, _) => ty.clone(),
+                (None, Some(v), Some(e)) => static_type(ctx, e).unwrap_or_else(|| type_of_value(v)),
+                (None, _, _) => TypeName::Builtin(Types::i64),
             };
+            let llvm_type = ctx.get_llvm_type(&var_type)?;
 
-            let value = if let Some(e) = initializer {
-                codegen_expressions(ctx, e, registry).map_err(|e| format!("initializer failed: {:?}", e))?
-            } else {
-                default_val
+            let value = match (value, initializer) {
+                (Some(v), Some(e)) => {
+                    let source = static_type(ctx, e);
+                    convert_to_type(ctx, v, source.as_ref(), &var_type, *span)?
+                }
+                _ => ctx.default_value(&var_type)?,
             };
 
-            let pointer = ctx.builder.build_alloca(llvm_type, name)
-                .map_err(|e| format!("alloca for var '{}' failed: {:?}", name, e))?;
+            if managed::is_owned(ctx, &var_type) {
+                let value = match initializer {
+                    Some(e) => managed::take_ownership(ctx, value, &var_type, e)?,
+                    None => value,
+                };
+                let slot = managed::declare_variable(ctx, name, value, &var_type)?;
+                ctx.variables.insert(name.clone(), slot);
+                ctx.variable_types.insert(name.clone(), var_type);
+                return Ok(());
+            }
+
+            let pointer = ctx.build_entry_alloca(llvm_type, name, None)?;
 
             ctx.builder.build_store(pointer, value)
                 .map_err(|e| format!("store for var '{}' failed: {:?}", name, e))?;
             ctx.variables.insert(name.clone(), pointer);
+            ctx.variable_types.insert(name.clone(), var_type);
             Ok(())
         }
 
+        Statement::TypeDecl { name, definition } => declare_type(ctx, name, definition),
 
-        Statement::Block(statements) => {
-            for s in statements {
-                codegen_statements(ctx, s, registry)?;
-            }
-            Ok(())
-        },
+        Statement::Block(statements) => codegen_block(ctx, statements, registry),
 
         Statement::If { condition, then_branch, else_branch } => {
             let cond_val = codegen_expressions(ctx, condition, registry).map_err(|e| format!("if condition failed: {:?}", e))?;
             // println!("Condition value: {:?}", cond_val);
-            let cond_i1 = match cond_val {
-                BasicValueEnum::IntValue(iv) => {
-                    let zero = iv.get_type().const_zero();
-                    ctx.builder.build_int_compare(inkwell::IntPredicate::NE, iv, zero, "ifcond")
-                }
-                BasicValueEnum::FloatValue(fv) => {
-                    let zero_f = ctx.context.f64_type().const_float(0.0);
-                    ctx.builder.build_float_compare(inkwell::FloatPredicate::ONE, fv, zero_f, "ifcond")
-                }
-                BasicValueEnum::PointerValue(pv) => {
-                    let null_ptr = pv.get_type().const_null();
-                    ctx.builder.build_int_compare(
-                        inkwell::IntPredicate::NE,
-                        pv.const_to_int(ctx.context.i64_type()),
-                        null_ptr.const_to_int(ctx.context.i64_type()),
-                        "ifcond_ptr"
-                    )
-                }
-                _ => return Err("Cannot use this type in if condition".into()),
-            }.unwrap();
+            let cond_i1 = build_condition(ctx, cond_val, "ifcond")?;
 
             let func = ctx.builder.get_insert_block().ok_or("No insert block")?.get_parent().ok_or("No parent function")?;
             let then_block = ctx.context.append_basic_block(func, "then");
@@ -184,11 +222,8 @@ pub fn codegen_statements<'ctx>(ctx: &mut CodeGenContext<'ctx>, stmt: &Statement
             ctx.builder.position_at_end(cond_block);
 
             let cond_val = codegen_expressions(ctx, condition, registry)
-                .map_err(|e| format!("while condition failed: {:?}", e))?
-                .into_float_value();
-            let zero = ctx.context.f64_type().const_float(0.0);
-            let comparison = ctx.builder.build_float_compare(inkwell::FloatPredicate::ONE, cond_val, zero, "whilecond")
-                .map_err(|e| format!("float compare failed: {:?}", e))?;
+                .map_err(|e| format!("while condition failed: {:?}", e))?;
+            let comparison = build_condition(ctx, cond_val, "whilecond")?;
 
             ctx.builder.build_conditional_branch(comparison, body_block, merge)
                 .map_err(|e| format!("conditional branch failed: {:?}", e))?;
@@ -200,18 +235,269 @@ pub fn codegen_statements<'ctx>(ctx: &mut CodeGenContext<'ctx>, stmt: &Statement
             Ok(())
         },
 
+        Statement::Case { subject, arms } => {
+            let subject_type = static_type(ctx, subject);
+            let literals = subject_type.as_ref().and_then(|t| ctx.enum_literals_of(t))
+                .ok_or("case subject must be of an enumeration type")?;
+
+            let mut choices = Vec::new();
+            let mut has_others = false;
+            for arm in arms {
+                match &arm.choice {
+                    Some(choice) if !literals.contains(choice) => {
+                        return Err(format!("'{}' is not a value of {:?}", choice, subject_type.as_ref().unwrap()));
+                    }
+                    Some(choice) if choices.contains(choice) => return Err(format!("Duplicate case choice '{}'", choice)),
+                    Some(choice) => choices.push(choice.clone()),
+                    None if has_others => return Err("Duplicate 'when others' in case".to_string()),
+                    None => has_others = true,
+                }
+            }
+            if !has_others {
+                let missing: Vec<&String> = literals.iter().filter(|l| !choices.contains(*l)).collect();
+                if !missing.is_empty() {
+                    return Err(format!("case does not cover {:?}, add them or 'when others'", missing));
+                }
+            }
+
+            let value = match codegen_expressions(ctx, subject, registry)? {
+                BasicValueEnum::IntValue(iv) => iv,
+                _ => return Err("case subject must be a discrete value".to_string()),
+            };
+
+            // Inside `when Circle` of `case s.kind`, accesses to `s`'s Circle fields are known to be safe.
+            let proven_record = match subject {
+                Expr::Variable { var_name, .. } => ctx.discriminant_owner(var_name),
+                _ => None,
+            };
+
+            let func = ctx.builder.get_insert_block().ok_or("No insert block")?.get_parent().ok_or("No parent function")?;
+            let merge = ctx.context.append_basic_block(func, "casecont");
+            let arm_blocks: Vec<_> = arms.iter()
+                .map(|arm| ctx.context.append_basic_block(func, &format!("case_{}", arm.choice.as_deref().unwrap_or("others"))))
+                .collect();
+
+            let default_block = match arms.iter().position(|arm| arm.choice.is_none()) {
+                Some(i) => arm_blocks[i],
+                None => {
+                    let block = ctx.context.append_basic_block(func, "case_none");
+                    let builder = ctx.context.create_builder();
+                    builder.position_at_end(block);
+                    builder.build_unreachable().map_err(|e| format!("unreachable failed: {:?}", e))?;
+                    block
+                }
+            };
+
+            let mut cases = Vec::new();
+            for (arm, block) in arms.iter().zip(&arm_blocks) {
+                if let Some(choice) = &arm.choice {
+                    let (_, position) = ctx.enum_literal(choice).ok_or_else(|| format!("Unknown choice '{}'", choice))?;
+                    cases.push((value.get_type().const_int(position, false), *block));
+                }
+            }
+            ctx.builder.build_switch(value, default_block, &cases)
+                .map_err(|e| format!("case switch failed: {:?}", e))?;
+
+            for (arm, block) in arms.iter().zip(arm_blocks) {
+                ctx.builder.position_at_end(block);
+
+                let previous = match (&proven_record, &arm.choice) {
+                    (Some(record), Some(choice)) => Some(ctx.known_variants.insert(record.clone(), choice.clone())),
+                    _ => None,
+                };
+                codegen_block(ctx, &arm.body, registry)?;
+                match (previous, &proven_record) {
+                    (Some(Some(old)), Some(record)) => { ctx.known_variants.insert(record.clone(), old); },
+                    (Some(None), Some(record)) => { ctx.known_variants.remove(record); },
+                    _ => {},
+                }
+
+                if ctx.builder.get_insert_block().and_then(|b| b.get_terminator()).is_none() {
+                    ctx.builder.build_unconditional_branch(merge)
+                        .map_err(|e| format!("branch out of case failed: {:?}", e))?;
+                }
+            }
+
+            ctx.builder.position_at_end(merge);
+            Ok(())
+        },
+
+        Statement::For { var, iteration, body, span } => {
+            // Ranges count the loop variable itself, arrays and vectors count a hidden index and copy each element into it.
+            let (counter_type, low, high, elements) = match iteration {
+                Iteration::Range { low, high } => {
+                    // The loop variable has the wider type of the two bounds, i64 unless one of them
+                    // has a discrete type. Both are converted into it like an assignment, checks and all.
+                    let bounds = [
+                        (codegen_expressions(ctx, low, registry)?, static_type(ctx, low)),
+                        (codegen_expressions(ctx, high, registry)?, static_type(ctx, high)),
+                    ];
+                    let counter_type = widest_type(ctx, &bounds, *span)?
+                        .filter(|t| ctx.static_bounds(t).is_some())
+                        .unwrap_or(TypeName::Builtin(Types::i64));
+                    let [low, high] = bounds.map(|(value, source)| convert_to_type(ctx, value, source.as_ref(), &counter_type, *span));
+                    (counter_type, low?.into_int_value(), high?.into_int_value(), None)
+                }
+                Iteration::Elements(items) => match static_type(ctx, items) {
+                    Some(items_type @ (TypeName::Vector(_) | TypeName::Map { .. })) => {
+                        // Elements are read through the variable on every iteration, in case the body
+                        // replaces the vector. A vector built just for the loop, like the keys of a
+                        // map, is owned like any variable.
+                        let (vector_slot, element) = match (&items_type, items) {
+                            (TypeName::Map { key, .. }, _) => {
+                                let map_handle = codegen_expressions(ctx, items, registry)?;
+                                let keys = map::keys(ctx, map_handle)?;
+                                let keys_type = TypeName::Vector(key.clone());
+                                (managed::declare_variable(ctx, "for_keys", keys, &keys_type)?, key.clone())
+                            }
+                            (TypeName::Vector(element), Expr::Variable { .. }) => (codegen_place(ctx, items, registry)?.0, element.clone()),
+                            (TypeName::Vector(element), _) => {
+                                let handle = codegen_expressions(ctx, items, registry)?;
+                                (managed::declare_variable(ctx, "for_vector", handle, &items_type)?, element.clone())
+                            }
+                            _ => unreachable!(),
+                        };
+                        let handle = ctx.builder.build_load(vector::handle_type(ctx), vector_slot, "for_vector")
+                            .map_err(|e| format!("load failed: {:?}", e))?;
+                        let element_slot = ctx.build_entry_alloca(ctx.get_llvm_type(&element)?, var, None)?;
+
+                        let len = vector::length(ctx, handle)?;
+                        let last_index = ctx.builder.build_int_sub(len, ctx.i64_type.const_int(1, false), "last_index")
+                            .map_err(|e| format!("int sub failed: {:?}", e))?;
+                        (TypeName::Builtin(Types::i64), ctx.i64_type.const_zero(), last_index, Some((ElementSource::Vector(vector_slot), element_slot, *element)))
+                    }
+                    _ => {
+                        let (array_ptr, array_type) = match items {
+                            Expr::Variable { .. } | Expr::Index { .. } => codegen_place(ctx, items, registry)?,
+                            _ => {
+                                let value = codegen_expressions(ctx, items, registry)?;
+                                let array_type = type_of_value(&value);
+                                let temp = ctx.build_entry_alloca(value.get_type(), "for_array", None)?;
+                                ctx.builder.build_store(temp, value).map_err(|e| format!("store failed: {:?}", e))?;
+                                (temp, Some(array_type))
+                            }
+                        };
+                        let Some(TypeName::Array { element, first, last }) = array_type else {
+                            return Err(format!("for '{}' needs a range, an array or a vector, got {:?}", var, array_type));
+                        };
+                        let array_llvm_type = ctx.get_llvm_type(&TypeName::Array { element: element.clone(), first, last })?;
+                        let element_slot = ctx.build_entry_alloca(ctx.get_llvm_type(&element)?, var, None)?;
+
+                        let last_index = (last - first + 1).max(0) - 1;
+                        (
+                            TypeName::Builtin(Types::i64),
+                            ctx.i64_type.const_zero(),
+                            ctx.i64_type.const_int(last_index as u64, true),
+                            Some((ElementSource::Array(array_ptr, array_llvm_type), element_slot, *element)),
+                        )
+                    }
+                },
+            };
+
+            let counter_llvm_type = ctx.get_llvm_type(&counter_type)?;
+            let counter = ctx.build_entry_alloca(counter_llvm_type, if elements.is_some() { "for_index" } else { var }, None)?;
+            ctx.builder.build_store(counter, low).map_err(|e| format!("store failed: {:?}", e))?;
+
+            // The loop variable shadows any outer variable of the same name until the loop ends.
+            let shadowed = (ctx.variables.remove(var), ctx.variable_types.remove(var));
+            match &elements {
+                Some((_, slot, element)) => {
+                    ctx.variables.insert(var.clone(), *slot);
+                    ctx.variable_types.insert(var.clone(), element.clone());
+                }
+                None => {
+                    ctx.variables.insert(var.clone(), counter);
+                    ctx.variable_types.insert(var.clone(), counter_type.clone());
+                }
+            }
+
+            let func = ctx.builder.get_insert_block().ok_or("No insert block")?.get_parent().ok_or("No parent function")?;
+            let body_block = ctx.context.append_basic_block(func, "forbody");
+            let latch_block = ctx.context.append_basic_block(func, "forlatch");
+            let step_block = ctx.context.append_basic_block(func, "forstep");
+            let merge = ctx.context.append_basic_block(func, "forcont");
+
+            let enter_predicate = if ctx.is_unsigned(&counter_type) { inkwell::IntPredicate::ULE } else { inkwell::IntPredicate::SLE };
+            let enter = ctx.builder.build_int_compare(enter_predicate, low, high, "forenter")
+                .map_err(|e| format!("int compare failed: {:?}", e))?;
+            ctx.builder.build_conditional_branch(enter, body_block, merge)
+                .map_err(|e| format!("conditional branch failed: {:?}", e))?;
+
+            ctx.builder.position_at_end(body_block);
+            if let Some((source, slot, element)) = &elements {
+                let index = ctx.builder.build_load(counter_llvm_type, counter, "for_index")
+                    .map_err(|e| format!("load failed: {:?}", e))?.into_int_value();
+                let element_ptr = match source {
+                    ElementSource::Array(array_ptr, array_llvm_type) => unsafe {
+                        ctx.builder.build_in_bounds_gep(*array_llvm_type, *array_ptr, &[ctx.i64_type.const_zero(), index], "elem_ptr")
+                    }.map_err(|e| format!("element access failed: {:?}", e))?,
+                    ElementSource::Vector(vector_slot) => {
+                        let handle = ctx.builder.build_load(vector::handle_type(ctx), *vector_slot, "for_vector")
+                            .map_err(|e| format!("load failed: {:?}", e))?;
+                        vector::element_ptr(ctx, handle, index, "vector shrank while iterating over it", *span)?
+                    }
+                };
+                let value = ctx.builder.build_load(ctx.get_llvm_type(element)?, element_ptr, "elem")
+                    .map_err(|e| format!("element load failed: {:?}", e))?;
+                ctx.builder.build_store(*slot, value).map_err(|e| format!("store failed: {:?}", e))?;
+            }
+            codegen_block(ctx, body, registry)?;
+            if ctx.builder.get_insert_block().and_then(|b| b.get_terminator()).is_none() {
+                ctx.builder.build_unconditional_branch(latch_block)
+                    .map_err(|e| format!("branch to latch failed: {:?}", e))?;
+            }
+
+            // Compare before stepping so a range ending at the type's last value doesn't overflow.
+            ctx.builder.position_at_end(latch_block);
+            let current = ctx.builder.build_load(counter_llvm_type, counter, "for_current")
+                .map_err(|e| format!("load failed: {:?}", e))?.into_int_value();
+            let done = ctx.builder.build_int_compare(inkwell::IntPredicate::EQ, current, high, "fordone")
+                .map_err(|e| format!("int compare failed: {:?}", e))?;
+            ctx.builder.build_conditional_branch(done, merge, step_block)
+                .map_err(|e| format!("conditional branch failed: {:?}", e))?;
+
+            ctx.builder.position_at_end(step_block);
+            let next = ctx.builder.build_int_add(current, current.get_type().const_int(1, false), "for_next")
+                .map_err(|e| format!("int add failed: {:?}", e))?;
+            ctx.builder.build_store(counter, next).map_err(|e| format!("store failed: {:?}", e))?;
+            ctx.builder.build_unconditional_branch(body_block)
+                .map_err(|e| format!("branch back to body failed: {:?}", e))?;
+
+            ctx.builder.position_at_end(merge);
+            ctx.variables.remove(var);
+            ctx.variable_types.remove(var);
+            if let (Some(pointer), var_type) = shadowed {
+                ctx.variables.insert(var.clone(), pointer);
+                if let Some(var_type) = var_type {
+                    ctx.variable_types.insert(var.clone(), var_type);
+                }
+            }
+            Ok(())
+        },
+
         Statement::Function(declaration) => {
+            ctx.register_adan_fn("", declaration.clone());
             codegen_function(ctx, declaration, registry)?;
             Ok(())
         },
 
-        Statement::Return { value } => {
+        Statement::Return { value, span } => {
+            let return_type = ctx.return_type.clone();
             let return_value = if let Some(v) = value {
-                codegen_expressions(ctx, v, registry).map_err(|e| format!("return expr failed: {:?}", e))?
+                let returned = codegen_expected(ctx, v, &return_type, registry).map_err(|e| format!("return expr failed: {:?}", e))?;
+                let source = static_type(ctx, v);
+                let returned = convert_to_type(ctx, returned, source.as_ref(), &return_type, *span)?;
+                // The caller gets a String of its own, since the function frees its variables now.
+                if managed::is_owned(ctx, &return_type) {
+                    managed::take_ownership(ctx, returned, &return_type, v)?
+                } else {
+                    returned
+                }
             } else {
-                ctx.f64_type.const_float(0.0).into()
+                ctx.default_value(&return_type)?
             };
 
+            managed::free_owned(ctx)?;
             ctx.builder.build_return(Some(&return_value));
             Ok(())
         },
@@ -236,6 +522,85 @@ pub fn codegen_statements<'ctx>(ctx: &mut CodeGenContext<'ctx>, stmt: &Statement
             Ok(())
         }
 
+        Statement::Pragma { name, args, span } => match name.as_str() {
+            "Suppress" => {
+                if args.is_empty() {
+                    return Err(format!("{}:{}:{}: pragma Suppress needs the checks to suppress", ctx.source_name, span.line, span.column));
+                }
+                for arg in args {
+                    let checks = Check::from_name(arg)
+                        .ok_or_else(|| format!("{}:{}:{}: unknown check '{}'", ctx.source_name, span.line, span.column, arg))?;
+                    ctx.suppressed.extend_from_slice(checks);
+                }
+                Ok(())
+            }
+            _ => Err(format!("{}:{}:{}: unknown pragma '{}'", ctx.source_name, span.line, span.column, name)),
+        },
+
         _ => unimplemented!(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::code_gen::testing::*;
+
+    #[test]
+    fn ranges_over_an_enumeration_count_in_the_enumeration() {
+        let source = "
+            type Color is (Red, Green, Blue);
+            program -> main: i32 {
+                local n: i32 -> 0;
+                for c in Color'Range {
+                    local d: Color -> c;
+                    n -> n + 1;
+                }
+                for c in Color'First..Color'Last {
+                    local d: Color -> c;
+                }
+                if (n /= 3) { return 1; }
+                return 0;
+            }";
+        assert_compiles(source);
+        run(source).assert_succeeded();
+    }
+
+    #[test]
+    fn range_bounds_meet_in_the_wider_type() {
+        // 300 used to be cut to the u8 of the low bound, 44, and the loop never ran.
+        run("program -> main: i32 {
+                local low: u8 -> 250;
+                local n: i64 -> 0;
+                for i in low..300 { n -> n + 1; }
+                if (n /= 51) { return 1; }
+                return 0;
+            }").assert_succeeded();
+    }
+
+    #[test]
+    fn range_bounds_reject_lossy_mixes() {
+        let error = compile_error("program -> main: i32 { local big: u64 -> 5; for i in -1..big { } return 0; }");
+        assert!(error.contains("don't convert into one another"), "{}", error);
+        let error = compile_error("program -> main: i32 { for i in 1..2.5 { } return 0; }");
+        assert!(error.contains("drops the fraction"), "{}", error);
+    }
+
+    #[test]
+    fn loop_bodies_free_the_previous_iteration_value() {
+        // Each iteration frees the vector of the one before, the last one lives on until return.
+        let source = "program -> main: i32 {
+                local i: i64 -> 0;
+                while (i < 200000) {
+                    local v: Vector<i64> -> [i, i, i, i, i, i, i, i];
+                    local s: String -> \"iteration\";
+                    i -> i + 1;
+                }
+                if (v'Length /= 8) { return 1; }
+                if (v[0] /= 199999) { return 2; }
+                return 0;
+            }";
+        let outside = compile(source, |ctx| allocas_outside_entry(ctx.module.get_function("main").unwrap())).unwrap();
+        assert_eq!(outside, 0);
+        run(source).assert_succeeded();
+    }
+}
@@ -0,0 +1,109 @@
+// Shared by the compiler's tests: generating ADAN source in memory, and building and running it
+// against the runtime for the checks that only fail once the program runs.
+use std::fs;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use inkwell::context::Context;
+use inkwell::values::{FunctionValue, InstructionOpcode};
+use crate::code_gen::builder::CodeGenContext;
+
+pub const SOURCE_NAME: &str = "test.adn";
+
+// Exit status of a program that stopped in adan_panic, see adan_rt/src/panic.rs.
+const PANIC_STATUS: i32 = 101;
+
+// Generates `source` and hands the result to `inspect`, or returns the compile error.
+pub fn compile_with<R>(source: &str, checks: bool, inspect: impl FnOnce(&CodeGenContext<'_>) -> R) -> Result<R, String> {
+    let context = Context::create();
+    let mut ctx = CodeGenContext::new(&context, "adan_test");
+    ctx.source_name = SOURCE_NAME.to_string();
+    ctx.checks = checks;
+    crate::generate(&mut ctx, source)?;
+    Ok(inspect(&ctx))
+}
+
+pub fn compile<R>(source: &str, inspect: impl FnOnce(&CodeGenContext<'_>) -> R) -> Result<R, String> {
+    compile_with(source, true, inspect)
+}
+
+// The compile error for `source`, which must not compile.
+pub fn compile_error(source: &str) -> String {
+    match compile(source, |_| ()) {
+        Ok(()) => panic!("expected a compile error for:\n{}", source),
+        Err(e) => e,
+    }
+}
+
+pub fn assert_compiles(source: &str) {
+    if let Err(e) = compile(source, |_| ()) {
+        panic!("expected to compile, got '{}' for:\n{}", e, source);
+    }
+}
+
+// Allocas outside the entry block of `func`, which run again on every loop iteration.
+pub fn allocas_outside_entry(func: FunctionValue<'_>) -> usize {
+    func.get_basic_blocks().into_iter().skip(1)
+        .flat_map(|block| std::iter::successors(block.get_first_instruction(), |i| i.get_next_instruction()))
+        .filter(|instruction| instruction.get_opcode() == InstructionOpcode::Alloca)
+        .count()
+}
+
+// Runtime checks left in `func`, each one branches to a block of its own on failure.
+pub fn check_blocks(func: FunctionValue<'_>) -> usize {
+    func.get_basic_blocks().into_iter()
+        .filter(|block| block.get_name().to_str().is_ok_and(|name| name.starts_with("check_fail") || name.starts_with("disc_fail")))
+        .count()
+}
+
+// `body` as the body of main, which starts on line 3.
+pub fn in_main(includes: &str, body: &str) -> String {
+    format!("{}\nprogram -> main: i32 {{\n{}\nreturn 0;\n}}", includes, body)
+}
+
+// What a program printed and how it exited.
+pub struct Run {
+    pub status: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl Run {
+    // Asserts the program stopped in adan_panic with `message` at `line`.
+    pub fn assert_panicked(&self, line: u32, message: &str) {
+        assert_eq!(self.status, Some(PANIC_STATUS), "expected a panic, stderr: {}", self.stderr);
+        let expected = format!("{}:{}:", SOURCE_NAME, line);
+        assert!(self.stderr.starts_with(&expected) && self.stderr.contains(&format!("panic: {}", message)),
+            "expected '{}...panic: {}', got '{}'", expected, message, self.stderr);
+    }
+
+    pub fn assert_succeeded(&self) {
+        assert_eq!(self.status, Some(0), "expected success, stderr: {}", self.stderr);
+    }
+}
+
+static NEXT_DIR: AtomicUsize = AtomicUsize::new(0);
+
+// Compiles, links and runs `source`, whose `main` should return an i32 exit status.
+pub fn run_with(source: &str, checks: bool) -> Run {
+    let dir = std::env::temp_dir().join(format!("adan_test_{}_{}", std::process::id(), NEXT_DIR.fetch_add(1, Ordering::Relaxed)));
+    fs::create_dir_all(&dir).expect("create the test directory");
+
+    let ir = dir.join("output.ll");
+    compile_with(source, checks, |ctx| ctx.module.print_to_file(&ir).map_err(|e| e.to_string()))
+        .and_then(|written| written)
+        .unwrap_or_else(|e| panic!("expected to compile, got '{}' for:\n{}", e, source));
+    crate::create_binary(&dir)
+        .unwrap_or_else(|e| panic!("building the program failed, run `cargo build` first for libadan_rt.a: {}", e));
+
+    let output = Command::new(dir.join("output_exec")).output().expect("run the program");
+    let _ = fs::remove_dir_all(&dir);
+    Run {
+        status: output.status.code(),
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+    }
+}
+
+pub fn run(source: &str) -> Run {
+    run_with(source, true)
+}
@@ -0,0 +1,878 @@
+use crate::code_gen::builder::{Check, CodeGenContext};
+use crate::code_gen::managed;
+use crate::native::{self, map, string, vector};
+use crate::lexer::token::{Span, Types};
+use crate::parser::ast::{Expr, FieldDecl, Literal, Operation, TypeDefinition, TypeName, Variant};
+use inkwell::types::{BasicType, BasicTypeEnum, StructType};
+use inkwell::values::{AggregateValueEnum, ArrayValue, BasicValueEnum, IntValue};
+use inkwell::{FloatPredicate, IntPredicate};
+
+// A user declared type with its base resolved down to a builtin.
+#[derive(Debug, Clone)]
+pub enum TypeDecl {
+    Range {
+        base: Types,
+        bounds: Option<(i64, i64)>,
+    },
+    // `type Byte is mod 2**8;` holds 0..modulus-1 in the smallest unsigned base that fits, and
+    // arithmetic wraps around instead of overflowing.
+    Modular {
+        base: Types,
+        modulus: u128,
+    },
+    // Literals are numbered from zero in declaration order and stored as i32.
+    Enumeration {
+        literals: Vec<String>,
+    },
+    // Lowered to an LLVM named struct of the same name, fields in declaration order with the
+    // discriminant first. A variant part adds a trailing `[N x i64]` payload that each variant
+    // (an LLVM struct named `Record.Choice`) is overlaid onto.
+    Record {
+        fields: Vec<FieldDecl>,
+        variant: Option<VariantPart>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct VariantPart {
+    pub discriminant: String,
+    pub variants: Vec<Variant>,
+}
+
+// Where a field lives inside its record's LLVM struct.
+pub enum FieldLocation {
+    Direct(u32),
+    Variant { payload: u32, choice: String, index: u32 },
+}
+
+// Smallest and largest values of a builtin integer type that fit in an i64, u64 stops at i64::MAX.
+// Proofs about values go through `representable`, which has all of it.
+pub fn builtin_bounds(ty: Types) -> Option<(i64, i64)> {
+    match ty {
+        Types::i8 => Some((i8::MIN as i64, i8::MAX as i64)),
+        Types::i32 => Some((i32::MIN as i64, i32::MAX as i64)),
+        Types::i64 => Some((i64::MIN, i64::MAX)),
+        Types::u8 => Some((0, u8::MAX as i64)),
+        Types::u32 => Some((0, u32::MAX as i64)),
+        Types::u64 => Some((0, i64::MAX)),
+        _ => None,
+    }
+}
+
+// Exact range of a builtin integer type, including the top half of u64.
+fn representable(ty: Types) -> Option<(i128, i128)> {
+    match ty {
+        Types::u64 => Some((0, u64::MAX as i128)),
+        _ => builtin_bounds(ty).map(|(low, high)| (low as i128, high as i128)),
+    }
+}
+
+// Mantissa bits of a float type, integers up to this width convert exactly.
+fn mantissa_bits(ty: Types) -> Option<u32> {
+    match ty {
+        Types::f32 => Some(24),
+        Types::f64 => Some(53),
+        _ => None,
+    }
+}
+
+pub fn declare_type<'ctx>(ctx: &mut CodeGenContext<'ctx>, name: &str, definition: &TypeDefinition) -> Result<(), String> {
+    if ctx.types.contains_key(name) {
+        return Err(format!("Type '{}' is already declared", name));
+    }
+
+    let decl = match definition {
+        TypeDefinition::Range { base: None, bounds: Some((low, high)) } => {
+            // Like Ada, a new integer type gets the smallest base that holds its range.
+            let base = if *low >= i32::MIN as i64 && *high <= i32::MAX as i64 { Types::i32 } else { Types::i64 };
+            TypeDecl::Range { base, bounds: Some((*low, *high)) }
+        }
+        TypeDefinition::Range { base: Some(base), bounds } => {
+            let base_type = ctx.resolve_builtin(base)?;
+            if let Some((low, high)) = bounds {
+                let (base_low, base_high) = ctx.static_bounds(base)
+                    .ok_or_else(|| format!("Range constraint on '{}' requires an integer base type", name))?;
+                if (*low as i128) < base_low || *high as i128 > base_high {
+                    return Err(format!("Range {}..{} of '{}' does not fit its base type ({}..{})", low, high, name, base_low, base_high));
+                }
+            }
+
+            TypeDecl::Range { base: base_type, bounds: bounds.or_else(|| ctx.range_bounds(base)) }
+        }
+        TypeDefinition::Range { base: None, bounds: None } => {
+            return Err(format!("Type '{}' needs a range or a base type", name));
+        }
+        TypeDefinition::Modular { modulus } => {
+            let base = match *modulus {
+                0 | 1 => return Err(format!("Modulus of '{}' must be at least 2", name)),
+                m if m <= 1 << 8 => Types::u8,
+                m if m <= 1 << 32 => Types::u32,
+                m if m <= 1 << 64 => Types::u64,
+                _ => return Err(format!("Modulus of '{}' does not fit in 64 bits", name)),
+            };
+            TypeDecl::Modular { base, modulus: *modulus }
+        }
+        TypeDefinition::Enumeration { literals } => {
+            for (position, literal) in literals.iter().enumerate() {
+                if ctx.enum_literals.contains_key(literal) {
+                    return Err(format!("Enumeration literal '{}' is already declared", literal));
+                }
+                ctx.enum_literals.insert(literal.clone(), (name.to_string(), position as u64));
+            }
+
+            TypeDecl::Enumeration { literals: literals.clone() }
+        }
+        TypeDefinition::Record { discriminant, fields, variants } => {
+            let fields: Vec<FieldDecl> = discriminant.iter().chain(fields).cloned().collect();
+            let variant_fields = variants.iter().flat_map(|v| &v.fields);
+            let mut seen: Vec<&str> = Vec::new();
+            for field in fields.iter().chain(variant_fields) {
+                if seen.contains(&field.name.as_str()) {
+                    return Err(format!("Field '{}' is declared twice in record '{}'", field.name, name));
+                }
+                seen.push(&field.name);
+                if managed::is_managed(&field.field_type) {
+                    return Err(format!("Field '{}' of record '{}' can't be a {}, records are copied by value", field.name, name, type_display(&field.field_type)));
+                }
+            }
+
+            let mut field_types = fields.iter()
+                .map(|f| ctx.get_llvm_type(&f.field_type))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let variant = match discriminant {
+                Some(disc) if !variants.is_empty() => {
+                    let literals = ctx.enum_literals_of(&disc.field_type)
+                        .ok_or_else(|| format!("Discriminant '{}' of '{}' must be an enumeration", disc.name, name))?;
+                    for (i, variant) in variants.iter().enumerate() {
+                        if !literals.contains(&variant.choice) {
+                            return Err(format!("'{}' is not a value of the discriminant of '{}'", variant.choice, name));
+                        }
+                        if variants[..i].iter().any(|v| v.choice == variant.choice) {
+                            return Err(format!("Variant '{}' is declared twice in record '{}'", variant.choice, name));
+                        }
+                    }
+                    if let Some(missing) = literals.iter().find(|l| !variants.iter().any(|v| v.choice == **l)) {
+                        return Err(format!("Variant part of '{}' does not cover '{}'", name, missing));
+                    }
+
+                    let mut payload_words = 0;
+                    for variant in variants {
+                        let types = variant.fields.iter()
+                            .map(|f| ctx.get_llvm_type(&f.field_type))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        payload_words = payload_words.max(types.iter().map(|t| size_upper_bound(*t)).sum::<u64>() / 8);
+
+                        let variant_type = ctx.context.opaque_struct_type(&format!("{}.{}", name, variant.choice));
+                        variant_type.set_body(&types, false);
+                    }
+                    field_types.push(ctx.i64_type.array_type(payload_words as u32).into());
+
+                    Some(VariantPart { discriminant: disc.name.clone(), variants: variants.clone() })
+                }
+                _ => None,
+            };
+
+            let struct_type = ctx.context.opaque_struct_type(name);
+            struct_type.set_body(&field_types, false);
+            TypeDecl::Record { fields, variant }
+        }
+    };
+
+    ctx.types.insert(name.to_string(), decl);
+    Ok(())
+}
+
+impl<'ctx> CodeGenContext<'ctx> {
+    pub fn resolve_builtin(&self, ty: &TypeName) -> Result<Types, String> {
+        match ty {
+            TypeName::Builtin(t) => Ok(*t),
+            TypeName::Named(name) => match self.types.get(name) {
+                Some(TypeDecl::Range { base, .. }) | Some(TypeDecl::Modular { base, .. }) => Ok(*base),
+                Some(TypeDecl::Enumeration { .. }) => Ok(Types::i32),
+                Some(TypeDecl::Record { .. }) => Err(format!("'{}' is a record type", name)),
+                None => Err(format!("Unknown type '{}'", name)),
+            },
+            TypeName::Array { .. } => Err(format!("{:?} is an array type", ty)),
+            TypeName::Vector(_) => Err(format!("{:?} is a vector type", ty)),
+            TypeName::Map { .. } => Err(format!("{:?} is a map type", ty)),
+        }
+    }
+
+    pub fn record_fields(&self, name: &str) -> Result<Vec<FieldDecl>, String> {
+        match self.types.get(name) {
+            Some(TypeDecl::Record { fields, .. }) => Ok(fields.clone()),
+            Some(_) => Err(format!("'{}' is not a record type", name)),
+            None => Err(format!("Unknown type '{}'", name)),
+        }
+    }
+
+    pub fn variant_part(&self, name: &str) -> Option<VariantPart> {
+        match self.types.get(name) {
+            Some(TypeDecl::Record { variant, .. }) => variant.clone(),
+            _ => None,
+        }
+    }
+
+    pub fn variant_struct_type(&self, record: &TypeName, choice: &str) -> Result<StructType<'ctx>, String> {
+        let TypeName::Named(name) = record else {
+            return Err(format!("{:?} has no variants", record));
+        };
+        self.context.get_struct_type(&format!("{}.{}", name, choice))
+            .ok_or_else(|| format!("Record '{}' has no variant '{}'", name, choice))
+    }
+
+    // Enumeration type and position of a literal such as `Circle`.
+    pub fn enum_literal(&self, literal: &str) -> Option<(TypeName, u64)> {
+        self.enum_literals.get(literal).map(|(ty, position)| (TypeName::Named(ty.clone()), *position))
+    }
+
+    pub fn enum_literals_of(&self, ty: &TypeName) -> Option<Vec<String>> {
+        match ty {
+            TypeName::Named(name) => match self.types.get(name) {
+                Some(TypeDecl::Enumeration { literals }) => Some(literals.clone()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    // Index and type of `field` within the record `ty`.
+    pub fn record_field(&self, ty: &TypeName, field: &str) -> Result<(FieldLocation, TypeName), String> {
+        let TypeName::Named(name) = ty else {
+            return Err(format!("{:?} has no field '{}'", ty, field));
+        };
+
+        let fields = self.record_fields(name)?;
+        if let Some(index) = fields.iter().position(|f| f.name == field) {
+            return Ok((FieldLocation::Direct(index as u32), fields[index].field_type.clone()));
+        }
+
+        for variant in self.variant_part(name).map(|p| p.variants).unwrap_or_default() {
+            if let Some(index) = variant.fields.iter().position(|f| f.name == field) {
+                let location = FieldLocation::Variant { payload: fields.len() as u32, choice: variant.choice.clone(), index: index as u32 };
+                return Ok((location, variant.fields[index].field_type.clone()));
+            }
+        }
+
+        Err(format!("Record '{}' has no field '{}'", name, field))
+    }
+
+    // For a path like `s.kind` naming a record's discriminant, the path of the record (`s`).
+    pub fn discriminant_owner(&self, path: &str) -> Option<String> {
+        let (owner, field) = path.rsplit_once('.')?;
+        let TypeName::Named(record) = self.place_type(owner)? else {
+            return None;
+        };
+
+        let part = self.variant_part(&record)?;
+        (part.discriminant == field).then(|| owner.to_string())
+    }
+
+    // Drops what `case` proved about the variants of `path` and anything nested in it.
+    pub fn forget_variants(&mut self, path: &str) {
+        let nested = format!("{}.", path);
+        self.known_variants.retain(|known, _| known != path && !known.starts_with(&nested));
+    }
+
+    // Type of a variable or a `record.field` path.
+    pub fn place_type(&self, path: &str) -> Option<TypeName> {
+        let mut parts = path.split('.');
+        let mut ty = self.variable_types.get(parts.next()?).cloned()?;
+        for field in parts {
+            ty = self.record_field(&ty, field).ok()?.1;
+        }
+        Some(ty)
+    }
+
+    // Bounds declared on a range type or subtype, `None` for unconstrained types.
+    pub fn range_bounds(&self, ty: &TypeName) -> Option<(i64, i64)> {
+        match ty {
+            TypeName::Named(name) => match self.types.get(name) {
+                Some(TypeDecl::Range { bounds, .. }) => *bounds,
+                Some(TypeDecl::Modular { modulus, .. }) => Some((0, (modulus - 1).min(i64::MAX as u128) as i64)),
+                Some(TypeDecl::Enumeration { literals }) => Some((0, literals.len() as i64 - 1)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    pub fn modulus(&self, ty: &TypeName) -> Option<u128> {
+        match ty {
+            TypeName::Named(name) => match self.types.get(name) {
+                Some(TypeDecl::Modular { modulus, .. }) => Some(*modulus),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    // u8, u32, u64, modular types and Char, which divide, compare and widen as unsigned.
+    pub fn is_unsigned(&self, ty: &TypeName) -> bool {
+        matches!(self.resolve_builtin(ty), Ok(Types::u8 | Types::u32 | Types::u64 | Types::Char))
+    }
+
+    // Type of `a + b` on operands of type `ty`. Range types may leave their range halfway through
+    // a computation, so the result only has the base type, while modular types always wrap.
+    pub fn arithmetic_type(&self, ty: TypeName) -> TypeName {
+        match self.resolve_builtin(&ty) {
+            Ok(_) if self.modulus(&ty).is_some() => ty,
+            Ok(base) if builtin_bounds(base).is_some() => TypeName::Builtin(base),
+            _ => ty,
+        }
+    }
+
+    // Every value of `ty` is known to lie within these bounds, i128 so u64 and mod 2**64 types get
+    // their whole range.
+    pub fn static_bounds(&self, ty: &TypeName) -> Option<(i128, i128)> {
+        if let Some(modulus) = self.modulus(ty) {
+            return Some((0, modulus as i128 - 1));
+        }
+        self.range_bounds(ty)
+            .map(|(low, high)| (low as i128, high as i128))
+            .or_else(|| self.resolve_builtin(ty).ok().and_then(representable))
+    }
+
+    pub fn default_value(&self, ty: &TypeName) -> Result<BasicValueEnum<'ctx>, String> {
+        let llvm_type = self.get_llvm_type(ty)?;
+        if let TypeName::Vector(element) = ty {
+            return vector::build_new(self, element);
+        }
+        if let TypeName::Map { key, value } = ty {
+            return map::build_new(self, key, value);
+        }
+        if let TypeName::Array { element, first, last } = ty {
+            let element_default = self.default_value(element)?;
+            let values = vec![element_default; (last - first + 1).max(0) as usize];
+            return const_array(element_default.get_type(), &values);
+        }
+
+        if let TypeName::Named(name) = ty {
+            if let Some(TypeDecl::Record { fields, .. }) = self.types.get(name) {
+                let mut values = fields.iter()
+                    .map(|f| self.default_value(&f.field_type))
+                    .collect::<Result<Vec<_>, _>>()?;
+                // A variant payload starts zeroed, so its strings are null until they're assigned.
+                let struct_type = llvm_type.into_struct_type();
+                values.extend(struct_type.get_field_types().iter().skip(values.len()).map(|t| t.const_zero()));
+                return Ok(struct_type.const_named_struct(&values).into());
+            }
+        }
+
+        match (self.range_bounds(ty), llvm_type) {
+            // Zero may lie outside the range, so constrained types start at their low bound.
+            (Some((low, _)), BasicTypeEnum::IntType(int_type)) => Ok(int_type.const_int(low as u64, true).into()),
+            _ => Ok(llvm_type.const_zero()),
+        }
+    }
+}
+
+// Best guess at the declared type of an expression, used to skip checks that are already
+// guaranteed by the source type.
+pub fn static_type(ctx: &CodeGenContext<'_>, expr: &Expr) -> Option<TypeName> {
+    match expr {
+        Expr::Variable { var_name, var_type, .. } => var_type.clone()
+            .or_else(|| ctx.place_type(var_name))
+            .or_else(|| ctx.enum_literal(var_name).map(|(ty, _)| ty))
+            .or_else(|| ctx.module_variable(var_name).map(|(_, ty)| ty)),
+        Expr::Aggregate { type_name, .. } => Some(TypeName::Named(type_name.clone())),
+        Expr::Literal(Literal::Integer(n)) if *n > i64::MAX as i128 => Some(TypeName::Builtin(Types::u64)),
+        Expr::Literal(Literal::Char(_)) => Some(TypeName::Builtin(Types::Char)),
+        Expr::Literal(Literal::String(_)) | Expr::Interpolation { .. } => Some(TypeName::Builtin(Types::String)),
+        Expr::AttributeCall { attribute, .. } if attribute == "Pos" => Some(TypeName::Builtin(Types::i64)),
+        Expr::AttributeCall { attribute, .. } if attribute == "Image" => Some(TypeName::Builtin(Types::String)),
+        Expr::AttributeCall { prefix, attribute, .. } if attribute == "Val" || attribute == "Value" => Some(prefix.clone()),
+        Expr::FCall { callee, .. } if ctx.types.contains_key(callee) => Some(TypeName::Named(callee.clone())),
+        Expr::Assign { target, .. } => static_type(ctx, target),
+        Expr::FCall { callee, args, .. } => native::return_type(ctx, callee, args)
+            .or_else(|| ctx.function_return_type(callee)),
+        Expr::Index { base, .. } => match static_type(ctx, base)? {
+            TypeName::Array { element, .. } | TypeName::Vector(element) | TypeName::Map { value: element, .. } => Some(*element),
+            TypeName::Builtin(Types::String) => Some(TypeName::Builtin(Types::Char)),
+            _ => None,
+        },
+        Expr::Slice { base, .. } => static_type(ctx, base),
+        Expr::Conversion { target, .. } => Some(target.clone()),
+        Expr::Binary { op: Operation::Concat, .. } => Some(TypeName::Builtin(Types::String)),
+        Expr::Binary { op: Operation::Greater | Operation::Lesser | Operation::Gequal | Operation::Lequal | Operation::Equal | Operation::NotEqual, .. } => Some(TypeName::Builtin(Types::Boolean)),
+        Expr::Binary { left, right, .. } => static_type(ctx, left).or_else(|| static_type(ctx, right)).map(|t| ctx.arithmetic_type(t)),
+        Expr::Unary { op: Operation::Not, .. } => Some(TypeName::Builtin(Types::Boolean)),
+        Expr::Unary { right, .. } => static_type(ctx, right).map(|t| ctx.arithmetic_type(t)),
+        Expr::Attribute { prefix, attribute, .. } => attribute_type(ctx, prefix, attribute),
+        _ => None,
+    }
+}
+
+// Bounds and lengths of arrays, vectors and strings are i64 indices, while `T'First`, `T'Last` and
+// `T'Range` of a scalar type are values of the type.
+fn attribute_type(ctx: &CodeGenContext<'_>, prefix: &Expr, attribute: &str) -> Option<TypeName> {
+    let prefix_type = match prefix {
+        Expr::Variable { var_name, .. } if ctx.types.contains_key(var_name) => TypeName::Named(var_name.clone()),
+        other => static_type(ctx, other)?,
+    };
+    match (&prefix_type, attribute) {
+        (TypeName::Array { .. } | TypeName::Vector(_), "Length" | "First" | "Last" | "Range") => Some(TypeName::Builtin(Types::i64)),
+        (TypeName::Map { .. }, "Length") => Some(TypeName::Builtin(Types::i64)),
+        (ty, "Length" | "First" | "Last" | "Range") if string::is_string(ctx, ty) => Some(TypeName::Builtin(Types::i64)),
+        (ty, "First" | "Last" | "Range") if ctx.static_bounds(ty).is_some() => Some(prefix_type),
+        _ => None,
+    }
+}
+
+pub fn const_array<'ctx>(element_type: BasicTypeEnum<'ctx>, values: &[BasicValueEnum<'ctx>]) -> Result<BasicValueEnum<'ctx>, String> {
+    Ok(match element_type {
+        BasicTypeEnum::IntType(t) => t.const_array(&values.iter().map(|v| v.into_int_value()).collect::<Vec<_>>()),
+        BasicTypeEnum::FloatType(t) => t.const_array(&values.iter().map(|v| v.into_float_value()).collect::<Vec<_>>()),
+        BasicTypeEnum::PointerType(t) => t.const_array(&values.iter().map(|v| v.into_pointer_value()).collect::<Vec<_>>()),
+        BasicTypeEnum::StructType(t) => t.const_array(&values.iter().map(|v| v.into_struct_value()).collect::<Vec<_>>()),
+        BasicTypeEnum::ArrayType(t) => t.const_array(&values.iter().map(|v| v.into_array_value()).collect::<Vec<_>>()),
+        other => return Err(format!("Cannot build a constant array of {:?}", other)),
+    }.into())
+}
+
+// Upper bound on the size in bytes of `ty`. Every scalar is counted as a full 8 byte slot so the
+// bound holds however the target pads fields, without needing target data at this point.
+fn size_upper_bound(ty: BasicTypeEnum<'_>) -> u64 {
+    match ty {
+        BasicTypeEnum::StructType(st) => st.get_field_types().into_iter().map(size_upper_bound).sum(),
+        BasicTypeEnum::ArrayType(at) => at.len() as u64 * size_upper_bound(at.get_element_type()),
+        _ => 8,
+    }
+}
+
+// Type used for an untyped declaration, inferred from the initializer's value.
+pub fn type_of_value(value: &BasicValueEnum<'_>) -> TypeName {
+    type_of_llvm(value.get_type())
+}
+
+fn type_of_llvm(ty: BasicTypeEnum<'_>) -> TypeName {
+    let builtin = match ty {
+        BasicTypeEnum::StructType(st) => {
+            let name = st.get_name().map(|n| n.to_string_lossy().into_owned());
+            return TypeName::Named(name.unwrap_or_default());
+        }
+        // A map literal is an array of key/value pairs until it's converted into a map.
+        BasicTypeEnum::ArrayType(at) if is_pair_type(at.get_element_type()) => {
+            let pair = at.get_element_type().into_struct_type();
+            let field = |i| pair.get_field_type_at_index(i).map(type_of_llvm).unwrap_or(TypeName::Builtin(Types::i64));
+            return TypeName::Map { key: Box::new(field(0)), value: Box::new(field(1)) };
+        }
+        BasicTypeEnum::ArrayType(at) => {
+            let element = Box::new(type_of_llvm(at.get_element_type()));
+            return TypeName::Array { element, first: 0, last: at.len() as i64 - 1 };
+        }
+        BasicTypeEnum::IntType(it) => match it.get_bit_width() {
+            1 => Types::Boolean,
+            8 => Types::i8,
+            32 => Types::i32,
+            _ => Types::i64,
+        },
+        BasicTypeEnum::FloatType(ft) if ft == ft.get_context().f32_type() => Types::f32,
+        BasicTypeEnum::FloatType(_) => Types::f64,
+        _ => Types::String,
+    };
+
+    TypeName::Builtin(builtin)
+}
+
+// Map literal entries are unnamed `{ key, value }` structs, records are always named.
+pub fn is_pair_type(ty: BasicTypeEnum<'_>) -> bool {
+    matches!(ty, BasicTypeEnum::StructType(st) if st.get_name().is_none() && st.count_fields() == 2)
+}
+
+// Changes the machine representation of `value` to `target`, e.g. f64 -> i32 or i32 -> i64.
+pub fn coerce_value<'ctx>(ctx: &CodeGenContext<'ctx>, value: BasicValueEnum<'ctx>, target: BasicTypeEnum<'ctx>) -> Result<BasicValueEnum<'ctx>, String> {
+    cast_value(ctx, value, false, target, false)
+}
+
+// coerce_value for values whose signedness is known. Unsigned integers are zero extended and
+// converted with uitofp / fptoui, signed ones sign extended and converted with sitofp / fptosi.
+pub fn cast_value<'ctx>(ctx: &CodeGenContext<'ctx>, value: BasicValueEnum<'ctx>, source_unsigned: bool, target: BasicTypeEnum<'ctx>, target_unsigned: bool) -> Result<BasicValueEnum<'ctx>, String> {
+    if value.get_type() == target {
+        return Ok(value);
+    }
+
+    match (value, target) {
+        (BasicValueEnum::IntValue(iv), BasicTypeEnum::IntType(it)) => {
+            // Booleans widen to 0 / 1 rather than 0 / -1.
+            let is_signed = iv.get_type().get_bit_width() != 1 && !source_unsigned;
+            Ok(ctx.builder.build_int_cast_sign_flag(iv, it, is_signed, "int_cast").map_err(|e| e.to_string())?.into())
+        }
+        (BasicValueEnum::IntValue(iv), BasicTypeEnum::FloatType(ft)) if source_unsigned => {
+            Ok(ctx.builder.build_unsigned_int_to_float(iv, ft, "uint_to_float").map_err(|e| e.to_string())?.into())
+        }
+        (BasicValueEnum::IntValue(iv), BasicTypeEnum::FloatType(ft)) => {
+            Ok(ctx.builder.build_signed_int_to_float(iv, ft, "int_to_float").map_err(|e| e.to_string())?.into())
+        }
+        (BasicValueEnum::FloatValue(fv), BasicTypeEnum::IntType(it)) if target_unsigned => {
+            Ok(ctx.builder.build_float_to_unsigned_int(fv, it, "float_to_uint").map_err(|e| e.to_string())?.into())
+        }
+        (BasicValueEnum::FloatValue(fv), BasicTypeEnum::IntType(it)) => {
+            Ok(ctx.builder.build_float_to_signed_int(fv, it, "float_to_int").map_err(|e| e.to_string())?.into())
+        }
+        (BasicValueEnum::FloatValue(fv), BasicTypeEnum::FloatType(ft)) => {
+            Ok(ctx.builder.build_float_cast(fv, ft, "float_cast").map_err(|e| e.to_string())?.into())
+        }
+        (BasicValueEnum::PointerValue(pv), BasicTypeEnum::PointerType(_)) => Ok(pv.into()),
+        (value, target) => Err(format!("Cannot convert {:?} to {:?}", value.get_type(), target)),
+    }
+}
+
+// Converts `value` into `target`, inserting a range check when the target is constrained and
+// the source type does not already guarantee the value fits.
+pub fn convert_to_type<'ctx>(ctx: &CodeGenContext<'ctx>, value: BasicValueEnum<'ctx>, source: Option<&TypeName>, target: &TypeName, span: Span) -> Result<BasicValueEnum<'ctx>, String> {
+    if let (BasicValueEnum::ArrayValue(av), TypeName::Array { element, first, last }) = (value, target) {
+        return convert_array(ctx, av, source, element, last - first + 1, span);
+    }
+    if let (BasicValueEnum::ArrayValue(av), TypeName::Vector(element)) = (value, target) {
+        return vector::from_array(ctx, av, source, element, span);
+    }
+    if let (BasicValueEnum::ArrayValue(av), TypeName::Map { key, value }) = (value, target) {
+        return map::from_pairs(ctx, av, key, value, span);
+    }
+    if let Some(source) = source.filter(|s| managed::is_managed(s) || managed::is_managed(target)) {
+        if source != target {
+            return Err(format!("Expected {}, got {}", type_display(target), type_display(source)));
+        }
+    }
+
+    check_implicit(ctx, value, source, target, span)?;
+    let target_unsigned = ctx.is_unsigned(target);
+    let source_unsigned = source.is_some_and(|s| ctx.is_unsigned(s));
+
+    // Checked before converting, in the source type where the value is still exact. Once cast, an
+    // i64 300 going into an i8 based range would already have wrapped around to 44.
+    if ctx.range_bounds(target).is_some() {
+        if let (Some((low, high)), BasicValueEnum::IntValue(iv)) = (ctx.static_bounds(target), value) {
+            let source_type = source.cloned().unwrap_or_else(|| type_of_value(&value));
+            let (src_low, src_high) = ctx.static_bounds(&source_type).unwrap_or((i64::MIN as i128, i64::MAX as i128));
+            if src_low < low || src_high > high {
+                let failure = format!("value out of range for '{}' ({}..{})", type_display(target), low, high);
+                let bounds = (low.max(src_low).clamp(i64::MIN as i128, i64::MAX as i128) as i64, high.min(src_high).clamp(i64::MIN as i128, i64::MAX as i128) as i64);
+                build_range_check(ctx, iv, bounds, source_unsigned, &failure, Check::Range, span)?;
+            }
+        }
+    }
+
+    cast_value(ctx, value, source_unsigned, ctx.get_llvm_type(target)?, target_unsigned)
+}
+
+// Implicit conversions may only widen, anything that can lose information takes an explicit
+// conversion like `i32(x)`. Constants, literals among them, only need their value to fit.
+fn check_implicit<'ctx>(ctx: &CodeGenContext<'ctx>, value: BasicValueEnum<'ctx>, source: Option<&TypeName>, target: &TypeName, span: Span) -> Result<(), String> {
+    let source_type = source.cloned().unwrap_or_else(|| type_of_value(&value));
+    let (Ok(from), Ok(to)) = (ctx.resolve_builtin(&source_type), ctx.resolve_builtin(target)) else {
+        return Ok(());
+    };
+    let lossy = |detail: &str| Err(format!("{}:{}:{}: implicit conversion from {} to {} {}, write {}(...) to convert explicitly",
+        ctx.source_name, span.line, span.column, type_display(&source_type), type_display(target), detail, type_display(target)));
+    // Characters aren't numbers, Char'Pos and Char'Val go between the two.
+    if (from == Types::Char) != (to == Types::Char) && (representable(from).is_some() || representable(to).is_some()) {
+        return Err(format!("{}:{}:{}: can't use {} as {}, convert with Char'Pos or Char'Val",
+            ctx.source_name, span.line, span.column, type_display(&source_type), type_display(target)));
+    }
+    if (from == Types::String) != (to == Types::String) {
+        return Err(format!("{}:{}:{}: expected {}, got {}", ctx.source_name, span.line, span.column, type_display(target), type_display(&source_type)));
+    }
+
+    match (value, representable(from), representable(to), mantissa_bits(to)) {
+        (BasicValueEnum::IntValue(iv), _, Some((low, high)), _) if iv.is_const() => {
+            let constant = match ctx.is_unsigned(&source_type) {
+                true => iv.get_zero_extended_constant().map(|c| c as i128),
+                false => iv.get_sign_extended_constant().map(|c| c as i128),
+            };
+            match constant {
+                Some(c) if c < low || c > high => Err(format!("{}:{}:{}: {} doesn't fit in {}", ctx.source_name, span.line, span.column, c, type_display(target))),
+                _ => Ok(()),
+            }
+        }
+        (BasicValueEnum::IntValue(_), Some((from_low, from_high)), Some((to_low, to_high)), _) if from_low < to_low || from_high > to_high => {
+            lossy("can lose information")
+        }
+        (BasicValueEnum::IntValue(iv), Some((from_low, from_high)), None, Some(bits)) if !iv.is_const() => {
+            let magnitude = from_low.unsigned_abs().max(from_high as u128);
+            if 128 - magnitude.leading_zeros() > bits {
+                return lossy("can round large values");
+            }
+            Ok(())
+        }
+        (BasicValueEnum::FloatValue(_), None, Some(_), _) => lossy("drops the fraction"),
+        (BasicValueEnum::FloatValue(fv), None, None, Some(to_bits)) if !fv.is_const() && mantissa_bits(from).is_some_and(|from_bits| from_bits > to_bits) => {
+            lossy("loses precision")
+        }
+        _ => Ok(()),
+    }
+}
+
+// Element type of a literal like `[a, b]` that has no declared one: the widest of the element
+// types that every element converts into implicitly, so [1, 2.5] holds f64s. Elements that only
+// meet through an explicit conversion, an i64 and a u64 variable say, are an error.
+pub fn widest_type<'ctx>(ctx: &CodeGenContext<'ctx>, items: &[(BasicValueEnum<'ctx>, Option<TypeName>)], span: Span) -> Result<Option<TypeName>, String> {
+    let types: Vec<TypeName> = items.iter().map(|(value, source)| source.clone().unwrap_or_else(|| type_of_value(value))).collect();
+    let holds_all = |candidate: &TypeName| items.iter().zip(&types).all(|((value, _), ty)| {
+        ty == candidate || (ctx.resolve_builtin(ty).is_ok() && ctx.resolve_builtin(candidate).is_ok() && check_implicit(ctx, *value, Some(ty), candidate, span).is_ok())
+    });
+    // Floats are wider than integers, then more bits or a larger range is wider.
+    let width = |ty: &TypeName| match ctx.resolve_builtin(ty).ok().and_then(mantissa_bits) {
+        Some(bits) => (true, bits as i128),
+        None => (false, ctx.static_bounds(ty).map(|(low, high)| high - low).unwrap_or(0)),
+    };
+
+    match types.iter().filter(|ty| holds_all(ty)).max_by_key(|ty| width(ty)) {
+        Some(widest) => Ok(Some(widest.clone())),
+        None if types.is_empty() => Ok(None),
+        None => {
+            let mut names: Vec<String> = types.iter().map(type_display).collect();
+            names.sort();
+            names.dedup();
+            Err(format!("{}:{}:{}: elements of type {} don't convert into one another without losing information, convert them explicitly",
+                ctx.source_name, span.line, span.column, names.join(", ")))
+        }
+    }
+}
+
+// `T(x)` converts between any two numeric types. Values outside the target's range fail the
+// range check instead of wrapping, floats are truncated towards zero.
+pub fn convert_explicit<'ctx>(ctx: &CodeGenContext<'ctx>, value: BasicValueEnum<'ctx>, source: Option<&TypeName>, target: &TypeName, span: Span) -> Result<BasicValueEnum<'ctx>, String> {
+    let source_type = source.cloned().unwrap_or_else(|| type_of_value(&value));
+    let (from, to) = (ctx.resolve_builtin(&source_type)?, ctx.resolve_builtin(target)?);
+    let numeric = |t: Types| representable(t).is_some() || mantissa_bits(t).is_some();
+    if !numeric(from) || !numeric(to) {
+        return Err(format!("{}:{}:{}: can't convert {} to {}", ctx.source_name, span.line, span.column, type_display(&source_type), type_display(target)));
+    }
+
+    let source_unsigned = ctx.is_unsigned(&source_type);
+    let target_unsigned = ctx.is_unsigned(target);
+    let target_llvm = ctx.get_llvm_type(target)?;
+    let (low, high) = ctx.range_bounds(target)
+        .map(|(low, high)| (low as i128, high as i128))
+        .or_else(|| representable(to))
+        .unwrap_or((i128::MIN, i128::MAX));
+    let failure = format!("value out of range for '{}'", type_display(target));
+
+    match value {
+        BasicValueEnum::IntValue(iv) if representable(to).is_some() => {
+            // Checked before converting, in the source type, where the value is still exact.
+            let (from_low, from_high) = representable(from).unwrap_or((i64::MIN as i128, i64::MAX as i128));
+            if low > from_low || high < from_high {
+                let bounds = (low.max(from_low).clamp(i64::MIN as i128, i64::MAX as i128) as i64, high.min(from_high).clamp(i64::MIN as i128, i64::MAX as i128) as i64);
+                build_range_check(ctx, iv, bounds, source_unsigned, &failure, Check::Range, span)?;
+            }
+        }
+        BasicValueEnum::FloatValue(fv) if representable(to).is_some() => {
+            if let Some(constant) = fv.get_constant().map(|(c, _)| c) {
+                if constant.is_nan() || constant < low as f64 || constant >= high as f64 + 1.0 {
+                    return Err(format!("{}:{}:{}: {}, got {}", ctx.source_name, span.line, span.column, failure, constant));
+                }
+            } else if ctx.check_enabled(Check::Range) {
+                // Anything that truncates into low..high, NaN compares false and fails too.
+                let float_type = fv.get_type();
+                let above_low = ctx.builder.build_float_compare(FloatPredicate::OGT, fv, float_type.const_float(low as f64 - 1.0), "range_low")
+                    .map_err(|e| format!("range check compare failed: {:?}", e))?;
+                let below_high = ctx.builder.build_float_compare(FloatPredicate::OLT, fv, float_type.const_float(high as f64 + 1.0), "range_high")
+                    .map_err(|e| format!("range check compare failed: {:?}", e))?;
+                let in_range = ctx.builder.build_and(above_low, below_high, "in_range")
+                    .map_err(|e| format!("range check and failed: {:?}", e))?;
+                build_check(ctx, in_range, &failure, span)?;
+            }
+        }
+        _ => {}
+    }
+
+    cast_value(ctx, value, source_unsigned, target_llvm, target_unsigned)
+}
+
+// Converts an array element by element, so `[1, 2, 3]` can initialize an `Array<Percent, 3>`.
+fn convert_array<'ctx>(ctx: &CodeGenContext<'ctx>, value: ArrayValue<'ctx>, source: Option<&TypeName>, element: &TypeName, length: i64, span: Span) -> Result<BasicValueEnum<'ctx>, String> {
+    let value_length = value.get_type().len() as i64;
+    if value_length != length.max(0) {
+        return Err(format!("Expected an array of {} elements, got {}", length.max(0), value_length));
+    }
+
+    let target_type = ctx.get_llvm_type(element)?.array_type(value_length as u32);
+    if value.get_type() == target_type && ctx.range_bounds(element).is_none() {
+        return Ok(value.into());
+    }
+
+    let source_element = match source {
+        Some(TypeName::Array { element, .. }) => Some(element.as_ref()),
+        _ => None,
+    };
+    let mut converted: AggregateValueEnum<'ctx> = target_type.get_undef().into();
+    for i in 0..value_length as u32 {
+        let item = ctx.builder.build_extract_value(value, i, "elem")
+            .map_err(|e| format!("extract element failed: {:?}", e))?;
+        let item = convert_to_type(ctx, item, source_element, element, span)?;
+        converted = ctx.builder.build_insert_value(converted, item, i, "elem")
+            .map_err(|e| format!("insert element failed: {:?}", e))?;
+    }
+
+    Ok(converted.into_array_value().into())
+}
+
+pub fn type_display(ty: &TypeName) -> String {
+    match ty {
+        TypeName::Named(name) => name.clone(),
+        TypeName::Builtin(t) => format!("{:?}", t),
+        TypeName::Array { element, first, last } => format!("array ({}..{}) of {}", first, last, type_display(element)),
+        TypeName::Vector(element) => format!("Vector<{}>", type_display(element)),
+        TypeName::Map { key, value } => format!("Map<{}, {}>", type_display(key), type_display(value)),
+    }
+}
+
+// Fails with `failure` unless `low <= value <= high`. Constant values are checked right away, so
+// a provably bad value is a compile error and a provably good one costs nothing at runtime.
+// Other values are only checked at runtime while `check` is enabled.
+pub fn build_range_check<'ctx>(ctx: &CodeGenContext<'ctx>, value: IntValue<'ctx>, (low, high): (i64, i64), unsigned: bool, failure: &str, check: Check, span: Span) -> Result<(), String> {
+    let constant = match unsigned {
+        true => value.get_zero_extended_constant().map(|c| c as i128),
+        false => value.get_sign_extended_constant().map(|c| c as i128),
+    };
+    if let Some(constant) = constant {
+        if constant < low as i128 || constant > high as i128 {
+            return Err(format!("{}:{}:{}: {}, got {}", ctx.source_name, span.line, span.column, failure, constant));
+        }
+        return Ok(());
+    }
+    if !ctx.check_enabled(check) {
+        return Ok(());
+    }
+
+    let int_type = value.get_type();
+    let low_val = int_type.const_int(low as u64, true);
+    let high_val = int_type.const_int(high as u64, true);
+
+    let (ge, le) = if unsigned { (IntPredicate::UGE, IntPredicate::ULE) } else { (IntPredicate::SGE, IntPredicate::SLE) };
+    let above_low = ctx.builder.build_int_compare(ge, value, low_val, "range_low")
+        .map_err(|e| format!("range check compare failed: {:?}", e))?;
+    let below_high = ctx.builder.build_int_compare(le, value, high_val, "range_high")
+        .map_err(|e| format!("range check compare failed: {:?}", e))?;
+    let in_range = ctx.builder.build_and(above_low, below_high, "in_range")
+        .map_err(|e| format!("range check and failed: {:?}", e))?;
+
+    build_check(ctx, in_range, failure, span)
+}
+
+// Fails with `failure` at runtime unless `condition` holds, and continues in a fresh block otherwise.
+pub fn build_check<'ctx>(ctx: &CodeGenContext<'ctx>, condition: IntValue<'ctx>, failure: &str, span: Span) -> Result<(), String> {
+    let func = ctx.builder.get_insert_block().and_then(|b| b.get_parent()).ok_or("Runtime check outside of a function")?;
+    let fail_block = ctx.context.append_basic_block(func, "check_fail");
+    let ok_block = ctx.context.append_basic_block(func, "check_ok");
+
+    ctx.builder.build_conditional_branch(condition, ok_block, fail_block)
+        .map_err(|e| format!("check branch failed: {:?}", e))?;
+    ctx.builder.position_at_end(fail_block);
+    ctx.build_runtime_failure(failure, span)?;
+
+    ctx.builder.position_at_end(ok_block);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::code_gen::testing::*;
+
+    const RANGES: &str = "type Percent is range 0..100; subtype Index is i32 range 1..10;";
+
+    fn main_checks(source: &str) -> usize {
+        compile(source, |ctx| check_blocks(ctx.module.get_function("main").unwrap())).unwrap()
+    }
+
+    #[test]
+    fn constants_out_of_range_are_compile_errors() {
+        let error = compile_error(&in_main(RANGES, "local p: Percent -> 101;"));
+        assert!(error.contains("test.adn:3:") && error.contains("value out of range for 'Percent' (0..100), got 101"), "{}", error);
+        let error = compile_error(&in_main(RANGES, "local i: Index -> 0;"));
+        assert!(error.contains("value out of range for 'Index' (1..10), got 0"), "{}", error);
+        let error = compile_error(&in_main("subtype Small is i8 range 0..200;", ""));
+        assert!(error.contains("Range 0..200 of 'Small' does not fit its base type (-128..127)"), "{}", error);
+    }
+
+    #[test]
+    fn range_checks_are_left_out_when_the_value_fits() {
+        assert_eq!(main_checks(&in_main(RANGES, "local p: Percent -> 50;\np -> 100;\nlocal q: Percent -> p;")), 0);
+        assert_eq!(main_checks(&in_main(RANGES, "local n: i32 -> 50;\nlocal p: Percent -> n;")), 1);
+    }
+
+    #[test]
+    fn assignments_out_of_range_panic() {
+        run(&in_main(RANGES, "local n: i32 -> 100;\nlocal p: Percent -> n;")).assert_succeeded();
+        run(&in_main(RANGES, "local n: i32 -> 100;\nlocal p: Percent -> 0;\np -> n + 1;"))
+            .assert_panicked(5, "value out of range for 'Percent' (0..100)");
+    }
+
+    #[test]
+    fn parameters_out_of_range_panic() {
+        run(&format!("{}
+program -> at(i: Index): i32 {{ return i; }}
+program -> main: i32 {{
+local n: i32 -> 11;
+return at(n);
+}}", RANGES)).assert_panicked(5, "value out of range for 'Index' (1..10)");
+    }
+
+    #[test]
+    fn pragma_suppress_lasts_until_the_end_of_its_block() {
+        let source = in_main(RANGES, "local n: i32 -> 101;
+if (n > 0) { pragma Suppress(Range_Check); local p: Percent -> n; }
+local q: Percent -> n;");
+        run(&source).assert_panicked(5, "value out of range for 'Percent'");
+
+        let error = compile_error(&in_main(RANGES, "pragma Suppress(Bogus_Check);"));
+        assert!(error.contains("unknown check 'Bogus_Check'"), "{}", error);
+    }
+
+    #[test]
+    fn checks_off_leaves_range_checks_out() {
+        run_with(&in_main(RANGES, "local n: i32 -> 101;\nlocal p: Percent -> n;"), false).assert_succeeded();
+    }
+
+    #[test]
+    fn moduli_pick_the_smallest_unsigned_base() {
+        assert_compiles(&in_main("type Byte is mod 2**8; type Hour is mod 24; type Word is mod 2**64;", "local h: Hour -> 23;"));
+        let error = compile_error(&in_main("type Hour is mod 24;", "local h: Hour -> 24;"));
+        assert!(error.contains("value out of range for 'Hour' (0..23), got 24"), "{}", error);
+        let error = compile_error(&in_main("type Unit is mod 1;", ""));
+        assert!(error.contains("Modulus of 'Unit' must be at least 2"), "{}", error);
+        let error = compile_error(&in_main("type Huge is mod 2**65;", ""));
+        assert!(error.contains("Modulus of 'Huge' does not fit in 64 bits"), "{}", error);
+    }
+
+    #[test]
+    fn implicit_conversions_only_widen() {
+        assert_compiles(&in_main("", "local a: i8 -> 5;\nlocal b: i64 -> a;\nlocal c: f64 -> a;\nlocal d: f32 -> 1;\nlocal e: u8 -> 255;"));
+        let rejected = [
+            ("local x: f64 -> 2.5;\nlocal i: i32 -> x;", "implicit conversion from f64 to i32 drops the fraction, write i32(...) to convert explicitly"),
+            ("local i: i32 -> 2.5;", "drops the fraction"),
+            ("local x: i64 -> 5;\nlocal s: i32 -> x;", "implicit conversion from i64 to i32 can lose information"),
+            ("local x: i64 -> 5;\nlocal f: f64 -> x;", "implicit conversion from i64 to f64 can round large values"),
+            ("local x: i32 -> 5;\nlocal f: f32 -> x;", "implicit conversion from i32 to f32 can round large values"),
+            ("local x: f64 -> 0.1;\nlocal f: f32 -> x;", "implicit conversion from f64 to f32 loses precision"),
+            ("local b: u8 -> 256;", "256 doesn't fit in u8"),
+        ];
+        for (body, message) in rejected {
+            let error = compile_error(&in_main("", body));
+            assert!(error.contains(message), "expected '{}' for '{}', got '{}'", message, body, error);
+        }
+    }
+
+    #[test]
+    fn explicit_conversions_truncate_and_check_the_range() {
+        run(&in_main("", "
+            local x: f64 -> 2.9;
+            if (i32(x) /= 2) { return 1; }
+            if (i32(-x) /= -2) { return 2; }
+            local n: i64 -> 5000000000;
+            if (f64(n) /= 5000000000.0) { return 3; }
+            local b: u8 -> 200;
+            if (i8(i32(b) - 100) /= 100) { return 4; }")).assert_succeeded();
+        run(&in_main("", "local n: i32 -> 300;\nlocal b: u8 -> u8(n);")).assert_panicked(4, "value out of range for 'u8'");
+        run(&in_main("", "local x: f64 -> -1.0;\nlocal b: u32 -> u32(x);")).assert_panicked(4, "value out of range for 'u32'");
+
+        let error = compile_error(&in_main("", "local i: i32 -> i32(3.0e10);"));
+        assert!(error.contains("value out of range for 'i32', got 30000000000"), "{}", error);
+        let error = compile_error(&in_main("", "local i: i32 -> i32(\"5\");"));
+        assert!(error.contains("can't convert String to i32"), "{}", error);
+    }
+}
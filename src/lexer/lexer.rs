@@ -3,6 +3,9 @@ use crate::lexer::token::*;
 pub struct Lexer {
     input: Vec<char>,
     pos: usize,
+    line: u32,
+    column: u32,
+    token_start: Span,
 }
 
 impl Lexer {
@@ -10,17 +13,35 @@ impl Lexer {
         Self {
             input: to_process.chars().collect(),
             pos: 0,
+            line: 1,
+            column: 1,
+            token_start: Span::default(),
         }
     }
 
+    // Lexes the code of a `{...}` interpolation, with spans pointing into the enclosing file.
+    fn starting_at(to_process: &str, start: Span) -> Self {
+        Self { line: start.line, column: start.column, ..Self::new(to_process) }
+    }
+
     pub fn preview(&self) -> Option<char> {
         self.input.get(self.pos).copied()
     }
 
     pub fn advance(&mut self) {
+        if self.preview() == Some('\n') {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
         self.pos += 1
     }
 
+    pub fn span(&self) -> Span {
+        Span { line: self.line, column: self.column }
+    }
+
     fn read_while<F>(&mut self, condition: F) -> String
     where
         F: Fn(char) -> bool,
@@ -36,12 +57,40 @@ impl Lexer {
         result
     }
 
+    // Source of a `{...}` interpolation up to its closing brace, which is consumed. Strings inside
+    // it may hold braces of their own. None when the literal ends first.
+    fn read_interpolation(&mut self) -> Option<String> {
+        let mut code = String::new();
+        let mut depth = 0;
+        let mut in_string = false;
+        while let Some(ch) = self.preview() {
+            match ch {
+                '"' => in_string = !in_string,
+                '\\' if in_string => {
+                    code.push(ch);
+                    self.advance();
+                }
+                '{' if !in_string => depth += 1,
+                '}' if !in_string && depth == 0 => {
+                    self.advance();
+                    return Some(code);
+                }
+                '}' if !in_string => depth -= 1,
+                _ => {}
+            }
+            code.push(self.preview()?);
+            self.advance();
+        }
+        None
+    }
+
     fn skip_whitespace(&mut self) {
         self.read_while(|c| c.is_whitespace());
     }
 
     pub fn next_token(&mut self) -> Token {
         self.skip_whitespace();
+        self.token_start = self.span();
 
         let c = match self.preview() {
             Some(c) => c,
@@ -60,12 +109,28 @@ impl Lexer {
                 "while" => Token::Keyword(Keyword::While),
                 "else" => Token::Keyword(Keyword::Else),
                 "return" => Token::Keyword(Keyword::Return),
+                "type" => Token::Keyword(Keyword::Type),
+                "subtype" => Token::Keyword(Keyword::Subtype),
+                "is" => Token::Keyword(Keyword::Is),
+                "range" => Token::Keyword(Keyword::Range),
+                "record" => Token::Keyword(Keyword::Record),
+                "mod" => Token::Keyword(Keyword::Mod),
+                "in" => Token::Keyword(Keyword::In),
+                "out" => Token::Keyword(Keyword::Out),
+                "case" => Token::Keyword(Keyword::Case),
+                "when" => Token::Keyword(Keyword::When),
+                "others" => Token::Keyword(Keyword::Others),
+                "for" => Token::Keyword(Keyword::For),
+                "array" => Token::Keyword(Keyword::Array),
+                "of" => Token::Keyword(Keyword::Of),
+                "pragma" => Token::Keyword(Keyword::Pragma),
 
                 "String" => Token::Types(Types::String),
                 "Boolean" => Token::Types(Types::Boolean),
                 "Char" => Token::Types(Types::Char),
                 "Array" => Token::Types(Types::Array),
-                "Object" => Token::Types(Types::Object),
+                "Vector" => Token::Types(Types::Vector),
+                "Map" => Token::Types(Types::Map),
 
                 "i8" => Token::Types(Types::i8),
                 "i32" => Token::Types(Types::i32),
@@ -81,7 +146,25 @@ impl Lexer {
         }
 
         if c.is_digit(10) {
-            let number = self.read_while(|ch| ch.is_digit(10));
+            let mut number = self.read_while(|ch| ch.is_digit(10));
+            // `1.5` is a real, `1..5` is a range.
+            let at = |offset: usize| self.input.get(self.pos + offset).copied();
+            if at(0) == Some('.') && at(1).is_some_and(|ch| ch.is_ascii_digit()) {
+                self.advance();
+                number.push('.');
+                number.push_str(&self.read_while(|ch| ch.is_digit(10)));
+            }
+            let at = |offset: usize| self.input.get(self.pos + offset).copied();
+            let signed_exponent = matches!(at(1), Some('+' | '-')) && at(2).is_some_and(|ch| ch.is_ascii_digit());
+            if matches!(at(0), Some('e' | 'E')) && (at(1).is_some_and(|ch| ch.is_ascii_digit()) || signed_exponent) {
+                number.push('e');
+                self.advance();
+                if signed_exponent {
+                    number.push(self.preview().unwrap_or('+'));
+                    self.advance();
+                }
+                number.push_str(&self.read_while(|ch| ch.is_digit(10)));
+            }
             return Token::Number(number);
         }
 
@@ -113,6 +196,24 @@ impl Lexer {
             return self.next_token();
         }
 
+        if c == '.' && next == Some('.') {
+            self.advance();
+            self.advance();
+            return Token::Symbols(Symbols::DoublePeriod);
+        }
+
+        if c == '=' && next == Some('>') {
+            self.advance();
+            self.advance();
+            return Token::Symbols(Symbols::Arrow);
+        }
+
+        if c == '/' && next == Some('=') {
+            self.advance();
+            self.advance();
+            return Token::Symbols(Symbols::NotEqual);
+        }
+
         if c == '>' && next == Some('=') {
             self.advance();
             self.advance();
@@ -125,10 +226,19 @@ impl Lexer {
             return Token::Symbols(Symbols::Lequal);
         }
 
+        // In a string literal:
+        //   {expr}     is replaced by the value of expr, "x = {x}".
+        //   {}         is kept as is, it's the placeholder of io.printf formats.
+        //   {{ and }}  are a literal { and }, so "{{x}}" is the text {x}. "{{}}" is the text {}, which
+        //              io.printf reads as a placeholder like any other {}.
+        //   { }        with only blanks inside is an error rather than a second spelling of {}.
+        // The code between { and } is lexed on its own, the \ escapes of the literal don't apply to
+        // it. Strings in there use plain quotes and their own escapes, "{s.replace("\"", "'")}".
         if c == '"' {
             self.advance();
             
             let mut string_content = String::new();
+            let mut fragments = Vec::new();
             while let Some(ch) = self.preview() {
                 if ch == '"' {
                     self.advance(); // us closing "
@@ -148,6 +258,34 @@ impl Lexer {
                         None => break,
                     }
                     self.advance();
+                } else if (ch == '{' || ch == '}') && self.input.get(self.pos + 1) == Some(&ch) {
+                    // {{ and }} stand for the braces themselves.
+                    string_content.push(ch);
+                    self.advance();
+                    self.advance();
+                } else if ch == '{' && self.input.get(self.pos + 1) == Some(&'}') {
+                    // An empty {} is kept, it's the placeholder of io.printf formats.
+                    string_content.push_str("{}");
+                    self.advance();
+                    self.advance();
+                } else if ch == '{' {
+                    self.advance();
+                    let start = self.span();
+                    let Some(code) = self.read_interpolation() else {
+                        return Token::Error("Unterminated {...} in string literal".to_string());
+                    };
+                    if code.trim().is_empty() {
+                        return Token::Error("Empty { } in string literal, write {} for a placeholder or {{ }} for the braces".to_string());
+                    }
+                    let tokens = match Lexer::starting_at(&code, start).tokenize() {
+                        Ok(tokens) => tokens,
+                        Err(e) => return Token::Error(e),
+                    };
+
+                    if !string_content.is_empty() {
+                        fragments.push(Fragment::Text(std::mem::take(&mut string_content)));
+                    }
+                    fragments.push(Fragment::Code(tokens));
                 } else {
                     string_content.push(ch);
                     self.advance();
@@ -156,19 +294,38 @@ impl Lexer {
                 //self.advance();
             }
 
-            return Token::Literal(string_content);
+            if fragments.is_empty() {
+                return Token::Literal(string_content);
+            }
+            if !string_content.is_empty() {
+                fragments.push(Fragment::Text(string_content));
+            }
+            return Token::Interpolated(fragments);
+        }
+
+        // '\n' and '\'' are escaped char literals, checked first since '\'' also looks like 'x'.
+        if c == '\'' && next == Some('\\') && self.input.get(self.pos + 3) == Some(&'\'') {
+            let escape = self.input[self.pos + 2];
+            for _ in 0..4 {
+                self.advance();
+            }
+            return match escape {
+                'n' => Token::CharLiteral('\n'),
+                't' => Token::CharLiteral('\t'),
+                'r' => Token::CharLiteral('\r'),
+                '0' => Token::CharLiteral('\0'),
+                '\'' | '"' | '\\' => Token::CharLiteral(escape),
+                other => Token::Error(format!("Unknown escape in char literal: \\{}", other)),
+            };
         }
 
-        if c == '\'' {
+        // 'x' is a char literal, any other quote is an attribute tick like a'Length.
+        if c == '\'' && self.input.get(self.pos + 2) == Some(&'\'') {
             self.advance();
             if let Some(ch) = self.preview() {
                 self.advance();
-                if self.preview() == Some ('\'') {
-                    self.advance();
-                    return Token::CharLiteral(ch);
-                } else {
-                    return Token::Error("Unterminated char literal".to_string());
-                }
+                self.advance();
+                return Token::CharLiteral(ch);
             }
         }
 
@@ -180,6 +337,8 @@ impl Lexer {
             ')' => Token::Symbols(Symbols::RParen),
             '{' => Token::Symbols(Symbols::LCurlyBracket),
             '}' => Token::Symbols(Symbols::RCurlyBracket),
+            '[' => Token::Symbols(Symbols::LBracket),
+            ']' => Token::Symbols(Symbols::RBracket),
             '\'' => Token::Symbols(Symbols::SingleQuote),
             '.' => Token::Symbols(Symbols::Period),
             ',' => Token::Symbols(Symbols::Comma),
@@ -191,21 +350,140 @@ impl Lexer {
             '*' => Token::Symbols(Symbols::Mul),
             '/' => Token::Symbols(Symbols::Div),
             '%' => Token::Symbols(Symbols::Mod),
+            '&' => Token::Symbols(Symbols::Ampersand),
             _ => Token::Error(format!("Unexpected char: {}", c)),
         }
     }
 
-    pub fn tokenize(&mut self) -> Result<Vec<Token>, String> {
+    pub fn tokenize(&mut self) -> Result<Vec<(Token, Span)>, String> {
         let mut tokens = Vec::new();
         loop {
             let tok = self.next_token();
+            let span = self.token_start;
             if let Token::Error(e) = &tok {
                 if e == "Unexpected EOF" {
                     break;
                 }
             }
-            tokens.push(tok);
+            tokens.push((tok, span));
         }
         Ok(tokens)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string_token(source: &str) -> Token {
+        Lexer::new(source).next_token()
+    }
+
+    fn idents(tokens: &[(Token, Span)]) -> Vec<String> {
+        tokens.iter().filter_map(|(t, _)| match t {
+            Token::Ident(name) => Some(name.clone()),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        assert!(matches!(string_token(r#""{{x}}""#), Token::Literal(s) if s == "{x}"));
+        assert!(matches!(string_token(r#""a }} b {{""#), Token::Literal(s) if s == "a } b {"));
+        assert!(matches!(string_token(r#""{{}}""#), Token::Literal(s) if s == "{}"));
+    }
+
+    #[test]
+    fn empty_braces_are_a_placeholder() {
+        assert!(matches!(string_token(r#""x = {}""#), Token::Literal(s) if s == "x = {}"));
+    }
+
+    #[test]
+    fn blank_braces_are_an_error() {
+        assert!(matches!(string_token(r#""{ }""#), Token::Error(e) if e.starts_with("Empty { }")));
+    }
+
+    #[test]
+    fn interpolation_splits_text_and_code() {
+        let Token::Interpolated(fragments) = string_token(r#""x = {x}, y""#) else {
+            panic!("expected an interpolated string");
+        };
+        assert!(matches!(&fragments[0], Fragment::Text(t) if t == "x = "));
+        assert!(matches!(&fragments[1], Fragment::Code(tokens) if idents(tokens) == ["x"]));
+        assert!(matches!(&fragments[2], Fragment::Text(t) if t == ", y"));
+    }
+
+    #[test]
+    fn nested_strings_keep_their_braces() {
+        let Token::Interpolated(fragments) = string_token(r#""{f("}}{{", "a")}""#) else {
+            panic!("expected an interpolated string");
+        };
+        let [Fragment::Code(tokens)] = fragments.as_slice() else {
+            panic!("expected a single code fragment");
+        };
+        let literals: Vec<_> = tokens.iter().filter_map(|(t, _)| match t {
+            Token::Literal(s) => Some(s.as_str()),
+            _ => None,
+        }).collect();
+        assert_eq!(literals, ["}{", "a"]);
+    }
+
+    #[test]
+    fn escapes_inside_code_belong_to_its_strings() {
+        let Token::Interpolated(fragments) = string_token(r#""{s.replace("\"", "\n")}""#) else {
+            panic!("expected an interpolated string");
+        };
+        let [Fragment::Code(tokens)] = fragments.as_slice() else {
+            panic!("expected a single code fragment");
+        };
+        let literals: Vec<_> = tokens.iter().filter_map(|(t, _)| match t {
+            Token::Literal(s) => Some(s.as_str()),
+            _ => None,
+        }).collect();
+        assert_eq!(literals, ["\"", "\n"]);
+    }
+
+    #[test]
+    fn escapes_outside_code_still_apply() {
+        let Token::Interpolated(fragments) = string_token(r#""\t{x}\n""#) else {
+            panic!("expected an interpolated string");
+        };
+        assert!(matches!(&fragments[0], Fragment::Text(t) if t == "\t"));
+        assert!(matches!(&fragments[2], Fragment::Text(t) if t == "\n"));
+    }
+
+    #[test]
+    fn unterminated_interpolation_is_an_error() {
+        assert!(matches!(string_token(r#""x = {x""#), Token::Error(e) if e.starts_with("Unterminated")));
+        assert!(matches!(string_token(r#""{f("}")"#), Token::Error(e) if e.starts_with("Unterminated")));
+    }
+
+    #[test]
+    fn code_spans_point_into_the_file() {
+        let Token::Interpolated(fragments) = string_token("\"ab{x}\"") else {
+            panic!("expected an interpolated string");
+        };
+        let Fragment::Code(tokens) = &fragments[1] else {
+            panic!("expected code after the text");
+        };
+        assert_eq!((tokens[0].1.line, tokens[0].1.column), (1, 5));
+    }
+
+    #[test]
+    fn char_literals_take_escapes() {
+        assert!(matches!(string_token("'a'"), Token::CharLiteral('a')));
+        assert!(matches!(string_token(r"'\n'"), Token::CharLiteral('\n')));
+        assert!(matches!(string_token(r"'\''"), Token::CharLiteral('\'')));
+        assert!(matches!(string_token(r"'\\'"), Token::CharLiteral('\\')));
+        assert!(matches!(string_token(r"'\0'"), Token::CharLiteral('\0')));
+        assert!(matches!(string_token(r"'\q'"), Token::Error(e) if e == r"Unknown escape in char literal: \q"));
+    }
+
+    #[test]
+    fn ticks_after_a_name_are_attributes() {
+        let tokens = Lexer::new("a'Length 'b'").tokenize().unwrap();
+        assert!(matches!(tokens[1].0, Token::Symbols(Symbols::SingleQuote)));
+        assert_eq!(idents(&tokens), ["a", "Length"]);
+        assert!(matches!(tokens[3].0, Token::CharLiteral('b')));
+    }
+}
@@ -14,11 +14,19 @@ pub enum Token {
 
     Ident(String),
     Literal(String),
+    Interpolated(Vec<Fragment>), // A string literal with `{expr}` in it.
     CharLiteral(char),
 
     Error(String),
 }
 
+// Piece of an interpolated string, "x = {x}" is Text("x = ") followed by the tokens of `x`.
+#[derive(Debug, Clone)]
+pub enum Fragment {
+    Text(String),
+    Code(Vec<(Token, Span)>),
+}
+
 // Individual enum pairs
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Keyword {
@@ -34,6 +42,26 @@ pub enum Keyword {
 
     Include,        // Importing binaries or third party packages to your AdaN script.
     Program,        // Creating a new function outside of the main function. (program -> {var})
+
+    Type,           // Declares a new named type. (type Percent is range 0..100;)
+    Subtype,        // Constrains an existing type. (subtype Index is i32 range 1..10;)
+    Is,             // Separates a type name from its definition.
+    Range,          // Introduces an inclusive `low..high` constraint on an integer type.
+    Record,         // A type made of named fields. (type Point is record { x: f64, y: f64 })
+    Mod,            // Modular integer type that wraps around. (type Byte is mod 2**8;)
+
+    Case,           // Picks a branch by the value of a discrete expression. (case s.kind { when Circle => { ... } })
+    When,           // A single branch of a `case`, or a variant of a discriminated record.
+    Others,         // Catch-all choice of a `case`. (when others => { ... })
+    For,            // Iterates over a range or the elements of an array. (for x in a { ... })
+
+    Array,          // Ada style array type. (array (1..10) of i32)
+    Of,             // Separates an array's index range from its element type.
+
+    In,             // Parameter mode, passed by value. (program -> show(p: in Point))
+    Out,            // Parameter mode, `in out` passes a variable by reference. (program -> move(p: in out Point))
+
+    Pragma,         // Compiler directive for the rest of the enclosing block. (pragma Suppress(Overflow_Check);)
 }
 
 // Less priority symbols unlike Equality & SemiColon.
@@ -48,7 +76,13 @@ pub enum Symbols {
     LCurlyBracket,
     RCurlyBracket,
 
+    LBracket,       // Array literals and indexing. ([1, 2, 3], a[i])
+    RBracket,
+
+    Arrow,          // => Separates a `when` choice from its branch.
+
     Equal,          // NOT used for assignment! Used for checking the equality between two values.
+    NotEqual,       // /= Checks that two values differ, returns a boolean.
     Greater,        // Checks if x is > y, returns a boolean.
     Lesser,         // Checks if x is < y, returns a boolean.
     Gequal,         // Checks if x is < or equal to y, returns a boolean.
@@ -59,22 +93,24 @@ pub enum Symbols {
     Mul,            // x * y = z
     Div,            // x / y = z
     Mod,            // x % y = remainder of x / y
+    Ampersand,      // a & b joins two strings, like Ada.
    
     Not,            // ! (or NOT) can be chained like -> !!x = not not x, or just x, it can also
                     // just be !x, or not x. ! can be chained indefinitely. (!!!!!!x, or just x is passable)
 
     Quotation,      // ""
-    SingleQuote,    // ''
+    SingleQuote,    // '' Also used as the attribute tick. (a'Length)
     
     SemiColon,      // Used to tell the compiler it's ready to move on to the next line.
     Colon,          // Used when explicitly defining the type of a variable.
     Period,
+    DoublePeriod,   // Separates the bounds of a range. (0..100)
     Comma,          // Typically used as a separator for function parameters. (program -> sample(a:
                     // String -> 1, b: i8 -> 2, ...))
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Types {
     String,
 
@@ -86,6 +122,20 @@ pub enum Types {
     Boolean,
     Char,          // Single *char*acter ('a', 'b', ...)
     
-    Array,         // Fixed size list of object, where all objects must be of the same type.
-    Object,        // Fixed size object where a name (String) is assigned to `x` value.
+    Array,         // Fixed size list of object, where all objects must be of the same type. (Array<i32, 10>)
+    Vector,        // Growable list living on the heap, freed when its variable goes out of scope. (Vector<i32>)
+    Map,           // Keys assigned to values, also on the heap. (Map<String, i64>)
+}
+
+// Position of a token in the source file, used to point runtime check failures at the offending code.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Span {
+    pub line: u32,
+    pub column: u32,
+}
+
+impl Default for Span {
+    fn default() -> Self {
+        Self { line: 1, column: 1 }
+    }
 }
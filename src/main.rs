@@ -5,24 +5,38 @@ mod code_gen;
 mod cli;
 
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use crate::lexer::lexer::Lexer;
 use crate::parser::parser::Parser;
 use crate::code_gen::statements::{codegen_statements, load_native_registry};
 use crate::code_gen::builder::CodeGenContext;
-//use crate::cli::initialize;
+use crate::cli::initialize;
 
-fn create_binary() -> Result<(), Box<dyn std::error::Error>> {
-    fs::create_dir_all("compiled")?;
+// Builds `dir/output_exec` from the `dir/output.ll` written by the code generator.
+fn create_binary(dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let (ir, object, executable) = (dir.join("output.ll"), dir.join("output.o"), dir.join("output_exec"));
 
-    Command::new("llc")
-        .args(&["compiled/output.ll", "-filetype=obj", "-o", "compiled/output.o"])
+    let status = Command::new("llc")
+        .arg(&ir)
+        .args(&["-filetype=obj", "-o"])
+        .arg(&object)
         .status()?;
+    if !status.success() {
+        return Err(format!("llc failed ({})", status).into());
+    }
 
-    Command::new("gcc")
-        .args(&["compiled/output.o", "-o", "compiled/output_exec"])
+    let runtime_dir = runtime_dir()?;
+    let status = Command::new("gcc")
+        .arg(&object)
+        .arg("-o")
+        .arg(&executable)
+        .arg(format!("-L{}", runtime_dir.display()))
+        .args(&["-ladan_rt", "-lpthread", "-ldl", "-lm"])
         .status()?;
+    if !status.success() {
+        return Err(format!("linking failed ({}), is libadan_rt.a in {}?", status, runtime_dir.display()).into());
+    }
 
     //Command::new("./compiled/output_exec")
     //    .status()?;
@@ -30,29 +44,54 @@ fn create_binary() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let input_path = Path::new("./examples/hello_adan.adn");
-    let source = fs::read_to_string(&input_path)?;
-    let tokens = Lexer::new(&source).tokenize()?;
+// libadan_rt.a is built into the same target directory as the compiler itself, which is one
+// level up from test binaries in deps/. ADAN_RT_DIR points somewhere else when it's installed
+// separately.
+fn runtime_dir() -> std::io::Result<PathBuf> {
+    if let Some(dir) = std::env::var_os("ADAN_RT_DIR") {
+        return Ok(PathBuf::from(dir));
+    }
+
+    let exe = std::env::current_exe()?;
+    let dir = exe.parent().map(|p| p.to_path_buf()).unwrap_or_default();
+    match dir.file_name() {
+        Some(name) if name == "deps" => Ok(dir.parent().map(|p| p.to_path_buf()).unwrap_or_default()),
+        _ => Ok(dir),
+    }
+}
+
+// Lexes, parses and generates `source` into `ctx.module`.
+fn generate(ctx: &mut CodeGenContext<'_>, source: &str) -> Result<(), String> {
+    let tokens = Lexer::new(source).tokenize()?;
     let mut parser = Parser::new(tokens);
     let statements = parser.parse()?;
 
-    let context = inkwell::context::Context::create();
-    let mut ctx = CodeGenContext::new(&context, "adan_module");
-
     let registry = load_native_registry();
     for register_fn in registry.values() {
-        register_fn(&mut ctx);
+        register_fn(ctx);
     }
 
     for stmt in &statements {
-        codegen_statements(&mut ctx, stmt, &registry)?;
+        codegen_statements(ctx, stmt, &registry)?;
     }
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let options = initialize();
+    let input_path = options.input.as_path();
+    let source = fs::read_to_string(&input_path)?;
+
+    let context = inkwell::context::Context::create();
+    let mut ctx = CodeGenContext::new(&context, "adan_module");
+    ctx.source_name = input_path.display().to_string();
+    ctx.checks = options.checks;
+    generate(&mut ctx, &source)?;
 
-    fs::create_dir_all("compiled")?;
-    ctx.module.print_to_file("compiled/output.ll")?;
-    create_binary()?;
+    let output_dir = Path::new("compiled");
+    fs::create_dir_all(output_dir)?;
+    ctx.module.print_to_file(output_dir.join("output.ll"))?;
+    create_binary(output_dir)?;
 
-    //initialize();
     Ok(())
 }
@@ -0,0 +1,66 @@
+use inkwell::values::BasicValueEnum;
+use crate::code_gen::builder::{CodeGenContext, NativeParam};
+use crate::lexer::token::Types;
+use crate::parser::ast::TypeName;
+
+static LIBRARY_NAME: &str = "chars";
+
+// Type of `chars.f(c)`, the natives themselves only see LLVM values.
+pub fn return_type(callee: &str) -> Option<TypeName> {
+    match callee.strip_prefix("chars.")? {
+        "is_digit" | "is_alpha" | "is_alnum" | "is_space" | "is_upper" | "is_lower" => Some(TypeName::Builtin(Types::Boolean)),
+        "to_upper" | "to_lower" => Some(TypeName::Builtin(Types::Char)),
+        _ => None,
+    }
+}
+
+// Classification is ASCII only, like the rest of the runtime's byte strings (adan_rt/src/chars.rs).
+fn classify<'ctx>(ctx: &CodeGenContext<'ctx>, name: &str, args: &[BasicValueEnum<'ctx>]) -> BasicValueEnum<'ctx> {
+    let returns = if name.starts_with("adan_char_to_") { ctx.i8_type } else { ctx.bool_type };
+    ctx.call_runtime(name, Some(returns.into()), &[args[0]])
+        .and_then(|v| v.ok_or_else(|| format!("{} returned nothing", name)))
+        .expect("chars call failed")
+}
+
+pub fn is_digit<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    classify(ctx, "adan_char_is_digit", &args)
+}
+
+pub fn is_alpha<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    classify(ctx, "adan_char_is_alpha", &args)
+}
+
+pub fn is_alnum<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    classify(ctx, "adan_char_is_alnum", &args)
+}
+
+pub fn is_space<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    classify(ctx, "adan_char_is_space", &args)
+}
+
+pub fn is_upper<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    classify(ctx, "adan_char_is_upper", &args)
+}
+
+pub fn is_lower<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    classify(ctx, "adan_char_is_lower", &args)
+}
+
+pub fn to_upper<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    classify(ctx, "adan_char_to_upper", &args)
+}
+
+pub fn to_lower<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    classify(ctx, "adan_char_to_lower", &args)
+}
+
+pub fn register_native<'ctx>(ctx: &mut CodeGenContext<'ctx>) {
+    ctx.register_native_fn(LIBRARY_NAME, "is_digit", &[NativeParam::CHAR], is_digit);
+    ctx.register_native_fn(LIBRARY_NAME, "is_alpha", &[NativeParam::CHAR], is_alpha);
+    ctx.register_native_fn(LIBRARY_NAME, "is_alnum", &[NativeParam::CHAR], is_alnum);
+    ctx.register_native_fn(LIBRARY_NAME, "is_space", &[NativeParam::CHAR], is_space);
+    ctx.register_native_fn(LIBRARY_NAME, "is_upper", &[NativeParam::CHAR], is_upper);
+    ctx.register_native_fn(LIBRARY_NAME, "is_lower", &[NativeParam::CHAR], is_lower);
+    ctx.register_native_fn(LIBRARY_NAME, "to_upper", &[NativeParam::CHAR], to_upper);
+    ctx.register_native_fn(LIBRARY_NAME, "to_lower", &[NativeParam::CHAR], to_lower);
+}
@@ -0,0 +1,59 @@
+use inkwell::values::BasicValueEnum;
+use crate::code_gen::builder::{CodeGenContext, NativeParam};
+use crate::lexer::token::Types;
+use crate::native::vector::{handle_type, runtime_value};
+use crate::parser::ast::TypeName;
+
+static LIBRARY_NAME: &str = "env";
+
+// Type of `env.f(...)`, the natives themselves only see LLVM values.
+pub fn return_type(callee: &str) -> Option<TypeName> {
+    let string = || Box::new(TypeName::Builtin(Types::String));
+    match callee.strip_prefix("env.")? {
+        "get" => Some(TypeName::Builtin(Types::String)),
+        "has" => Some(TypeName::Builtin(Types::Boolean)),
+        "vars" => Some(TypeName::Map { key: string(), value: string() }),
+        _ => None,
+    }
+}
+
+// env.get(name) is the value of the environment variable `name`, or "" when it isn't set.
+pub fn get<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    runtime_value(ctx, "adan_env_get", ctx.string_type.into(), &[args[0]]).expect("env.get failed")
+}
+
+// env.has(name) tells a variable set to "" from one that isn't set.
+pub fn has<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    runtime_value(ctx, "adan_env_has", ctx.bool_type.into(), &[args[0]]).expect("env.has failed")
+}
+
+// env.set(name, value) sets the variable for this program and the ones it runs. A name that is
+// empty or holds a `=` panics at the call.
+pub fn set<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    let error = runtime_value(ctx, "adan_env_set", ctx.string_type.into(), &[args[0], args[1]])
+        .expect("env.set failed")
+        .into_pointer_value();
+    let set = ctx.builder.build_is_null(error, "env_set").expect("env.set failed");
+
+    let func = ctx.builder.get_insert_block().and_then(|b| b.get_parent()).expect("env.set outside of a function");
+    let fail_block = ctx.context.append_basic_block(func, "env_set_fail");
+    let ok_block = ctx.context.append_basic_block(func, "env_set_ok");
+    ctx.builder.build_conditional_branch(set, ok_block, fail_block).expect("env.set failed");
+    ctx.builder.position_at_end(fail_block);
+    ctx.build_panic(error.into(), ctx.call_span).expect("env.set failed");
+
+    ctx.builder.position_at_end(ok_block);
+    ctx.i64_type.const_zero().into()
+}
+
+// env.vars() is every variable in a new Map<String, String>, sorted by name.
+pub fn vars<'ctx>(ctx: &mut CodeGenContext<'ctx>, _args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    runtime_value(ctx, "adan_env_vars", handle_type(ctx), &[]).expect("env.vars failed")
+}
+
+pub fn register_native<'ctx>(ctx: &mut CodeGenContext<'ctx>) {
+    ctx.register_native_fn(LIBRARY_NAME, "get", &[NativeParam::STRING], get);
+    ctx.register_native_fn(LIBRARY_NAME, "has", &[NativeParam::STRING], has);
+    ctx.register_native_fn(LIBRARY_NAME, "set", &[NativeParam::STRING, NativeParam::STRING], set);
+    ctx.register_native_fn(LIBRARY_NAME, "vars", &[], vars);
+}
@@ -0,0 +1,170 @@
+use inkwell::types::BasicTypeEnum;
+use inkwell::values::{BasicValueEnum, PointerValue};
+use crate::code_gen::builder::{CodeGenContext, NativeParam};
+use crate::code_gen::types::declare_type;
+use crate::lexer::token::Types;
+use crate::native::vector::runtime_value;
+use crate::parser::ast::{FieldDecl, TypeDefinition, TypeName};
+
+static LIBRARY_NAME: &str = "fs";
+
+// I/O errors don't stop the program, every call that can fail returns one of these records
+// instead. `ok` tells whether it worked and `error` says why it didn't:
+//   Fs_Status  (ok, error)        fs.write, fs.append, fs.remove, fs.list_dir, ...
+//   Fs_Text    (ok, error, text)  fs.read_to_string, fs.read_line
+//   Fs_File    (ok, error, file)  fs.open
+const STATUS: &str = "Fs_Status";
+const TEXT: &str = "Fs_Text";
+const FILE: &str = "Fs_File";
+
+// Type of `fs.f(...)`, the natives themselves only see LLVM values.
+pub fn return_type(callee: &str) -> Option<TypeName> {
+    let name = match callee.strip_prefix("fs.")? {
+        "exists" | "eof" => return Some(TypeName::Builtin(Types::Boolean)),
+        "read_to_string" | "read_line" => TEXT,
+        "open" => FILE,
+        "write" | "append" | "remove" | "list_dir" | "create_dir_all" | "write_str" | "write_line" | "close" => STATUS,
+        _ => return None,
+    };
+    Some(TypeName::Named(name.to_string()))
+}
+
+fn declare_result(ctx: &mut CodeGenContext<'_>, name: &str, value: Option<(&str, Types)>) {
+    if ctx.types.contains_key(name) {
+        return;
+    }
+
+    let field = |name: &str, ty| FieldDecl { name: name.to_string(), field_type: TypeName::Builtin(ty) };
+    let mut fields = vec![field("ok", Types::Boolean), field("error", Types::String)];
+    fields.extend(value.map(|(name, ty)| field(name, ty)));
+    declare_type(ctx, name, &TypeDefinition::Record { discriminant: None, fields, variants: Vec::new() })
+        .expect("fs result type");
+}
+
+// Calls a runtime function that returns its error message, null when it worked, and builds the
+// `result` record out of it. `value` is where the runtime leaves the extra field, if any.
+fn call<'ctx>(ctx: &CodeGenContext<'ctx>, name: &str, args: &[BasicValueEnum<'ctx>], result: &str, value: Option<(PointerValue<'ctx>, BasicTypeEnum<'ctx>)>) -> Result<BasicValueEnum<'ctx>, String> {
+    let error = runtime_value(ctx, name, ctx.string_type.into(), args)?.into_pointer_value();
+    let ok = ctx.builder.build_is_null(error, "fs_ok")
+        .map_err(|e| format!("fs check failed: {:?}", e))?;
+
+    let record_type = ctx.get_llvm_type(&TypeName::Named(result.to_string()))?.into_struct_type();
+    let mut record = ctx.builder.build_insert_value(record_type.get_undef(), ok, 0, "fs_result")
+        .map_err(|e| format!("fs result failed: {:?}", e))?;
+    // A null error is the empty string.
+    record = ctx.builder.build_insert_value(record, error, 1, "fs_result")
+        .map_err(|e| format!("fs result failed: {:?}", e))?;
+    if let Some((out, out_type)) = value {
+        let value = ctx.builder.build_load(out_type, out, "fs_value")
+            .map_err(|e| format!("load fs value failed: {:?}", e))?;
+        record = ctx.builder.build_insert_value(record, value, 2, "fs_result")
+            .map_err(|e| format!("fs result failed: {:?}", e))?;
+    }
+    Ok(record.into_struct_value().into())
+}
+
+// Slot for the value a runtime call leaves behind, zeroed so a failed call leaves "" or 0.
+fn out_slot<'ctx>(ctx: &CodeGenContext<'ctx>, ty: BasicTypeEnum<'ctx>) -> (PointerValue<'ctx>, BasicTypeEnum<'ctx>) {
+    let slot = ctx.build_entry_alloca(ty, "fs_out", None).expect("fs out slot");
+    ctx.builder.build_store(slot, ty.const_zero()).expect("fs out slot");
+    (slot, ty)
+}
+
+// fs.read_to_string(path) -> Fs_Text
+pub fn read_to_string<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    let out = out_slot(ctx, ctx.string_type.into());
+    call(ctx, "adan_fs_read_to_string", &[args[0], out.0.into()], TEXT, Some(out)).expect("fs.read_to_string failed")
+}
+
+// fs.write(path, text) -> Fs_Status, replacing whatever the file held.
+pub fn write<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    call(ctx, "adan_fs_write", &[args[0], args[1]], STATUS, None).expect("fs.write failed")
+}
+
+// fs.append(path, text) -> Fs_Status, creating the file if needed.
+pub fn append<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    call(ctx, "adan_fs_append", &[args[0], args[1]], STATUS, None).expect("fs.append failed")
+}
+
+// fs.exists(path) -> Boolean
+pub fn exists<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    runtime_value(ctx, "adan_fs_exists", ctx.bool_type.into(), &[args[0]]).expect("fs.exists failed")
+}
+
+// fs.remove(path) -> Fs_Status, for a file or an empty directory.
+pub fn remove<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    call(ctx, "adan_fs_remove", &[args[0]], STATUS, None).expect("fs.remove failed")
+}
+
+// fs.list_dir(path, entries) -> Fs_Status, filling the Vector<String> `entries` with the sorted
+// names in the directory. Records can't hold a vector, so it's passed in.
+pub fn list_dir<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    call(ctx, "adan_fs_list_dir", &[args[0], args[1]], STATUS, None).expect("fs.list_dir failed")
+}
+
+// fs.create_dir_all(path) -> Fs_Status
+pub fn create_dir_all<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    call(ctx, "adan_fs_create_dir_all", &[args[0]], STATUS, None).expect("fs.create_dir_all failed")
+}
+
+// fs.open(path, mode) -> Fs_File, `mode` is "r", "w" or "a". The `file` number it holds is what
+// the streaming calls below take.
+pub fn open<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    let out = out_slot(ctx, ctx.i64_type.into());
+    call(ctx, "adan_fs_open", &[args[0], args[1], out.0.into()], FILE, Some(out)).expect("fs.open failed")
+}
+
+// fs.read_line(file) -> Fs_Text, the next line without its line ending.
+pub fn read_line<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    let file = args[0];
+    let out = out_slot(ctx, ctx.string_type.into());
+    call(ctx, "adan_fs_read_line", &[file, out.0.into()], TEXT, Some(out)).expect("fs.read_line failed")
+}
+
+// fs.eof(file) -> Boolean, True once there are no lines left to read.
+pub fn eof<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    let file = args[0];
+    runtime_value(ctx, "adan_fs_eof", ctx.bool_type.into(), &[file]).expect("fs.eof failed")
+}
+
+fn write_to<'ctx>(ctx: &CodeGenContext<'ctx>, args: &[BasicValueEnum<'ctx>], newline: bool) -> BasicValueEnum<'ctx> {
+    let file = args[0];
+    let newline = ctx.bool_type.const_int(newline as u64, false);
+    call(ctx, "adan_fs_write_str", &[file, args[1], newline.into()], STATUS, None).expect("fs write failed")
+}
+
+// fs.write_str(file, text) and fs.write_line(file, text) -> Fs_Status
+pub fn write_str<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    write_to(ctx, &args, false)
+}
+
+pub fn write_line<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    write_to(ctx, &args, true)
+}
+
+// fs.close(file) -> Fs_Status
+pub fn close<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    let file = args[0];
+    call(ctx, "adan_fs_close", &[file], STATUS, None).expect("fs.close failed")
+}
+
+pub fn register_native<'ctx>(ctx: &mut CodeGenContext<'ctx>) {
+    declare_result(ctx, STATUS, None);
+    declare_result(ctx, TEXT, Some(("text", Types::String)));
+    declare_result(ctx, FILE, Some(("file", Types::i64)));
+    let entries = TypeName::Vector(Box::new(TypeName::Builtin(Types::String)));
+
+    ctx.register_native_fn(LIBRARY_NAME, "read_to_string", &[NativeParam::STRING], read_to_string);
+    ctx.register_native_fn(LIBRARY_NAME, "write", &[NativeParam::STRING, NativeParam::STRING], write);
+    ctx.register_native_fn(LIBRARY_NAME, "append", &[NativeParam::STRING, NativeParam::STRING], append);
+    ctx.register_native_fn(LIBRARY_NAME, "exists", &[NativeParam::STRING], exists);
+    ctx.register_native_fn(LIBRARY_NAME, "remove", &[NativeParam::STRING], remove);
+    ctx.register_native_fn(LIBRARY_NAME, "list_dir", &[NativeParam::STRING, NativeParam::Of(entries)], list_dir);
+    ctx.register_native_fn(LIBRARY_NAME, "create_dir_all", &[NativeParam::STRING], create_dir_all);
+    ctx.register_native_fn(LIBRARY_NAME, "open", &[NativeParam::STRING, NativeParam::STRING], open);
+    ctx.register_native_fn(LIBRARY_NAME, "read_line", &[NativeParam::I64], read_line);
+    ctx.register_native_fn(LIBRARY_NAME, "eof", &[NativeParam::I64], eof);
+    ctx.register_native_fn(LIBRARY_NAME, "write_str", &[NativeParam::I64, NativeParam::STRING], write_str);
+    ctx.register_native_fn(LIBRARY_NAME, "write_line", &[NativeParam::I64, NativeParam::STRING], write_line);
+    ctx.register_native_fn(LIBRARY_NAME, "close", &[NativeParam::I64], close);
+}
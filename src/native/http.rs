@@ -0,0 +1,103 @@
+use inkwell::types::BasicTypeEnum;
+use inkwell::values::BasicValueEnum;
+use crate::code_gen::builder::{CodeGenContext, NativeParam};
+use crate::code_gen::types::declare_type;
+use crate::lexer::token::Types;
+use crate::native::vector::{handle_type, runtime_value};
+use crate::parser::ast::{FieldDecl, TypeDefinition, TypeName};
+
+static LIBRARY_NAME: &str = "http";
+
+// What http.get and http.post hand back. Like the `Fs_*` records, `ok` tells whether an answer
+// came back at all and `error` says why it didn't, a 404 is still ok with a `status` of 404.
+// Records can't hold a map, so `headers` are the `name: value` lines of the response, which
+// http.header looks into.
+const RESPONSE: &str = "Http_Response";
+
+// Type of `http.f(...)`, the natives themselves only see LLVM values.
+pub fn return_type(callee: &str) -> Option<TypeName> {
+    match callee.strip_prefix("http.")? {
+        "get" | "post" => Some(TypeName::Named(RESPONSE.to_string())),
+        "header" => Some(TypeName::Builtin(Types::String)),
+        _ => None,
+    }
+}
+
+fn declare_response(ctx: &mut CodeGenContext<'_>) {
+    if ctx.types.contains_key(RESPONSE) {
+        return;
+    }
+
+    let field = |name: &str, ty| FieldDecl { name: name.to_string(), field_type: TypeName::Builtin(ty) };
+    let fields = vec![
+        field("ok", Types::Boolean),
+        field("error", Types::String),
+        field("status", Types::i64),
+        field("headers", Types::String),
+        field("body", Types::String),
+    ];
+    declare_type(ctx, RESPONSE, &TypeDefinition::Record { discriminant: None, fields, variants: Vec::new() })
+        .expect("http response type");
+}
+
+// Sends the request through the runtime (adan_rt/src/http.rs) and builds the Http_Response.
+// `headers` is a Map<String, String>, a map literal was turned into one just for the call.
+fn request<'ctx>(ctx: &CodeGenContext<'ctx>, method: &str, url: BasicValueEnum<'ctx>, body: Option<BasicValueEnum<'ctx>>, headers: Option<BasicValueEnum<'ctx>>) -> Result<BasicValueEnum<'ctx>, String> {
+    let null = handle_type(ctx).into_pointer_type().const_null();
+    let headers = headers.unwrap_or(null.into());
+
+    let out = |ty: BasicTypeEnum<'ctx>| -> Result<_, String> {
+        let slot = ctx.build_entry_alloca(ty, "http_out", None)?;
+        ctx.builder.build_store(slot, ty.const_zero())
+            .map_err(|e| format!("http out slot failed: {:?}", e))?;
+        Ok((slot, ty))
+    };
+    let outs = [out(ctx.i64_type.into())?, out(ctx.string_type.into())?, out(ctx.string_type.into())?];
+
+    let method = ctx.build_string_literal(method);
+    let mut args = vec![method.into(), url, body.unwrap_or(null.into()), headers];
+    args.extend(outs.iter().map(|(slot, _)| BasicValueEnum::from(*slot)));
+    let error = runtime_value(ctx, "adan_http_request", ctx.string_type.into(), &args)?.into_pointer_value();
+
+    let ok = ctx.builder.build_is_null(error, "http_ok")
+        .map_err(|e| format!("http check failed: {:?}", e))?;
+    let record_type = ctx.get_llvm_type(&TypeName::Named(RESPONSE.to_string()))?.into_struct_type();
+    let mut record = ctx.builder.build_insert_value(record_type.get_undef(), ok, 0, "http_response")
+        .map_err(|e| format!("http response failed: {:?}", e))?;
+    record = ctx.builder.build_insert_value(record, error, 1, "http_response")
+        .map_err(|e| format!("http response failed: {:?}", e))?;
+    for (i, (slot, ty)) in outs.into_iter().enumerate() {
+        let value = ctx.builder.build_load(ty, slot, "http_value")
+            .map_err(|e| format!("load http value failed: {:?}", e))?;
+        record = ctx.builder.build_insert_value(record, value, i as u32 + 2, "http_response")
+            .map_err(|e| format!("http response failed: {:?}", e))?;
+    }
+    Ok(record.into_struct_value().into())
+}
+
+// http.get(url) or http.get(url, headers) -> Http_Response
+pub fn get<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    request(ctx, "GET", args[0], None, args.get(1).copied()).expect("http.get failed")
+}
+
+// http.post(url, body) or http.post(url, body, headers) -> Http_Response, for instance
+// http.post(url, json, { "Content-Type" -> "application/json" }).
+pub fn post<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    request(ctx, "POST", args[0], Some(args[1]), args.get(2).copied()).expect("http.post failed")
+}
+
+// http.header(response.headers, name) is the value of the header `name`, whatever its case, or ""
+// when the response doesn't have it.
+pub fn header<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    runtime_value(ctx, "adan_http_header", ctx.string_type.into(), &[args[0], args[1]]).expect("http.header failed")
+}
+
+pub fn register_native<'ctx>(ctx: &mut CodeGenContext<'ctx>) {
+    declare_response(ctx);
+    let string = TypeName::Builtin(Types::String);
+    let headers = TypeName::Map { key: Box::new(string.clone()), value: Box::new(string) };
+
+    ctx.register_native_fn(LIBRARY_NAME, "get", &[NativeParam::STRING, NativeParam::Optional(headers.clone())], get);
+    ctx.register_native_fn(LIBRARY_NAME, "post", &[NativeParam::STRING, NativeParam::STRING, NativeParam::Optional(headers)], post);
+    ctx.register_native_fn(LIBRARY_NAME, "header", &[NativeParam::STRING, NativeParam::STRING], header);
+}
@@ -1,47 +1,143 @@
-use inkwell::values::{BasicValueEnum, PointerValue};
-use inkwell::AddressSpace;
-use crate::code_gen::builder::CodeGenContext;
+use inkwell::types::BasicTypeEnum;
+use inkwell::values::BasicValueEnum;
+use crate::code_gen::builder::{CodeGenContext, NativeParam};
+use crate::code_gen::types::{type_display, type_of_value};
+use crate::lexer::token::{Span, Types};
+use crate::native::string;
+use crate::native::vector::runtime_value;
+use crate::parser::ast::TypeName;
 
 static LIBRARY_NAME: &str = "io";
 
-fn add_printf_support<'ctx>(ctx: &mut CodeGenContext<'ctx>) -> inkwell::values::FunctionValue<'ctx> {
-    if let Some(func) = ctx.module.get_function("printf") {
-        func
-    } else {
-        let i8ptr_type = ctx.context.i8_type().ptr_type(AddressSpace::from(0));
-        let printf_type = ctx.context.i32_type().fn_type(&[i8ptr_type.into()], true);
-       
-        ctx.module.add_function("printf", printf_type, None)
+// Type of `io.f()` for the natives that return something worth knowing about.
+pub fn return_type(callee: &str) -> Option<TypeName> {
+    match callee.strip_prefix("io.")? {
+        "readln" => Some(TypeName::Builtin(Types::String)),
+        "read_i64" => Some(TypeName::Builtin(Types::i64)),
+        "read_f64" => Some(TypeName::Builtin(Types::f64)),
+        "eof" => Some(TypeName::Builtin(Types::Boolean)),
+        _ => None,
     }
 }
 
+// Natives that print their arguments. Calls to them are generated with the static type of each
+// argument (see `codegen_print`), the natives below only see LLVM values.
+pub const PRINT_FUNCTIONS: [&str; 3] = ["printf", "print", "eprintf"];
+
+// io.printf(x) prints any value on a line of its own, io.printf(format, args...) fills each `{}`
+// of the format with the image of the next argument. io.print leaves out the newline and
+// io.eprintf writes to stderr. Output goes through the runtime (adan_rt/src/io.rs), which also
+// takes care of flushing.
+pub fn codegen_print<'ctx>(ctx: &CodeGenContext<'ctx>, name: &str, args: &[(BasicValueEnum<'ctx>, Option<TypeName>)], span: Span) -> Result<(), String> {
+    let at = format!("{}:{}:{}", ctx.source_name, span.line, span.column);
+    let runtime_name = match name {
+        "printf" => "adan_io_println",
+        "print" => "adan_io_print",
+        "eprintf" => "adan_io_eprintln",
+        _ => return Err(format!("{}: io.{} doesn't print", at, name)),
+    };
+
+    let types: Vec<TypeName> = args.iter().map(|(value, source)| source.clone().unwrap_or_else(|| type_of_value(value))).collect();
+    let mut rendered = Vec::new();
+    for ((value, _), ty) in args.iter().zip(&types) {
+        if string::is_string(ctx, ty) {
+            rendered.push((*value, false));
+        } else {
+            rendered.push((string::image(ctx, ty, *value, Some(ty), span)?, true));
+        }
+    }
+
+    let (text, owned) = match rendered.as_slice() {
+        [] => return Err(format!("{}: io.{} expects something to print", at, name)),
+        [single] => *single,
+        [(format, _), rest @ ..] => {
+            if !string::is_string(ctx, &types[0]) {
+                return Err(format!("{}: the format of io.{} must be a String, got {}", at, name, type_display(&types[0])));
+            }
+            let pieces: Vec<BasicValueEnum<'ctx>> = rest.iter().map(|(piece, _)| *piece).collect();
+            (string::format(ctx, *format, &pieces, &format!("io.{}", name), span)?, true)
+        }
+    };
+
+    ctx.call_runtime(runtime_name, None, &[text])?;
+    if owned {
+        string::free(ctx, text)?;
+    }
+    for (piece, owned) in rendered.into_iter().skip(1) {
+        if owned {
+            string::free(ctx, piece)?;
+        }
+    }
+    Ok(())
+}
+
+fn print_values<'ctx>(ctx: &mut CodeGenContext<'ctx>, name: &str, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    let args: Vec<_> = args.into_iter().map(|value| (value, None)).collect();
+    codegen_print(ctx, name, &args, Span::default()).expect("io print failed");
+
+    // Not the argument, which may be a String that's freed right after the call.
+    ctx.i64_type.const_zero().into()
+}
+
 pub fn printf<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
-    let printf_fn = add_printf_support(ctx);
-    let fmt_str = ctx.context.const_string(b"%s\n\0", true);
-    
-    let g_fmt = ctx.module.add_global(fmt_str.get_type(), None, "fmt_str");
-    g_fmt.set_initializer(&fmt_str);
+    print_values(ctx, "printf", args)
+}
 
-    let fmt_ptr = g_fmt.as_pointer_value();
-    let str_ptr = args[0].into_pointer_value();
-    let str_i8 = ctx.builder.build_pointer_cast(
-        str_ptr,
-        ctx.context.i8_type().ptr_type(AddressSpace::from(0)),
-        "cast_str"
-    ).expect("pointer casting failed");
+pub fn print<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    print_values(ctx, "print", args)
+}
+
+pub fn eprintf<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    print_values(ctx, "eprintf", args)
+}
 
-    ctx.builder.build_call(
-        printf_fn,
-        &[fmt_ptr.into(), str_i8.into()],
-        "call_printf",
-    );
+// io.readln() is the next line of input without its line ending, or "" once the input has ended.
+pub fn readln<'ctx>(ctx: &mut CodeGenContext<'ctx>, _args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    runtime_value(ctx, "adan_io_readln", ctx.string_type.into(), &[]).expect("io.readln failed")
+}
 
-    args[0]
+// io.eof() is True when there's no more input, so `while not io.eof()` reads every line.
+pub fn eof<'ctx>(ctx: &mut CodeGenContext<'ctx>, _args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    runtime_value(ctx, "adan_io_eof", ctx.bool_type.into(), &[]).expect("io.eof failed")
+}
+
+// Reads a line holding one number. A line that isn't one, or the end of input, panics at the call
+// with the runtime's message.
+fn read_number<'ctx>(ctx: &CodeGenContext<'ctx>, name: &str, read_type: BasicTypeEnum<'ctx>) -> Result<BasicValueEnum<'ctx>, String> {
+    let out = ctx.build_entry_alloca(read_type, "read_out", None)?;
+    let error = runtime_value(ctx, name, ctx.string_type.into(), &[out.into()])?.into_pointer_value();
+    let read = ctx.builder.build_is_null(error, "read_ok")
+        .map_err(|e| format!("read check failed: {:?}", e))?;
+
+    let func = ctx.builder.get_insert_block().and_then(|b| b.get_parent()).ok_or("Read outside of a function")?;
+    let fail_block = ctx.context.append_basic_block(func, "read_fail");
+    let ok_block = ctx.context.append_basic_block(func, "read_ok");
+    ctx.builder.build_conditional_branch(read, ok_block, fail_block)
+        .map_err(|e| format!("read branch failed: {:?}", e))?;
+    ctx.builder.position_at_end(fail_block);
+    ctx.build_panic(error.into(), ctx.call_span)?;
+
+    ctx.builder.position_at_end(ok_block);
+    ctx.builder.build_load(read_type, out, "read")
+        .map_err(|e| format!("load read value failed: {:?}", e))
+}
+
+pub fn read_i64<'ctx>(ctx: &mut CodeGenContext<'ctx>, _args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    read_number(ctx, "adan_io_read_i64", ctx.i64_type.into()).expect("io.read_i64 failed")
+}
+
+pub fn read_f64<'ctx>(ctx: &mut CodeGenContext<'ctx>, _args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    read_number(ctx, "adan_io_read_f64", ctx.f64_type.into()).expect("io.read_f64 failed")
 }
 
 pub fn register_native<'ctx>(ctx: &mut CodeGenContext<'ctx>) {
-    ctx.register_native_fn(LIBRARY_NAME, "printf", printf);
+    ctx.register_native_fn(LIBRARY_NAME, "printf", &[NativeParam::Generated], printf);
+    ctx.register_native_fn(LIBRARY_NAME, "print", &[NativeParam::Generated], print);
+    ctx.register_native_fn(LIBRARY_NAME, "eprintf", &[NativeParam::Generated], eprintf);
+    ctx.register_native_fn(LIBRARY_NAME, "readln", &[], readln);
+    ctx.register_native_fn(LIBRARY_NAME, "eof", &[], eof);
+    ctx.register_native_fn(LIBRARY_NAME, "read_i64", &[], read_i64);
+    ctx.register_native_fn(LIBRARY_NAME, "read_f64", &[], read_f64);
 
     // println!("Added '{}' to module registry", LIBRARY_NAME);
 }
-
@@ -0,0 +1,231 @@
+use inkwell::values::{ArrayValue, BasicValueEnum, PointerValue};
+use crate::code_gen::builder::{CodeGenContext, NativeParam};
+use crate::code_gen::managed;
+use crate::code_gen::types::{build_check, builtin_bounds};
+use crate::lexer::token::{Span, Types};
+use crate::native::string;
+use crate::native::vector::{self, element_size, handle_type, own_item, runtime_value, spill_item};
+use crate::parser::ast::TypeName;
+
+static LIBRARY_NAME: &str = "map";
+
+// Maps are handles to the runtime's insertion ordered hash map (adan_rt/src/map.rs). Keys and
+// values go in and out through memory, the same way vector elements do.
+
+// Strings are compared by their contents, any other key must be discrete so comparing its bytes
+// is exact.
+pub fn valid_key(ctx: &CodeGenContext<'_>, key: &TypeName) -> bool {
+    match ctx.resolve_builtin(key) {
+        Ok(Types::String | Types::Boolean | Types::Char) => true,
+        Ok(t) => builtin_bounds(t).is_some(),
+        Err(_) => false,
+    }
+}
+
+pub fn build_new<'ctx>(ctx: &CodeGenContext<'ctx>, key: &TypeName, value: &TypeName) -> Result<BasicValueEnum<'ctx>, String> {
+    let string_keys = matches!(ctx.resolve_builtin(key), Ok(Types::String));
+    runtime_value(ctx, "adan_map_new", handle_type(ctx), &[
+        element_size(ctx, key)?,
+        element_size(ctx, value)?,
+        ctx.i8_type.const_int(string_keys as u64, false).into(),
+    ])
+}
+
+// `{ "a" -> 1 }` arrives as an array of key/value pairs, each converted and inserted in order.
+// The literal already made its strings the map's own, except keys, which the map copies anyway.
+pub fn from_pairs<'ctx>(ctx: &CodeGenContext<'ctx>, pairs: ArrayValue<'ctx>, key: &TypeName, value: &TypeName, span: Span) -> Result<BasicValueEnum<'ctx>, String> {
+    let map = build_new(ctx, key, value)?;
+    for i in 0..pairs.get_type().len() {
+        let pair = ctx.builder.build_extract_value(pairs, i, "pair")
+            .map_err(|e| format!("extract map entry failed: {:?}", e))?
+            .into_struct_value();
+        let pair_key = ctx.builder.build_extract_value(pair, 0, "key")
+            .map_err(|e| format!("extract map key failed: {:?}", e))?;
+        let pair_value = ctx.builder.build_extract_value(pair, 1, "value")
+            .map_err(|e| format!("extract map value failed: {:?}", e))?;
+
+        let key_ptr = spill_item(ctx, &(pair_key, None), key, span)?;
+        let value_ptr = spill_item(ctx, &(pair_value, None), value, span)?;
+        ctx.call_runtime("adan_map_insert", None, &[map, key_ptr.into(), value_ptr.into()])?;
+        if string::is_string(ctx, key) {
+            string::free(ctx, pair_key)?;
+        }
+    }
+
+    Ok(map)
+}
+
+// Like vectors, maps own their String values and the strings in record and array values.
+pub fn clone_map<'ctx>(ctx: &CodeGenContext<'ctx>, map: BasicValueEnum<'ctx>, value: &TypeName) -> Result<BasicValueEnum<'ctx>, String> {
+    let copy = runtime_value(ctx, "adan_map_clone", handle_type(ctx), &[map])?;
+    if string::is_string(ctx, value) {
+        ctx.call_runtime("adan_map_dup_strings", None, &[copy])?;
+    } else if managed::holds_strings(ctx, value) {
+        for_each_value(ctx, copy, &|item| managed::own_strings(ctx, item, value))?;
+    }
+    Ok(copy)
+}
+
+// Frees the strings the values hold, leaving the map itself alone.
+fn free_strings<'ctx>(ctx: &CodeGenContext<'ctx>, map: BasicValueEnum<'ctx>, value: &TypeName) -> Result<(), String> {
+    if string::is_string(ctx, value) {
+        ctx.call_runtime("adan_map_free_strings", None, &[map])?;
+    } else if managed::holds_strings(ctx, value) {
+        for_each_value(ctx, map, &|item| managed::free_strings(ctx, item, value))?;
+    }
+    Ok(())
+}
+
+// Calls `f` with the address of each value in turn.
+fn for_each_value<'ctx>(ctx: &CodeGenContext<'ctx>, map: BasicValueEnum<'ctx>, f: &dyn Fn(PointerValue<'ctx>) -> Result<(), String>) -> Result<(), String> {
+    let len = length(ctx, map)?.into_int_value();
+    managed::build_counted_loop(ctx, len, &|index| {
+        let item = runtime_value(ctx, "adan_map_value_at", handle_type(ctx), &[map, index.into()])?.into_pointer_value();
+        f(item)
+    })
+}
+
+pub fn free<'ctx>(ctx: &CodeGenContext<'ctx>, map: BasicValueEnum<'ctx>, value: &TypeName) -> Result<(), String> {
+    free_strings(ctx, map, value)?;
+    ctx.call_runtime("adan_map_free", None, &[map])?;
+    Ok(())
+}
+
+pub fn length<'ctx>(ctx: &CodeGenContext<'ctx>, map: BasicValueEnum<'ctx>) -> Result<BasicValueEnum<'ctx>, String> {
+    runtime_value(ctx, "adan_map_len", ctx.i64_type.into(), &[map])
+}
+
+// Keys in insertion order, as a new Vector<K>.
+pub fn keys<'ctx>(ctx: &CodeGenContext<'ctx>, map: BasicValueEnum<'ctx>) -> Result<BasicValueEnum<'ctx>, String> {
+    runtime_value(ctx, "adan_map_keys", handle_type(ctx), &[map])
+}
+
+// Address of the value for `key`, failing at runtime when the map doesn't contain it.
+pub fn value_ptr<'ctx>(ctx: &CodeGenContext<'ctx>, map: BasicValueEnum<'ctx>, key: PointerValue<'ctx>, span: Span) -> Result<PointerValue<'ctx>, String> {
+    let found = runtime_value(ctx, "adan_map_get", handle_type(ctx), &[map, key.into()])?.into_pointer_value();
+    let present = ctx.builder.build_is_not_null(found, "key_present")
+        .map_err(|e| format!("null check failed: {:?}", e))?;
+    build_check(ctx, present, "key not found in map", span)?;
+    Ok(found)
+}
+
+// Address of the value for `key`, adding the key first if needed. Assigning to `m[key]` uses this.
+pub fn entry_ptr<'ctx>(ctx: &CodeGenContext<'ctx>, map: BasicValueEnum<'ctx>, key: PointerValue<'ctx>) -> Result<PointerValue<'ctx>, String> {
+    Ok(runtime_value(ctx, "adan_map_entry", handle_type(ctx), &[map, key.into()])?.into_pointer_value())
+}
+
+// Frees the strings of the value stored under `key`, if there's one, before it's replaced or removed.
+// A missing value reads as a zeroed one, which has none.
+fn free_stored<'ctx>(ctx: &CodeGenContext<'ctx>, map: BasicValueEnum<'ctx>, key: PointerValue<'ctx>, value: &TypeName) -> Result<(), String> {
+    let value_type = ctx.get_llvm_type(value)?;
+    let none = ctx.build_entry_alloca(value_type, "no_value", None)?;
+    ctx.builder.build_store(none, value_type.const_zero())
+        .map_err(|e| format!("store empty value failed: {:?}", e))?;
+    let found = runtime_value(ctx, "adan_map_get", handle_type(ctx), &[map, key.into()])?.into_pointer_value();
+    let present = ctx.builder.build_is_not_null(found, "key_present")
+        .map_err(|e| format!("null check failed: {:?}", e))?;
+    let chosen = ctx.builder.build_select(present, found, none, "stored")
+        .map_err(|e| format!("select failed: {:?}", e))?;
+    managed::free_strings(ctx, chosen.into_pointer_value(), value)
+}
+
+// Type of `m.method(...)` when `m` is a map variable.
+pub fn method_type(ctx: &CodeGenContext<'_>, callee: &str) -> Option<TypeName> {
+    let (receiver, method) = callee.rsplit_once('.')?;
+    let Some(TypeName::Map { key, value }) = ctx.place_type(receiver) else {
+        return None;
+    };
+
+    match method {
+        "get" => Some(*value),
+        "keys" => Some(TypeName::Vector(key)),
+        "values" => Some(TypeName::Vector(value)),
+        "contains" | "remove" => Some(TypeName::Builtin(Types::Boolean)),
+        "insert" | "len" | "clear" => Some(TypeName::Builtin(Types::i64)),
+        _ => None,
+    }
+}
+
+// Empties the map, freeing its strings first.
+pub fn clear_items<'ctx>(ctx: &CodeGenContext<'ctx>, map: BasicValueEnum<'ctx>, value: &TypeName) -> Result<(), String> {
+    free_strings(ctx, map, value)?;
+    ctx.call_runtime("adan_map_clear", None, &[map])?;
+    Ok(())
+}
+
+// m.insert(k, v), m.get(k, default), m.contains(k), m.remove(k), m.keys(), m.values(), m.len()
+// and m.clear(). Like vectors, methods that only change the map return its new length, and String
+// values are copied on the way in and out.
+pub fn codegen_method<'ctx>(ctx: &mut CodeGenContext<'ctx>, slot: PointerValue<'ctx>, key: &TypeName, value: &TypeName, method: &str, args: Vec<(BasicValueEnum<'ctx>, Option<TypeName>)>, span: Span) -> Result<BasicValueEnum<'ctx>, String> {
+    let arity = match method {
+        "keys" | "values" | "len" | "clear" => 0,
+        "contains" | "remove" => 1,
+        "insert" | "get" => 2,
+        _ => return Err(format!("Maps have no method '{}'", method)),
+    };
+    if args.len() != arity {
+        return Err(format!("Map method '{}' expects {} arguments, got {}", method, arity, args.len()));
+    }
+
+    let map = ctx.builder.build_load(handle_type(ctx), slot, "map")
+        .map_err(|e| format!("load map failed: {:?}", e))?;
+
+    match method {
+        "insert" => {
+            let key_ptr = spill_item(ctx, &args[0], key, span)?;
+            let value_ptr = spill_item(ctx, &own_item(ctx, &args[1], value)?, value, span)?;
+            if managed::holds_strings(ctx, value) {
+                free_stored(ctx, map, key_ptr, value)?;
+            }
+            ctx.call_runtime("adan_map_insert", None, &[map, key_ptr.into(), value_ptr.into()])?;
+        }
+        "get" => {
+            // Reads either the stored value or the default, without branching.
+            let key_ptr = spill_item(ctx, &args[0], key, span)?;
+            let default_ptr = spill_item(ctx, &args[1], value, span)?;
+            let found = runtime_value(ctx, "adan_map_get", handle_type(ctx), &[map, key_ptr.into()])?.into_pointer_value();
+            let present = ctx.builder.build_is_not_null(found, "key_present")
+                .map_err(|e| format!("null check failed: {:?}", e))?;
+            let chosen = ctx.builder.build_select(present, found, default_ptr, "map_value")
+                .map_err(|e| format!("select failed: {:?}", e))?;
+
+            let found = ctx.builder.build_load(ctx.get_llvm_type(value)?, chosen.into_pointer_value(), "map_value")
+                .map_err(|e| format!("load map value failed: {:?}", e))?;
+            return managed::clone_value(ctx, found, value);
+        }
+        "contains" | "remove" => {
+            let key_ptr = spill_item(ctx, &args[0], key, span)?;
+            if method == "remove" && managed::holds_strings(ctx, value) {
+                free_stored(ctx, map, key_ptr, value)?;
+            }
+            return runtime_value(ctx, &format!("adan_map_{}", method), ctx.bool_type.into(), &[map, key_ptr.into()]);
+        }
+        "keys" => return keys(ctx, map),
+        "values" => {
+            let values = runtime_value(ctx, "adan_map_values", handle_type(ctx), &[map])?;
+            vector::own_strings(ctx, values, value)?;
+            return Ok(values);
+        }
+        "clear" => clear_items(ctx, map, value)?,
+        _ => {}
+    }
+
+    length(ctx, map)
+}
+
+// map.len(m) and map.clear(m), for callers that prefer functions over methods.
+pub fn len<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    length(ctx, args[0]).expect("map.len failed")
+}
+
+// Calls to map.clear go through codegen_method with the value type (see the FCall codegen), a
+// bare handle doesn't say whether there are strings to free.
+pub fn clear<'ctx>(ctx: &mut CodeGenContext<'ctx>, _args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    let span = ctx.call_span;
+    panic!("{}:{}:{}: map.clear needs a map variable", ctx.source_name, span.line, span.column)
+}
+
+pub fn register_native<'ctx>(ctx: &mut CodeGenContext<'ctx>) {
+    ctx.register_native_fn(LIBRARY_NAME, "len", &[NativeParam::AnyMap], len);
+    ctx.register_native_fn(LIBRARY_NAME, "clear", &[NativeParam::Generated], clear);
+}
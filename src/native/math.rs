@@ -0,0 +1,187 @@
+use inkwell::intrinsics::Intrinsic;
+use inkwell::types::BasicTypeEnum;
+use inkwell::values::{BasicMetadataValueEnum, BasicValueEnum};
+use crate::code_gen::builder::{Check, CodeGenContext, NativeParam};
+use crate::code_gen::types::{build_check, cast_value, convert_to_type, static_type, type_display, type_of_value};
+use crate::lexer::token::{Span, Types};
+use crate::parser::ast::{Expr, TypeName};
+
+static LIBRARY_NAME: &str = "math";
+
+// Float functions and the LLVM intrinsic behind each, which the optimizer can fold. They take and
+// give back the same float type, f32 stays single precision.
+const INTRINSICS: [(&str, &str, usize); 13] = [
+    ("sqrt", "llvm.sqrt", 1),
+    ("pow", "llvm.pow", 2),
+    ("exp", "llvm.exp", 1),
+    ("exp2", "llvm.exp2", 1),
+    ("log", "llvm.log", 1),
+    ("log2", "llvm.log2", 1),
+    ("log10", "llvm.log10", 1),
+    ("sin", "llvm.sin", 1),
+    ("cos", "llvm.cos", 1),
+    ("floor", "llvm.floor", 1),
+    ("ceil", "llvm.ceil", 1),
+    ("round", "llvm.round", 1),
+    ("trunc", "llvm.trunc", 1),
+];
+
+// Float functions without an intrinsic, computed by the runtime (adan_rt/src/math.rs).
+const LIBM: [(&str, usize); 8] = [("tan", 1), ("asin", 1), ("acos", 1), ("atan", 1), ("atan2", 2), ("sinh", 1), ("cosh", 1), ("tanh", 1)];
+
+// abs, min, max and clamp work on any number and keep its type.
+const NUMERIC: [(&str, usize); 4] = [("abs", 1), ("min", 2), ("max", 2), ("clamp", 3)];
+
+fn arity(name: &str) -> Option<usize> {
+    INTRINSICS.iter().map(|(n, _, arity)| (*n, *arity))
+        .chain(LIBM)
+        .chain(NUMERIC)
+        .find(|(n, _)| *n == name)
+        .map(|(_, arity)| arity)
+}
+
+// Type of `math.f(args)`: the float type of a float function, the numbers' type for the others.
+pub fn return_type(ctx: &CodeGenContext<'_>, callee: &str, args: &[Expr]) -> Option<TypeName> {
+    let name = callee.strip_prefix("math.")?;
+    arity(name)?;
+    let types: Vec<Option<TypeName>> = args.iter().map(|arg| static_type(ctx, arg)).collect();
+    if NUMERIC.iter().any(|(n, _)| *n == name) {
+        return types.into_iter().flatten().next().map(|t| ctx.arithmetic_type(t));
+    }
+    Some(TypeName::Builtin(float_type_of(ctx, &types)))
+}
+
+// f32 when the arguments are f32 and literals, f64 for anything else.
+fn float_type_of(ctx: &CodeGenContext<'_>, types: &[Option<TypeName>]) -> Types {
+    let builtins: Vec<Types> = types.iter().flatten().filter_map(|t| ctx.resolve_builtin(t).ok()).collect();
+    if !builtins.is_empty() && builtins.iter().all(|t| *t == Types::f32) { Types::f32 } else { Types::f64 }
+}
+
+fn call_intrinsic<'ctx>(ctx: &CodeGenContext<'ctx>, name: &str, overload: BasicTypeEnum<'ctx>, args: &[BasicValueEnum<'ctx>]) -> Result<BasicValueEnum<'ctx>, String> {
+    let intrinsic = Intrinsic::find(name).ok_or_else(|| format!("{} is missing", name))?;
+    let func = intrinsic.get_declaration(&ctx.module, &[overload])
+        .ok_or_else(|| format!("{} has no declaration for {:?}", name, overload))?;
+    let metadata_args: Vec<BasicMetadataValueEnum> = args.iter().map(|a| (*a).into()).collect();
+    let call_site = ctx.builder.build_call(func, &metadata_args, "math")
+        .map_err(|e| format!("call {} failed: {:?}", name, e))?;
+    Ok(unsafe { std::mem::transmute::<_, BasicValueEnum>(call_site.try_as_basic_value()) })
+}
+
+// math.f(args) with the static type of each argument. Integers passed to a float function are
+// converted, so math.sqrt(2) is the f64 square root of 2.
+pub fn codegen_call<'ctx>(ctx: &CodeGenContext<'ctx>, name: &str, args: &[(BasicValueEnum<'ctx>, Option<TypeName>)], span: Span) -> Result<BasicValueEnum<'ctx>, String> {
+    let at = format!("{}:{}:{}", ctx.source_name, span.line, span.column);
+    let expected = arity(name).ok_or_else(|| format!("{}: Function '{}' not defined in module 'math'", at, name))?;
+    if args.len() != expected {
+        return Err(format!("{}: math.{} expects {} arguments, got {}", at, name, expected, args.len()));
+    }
+    if NUMERIC.iter().any(|(n, _)| *n == name) {
+        return codegen_numeric(ctx, name, args, span);
+    }
+
+    let types: Vec<Option<TypeName>> = args.iter().map(|(_, ty)| ty.clone()).collect();
+    let float_type = float_type_of(ctx, &types);
+    let llvm_type = ctx.get_llvm_type(&TypeName::Builtin(float_type))?;
+    let mut values = Vec::new();
+    for (value, source) in args {
+        let source = source.clone().unwrap_or_else(|| type_of_value(value));
+        if !matches!(value, BasicValueEnum::IntValue(_) | BasicValueEnum::FloatValue(_)) || ctx.resolve_builtin(&source) == Ok(Types::Boolean) {
+            return Err(format!("{}: math.{} expects numbers, got {}", at, name, type_display(&source)));
+        }
+        values.push(cast_value(ctx, *value, ctx.is_unsigned(&source), llvm_type, false)?);
+    }
+
+    if let Some((_, intrinsic, _)) = INTRINSICS.iter().find(|(n, _, _)| *n == name) {
+        return call_intrinsic(ctx, intrinsic, llvm_type, &values);
+    }
+    let suffix = if float_type == Types::f32 { "f" } else { "" };
+    ctx.call_runtime(&format!("adan_math_{}{}", name, suffix), Some(llvm_type), &values)?
+        .ok_or_else(|| format!("adan_math_{} returned nothing", name))
+}
+
+// abs, min, max and clamp in the type of the first typed argument, literals take that type too
+// like they do in `x + 1`.
+fn codegen_numeric<'ctx>(ctx: &CodeGenContext<'ctx>, name: &str, args: &[(BasicValueEnum<'ctx>, Option<TypeName>)], span: Span) -> Result<BasicValueEnum<'ctx>, String> {
+    let at = format!("{}:{}:{}", ctx.source_name, span.line, span.column);
+    let ty = match args.iter().find_map(|(_, ty)| ty.clone()) {
+        Some(ty) => ctx.arithmetic_type(ty),
+        None if args.iter().any(|(value, _)| value.is_float_value()) => TypeName::Builtin(Types::f64),
+        None => type_of_value(&args[0].0),
+    };
+    let numbers = args.iter().all(|(value, _)| value.is_int_value() || value.is_float_value());
+    let Some(base) = ctx.resolve_builtin(&ty).ok().filter(|t| numbers && !matches!(t, Types::Boolean | Types::Char | Types::String)) else {
+        return Err(format!("{}: math.{} expects numbers, got {}", at, name, type_display(&ty)));
+    };
+
+    let mut values = Vec::new();
+    for (value, source) in args {
+        if let Some(source) = source {
+            if ctx.resolve_builtin(source).ok() != Some(base) {
+                return Err(format!("{}: math.{} arguments have different types, {} and {}, convert one of them explicitly", at, name, type_display(&ty), type_display(source)));
+            }
+        }
+        values.push(convert_to_type(ctx, *value, source.as_ref(), &ty, span)?);
+    }
+
+    let is_float = matches!(base, Types::f32 | Types::f64);
+    let unsigned = ctx.is_unsigned(&ty);
+    let overload = values[0].get_type();
+    let (min_name, max_name) = match (is_float, unsigned) {
+        (true, _) => ("llvm.minnum", "llvm.maxnum"),
+        (false, true) => ("llvm.umin", "llvm.umax"),
+        (false, false) => ("llvm.smin", "llvm.smax"),
+    };
+
+    match name {
+        "min" => call_intrinsic(ctx, min_name, overload, &values),
+        "max" => call_intrinsic(ctx, max_name, overload, &values),
+        "clamp" => {
+            let low = call_intrinsic(ctx, max_name, overload, &values[..2])?;
+            call_intrinsic(ctx, min_name, overload, &[low, values[2]])
+        }
+        _ if is_float => call_intrinsic(ctx, "llvm.fabs", overload, &values),
+        _ if unsigned => Ok(values[0]),
+        _ => {
+            // The most negative value has no positive counterpart, llvm.abs gives it back as it is.
+            let int_type = overload.into_int_type();
+            let poison = ctx.bool_type.const_zero();
+            let result = call_intrinsic(ctx, "llvm.abs", overload, &[values[0], poison.into()])?.into_int_value();
+            if ctx.check_enabled(Check::Overflow) {
+                let fits = ctx.builder.build_int_compare(inkwell::IntPredicate::SGE, result, int_type.const_zero(), "abs_fits")
+                    .map_err(|e| format!("abs compare failed: {:?}", e))?;
+                build_check(ctx, fits, "integer overflow", span)?;
+            }
+            Ok(result.into())
+        }
+    }
+}
+
+// Natives only see LLVM values, calls are normally generated through codegen_call with the type
+// of each argument.
+fn call_untyped<'ctx>(ctx: &CodeGenContext<'ctx>, name: &str, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    let args: Vec<_> = args.into_iter().map(|value| (value, None)).collect();
+    codegen_call(ctx, name, &args, ctx.call_span).expect("math call failed")
+}
+
+macro_rules! natives {
+    ($($name:ident),* $(,)?) => {
+        $(
+            pub fn $name<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+                call_untyped(ctx, stringify!($name), args)
+            }
+        )*
+
+        pub fn register_native<'ctx>(ctx: &mut CodeGenContext<'ctx>) {
+            $(ctx.register_native_fn(LIBRARY_NAME, stringify!($name), &[NativeParam::Generated], $name);)*
+
+            ctx.register_native_var(LIBRARY_NAME, "PI", ctx.f64_type.const_float(std::f64::consts::PI).into(), TypeName::Builtin(Types::f64));
+            ctx.register_native_var(LIBRARY_NAME, "E", ctx.f64_type.const_float(std::f64::consts::E).into(), TypeName::Builtin(Types::f64));
+        }
+    };
+}
+
+natives! {
+    sqrt, pow, exp, exp2, log, log2, log10, sin, cos, floor, ceil, round, trunc,
+    tan, asin, acos, atan, atan2, sinh, cosh, tanh,
+    abs, min, max, clamp,
+}
@@ -1 +1,51 @@
-pub mod io;
\ No newline at end of file
+pub mod chars;
+pub mod env;
+pub mod fs;
+pub mod http;
+pub mod io;
+pub mod map;
+pub mod math;
+pub mod net;
+pub mod process;
+pub mod random;
+pub mod string;
+pub mod time;
+pub mod vector;
+
+use crate::code_gen::builder::CodeGenContext;
+use crate::parser::ast::{Expr, TypeName};
+
+// What the compiler knows about a native module: the name programs call it by, what adds its
+// functions, and the type of `name.f(args)` for the calls whose type it can tell.
+pub struct NativeModule {
+    pub name: &'static str,
+    pub register: for<'ctx> fn(&mut CodeGenContext<'ctx>),
+    pub return_type: fn(&CodeGenContext<'_>, &str, &[Expr]) -> Option<TypeName>,
+}
+
+// Every native module, a new one only needs a line here. Vectors, maps and strings come first:
+// their types come from the receiver, and a variable named like a module is still a variable.
+pub static MODULES: &[NativeModule] = &[
+    NativeModule { name: "vector", register: vector::register_native, return_type: |ctx, callee, _| vector::method_type(ctx, callee) },
+    NativeModule { name: "map", register: map::register_native, return_type: |ctx, callee, _| map::method_type(ctx, callee) },
+    NativeModule { name: "string", register: string::register_native, return_type: |ctx, callee, _| string::method_type(ctx, callee) },
+    NativeModule { name: "chars", register: chars::register_native, return_type: |_, callee, _| chars::return_type(callee) },
+    NativeModule { name: "io", register: io::register_native, return_type: |_, callee, _| io::return_type(callee) },
+    NativeModule { name: "fs", register: fs::register_native, return_type: |_, callee, _| fs::return_type(callee) },
+    NativeModule { name: "http", register: http::register_native, return_type: |_, callee, _| http::return_type(callee) },
+    NativeModule { name: "net", register: net::register_native, return_type: |_, callee, _| net::return_type(callee) },
+    NativeModule { name: "time", register: time::register_native, return_type: |_, callee, _| time::return_type(callee) },
+    NativeModule { name: "env", register: env::register_native, return_type: |_, callee, _| env::return_type(callee) },
+    NativeModule { name: "process", register: process::register_native, return_type: |_, callee, _| process::return_type(callee) },
+    NativeModule { name: "math", register: math::register_native, return_type: math::return_type },
+    NativeModule { name: "random", register: random::register_native, return_type: random::return_type },
+];
+
+pub fn module(name: &str) -> Option<&'static NativeModule> {
+    MODULES.iter().find(|m| m.name == name)
+}
+
+// Type of a call to a native function, from the first module that knows it.
+pub fn return_type(ctx: &CodeGenContext<'_>, callee: &str, args: &[Expr]) -> Option<TypeName> {
+    MODULES.iter().find_map(|m| (m.return_type)(ctx, callee, args))
+}
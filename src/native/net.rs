@@ -0,0 +1,177 @@
+use inkwell::types::BasicTypeEnum;
+use inkwell::values::{BasicValueEnum, PointerValue};
+use crate::code_gen::builder::{CodeGenContext, NativeParam};
+use crate::code_gen::types::declare_type;
+use crate::lexer::token::Types;
+use crate::native::vector::runtime_value;
+use crate::parser::ast::{FieldDecl, TypeDefinition, TypeName};
+
+static LIBRARY_NAME: &str = "net";
+
+// Like fs, network errors don't stop the program, calls that can fail return one of these records
+// with `ok` and the `error` that explains a failure:
+//   Net_Status    (ok, error)              net.write, net.send_to, net.set_timeout, net.close
+//   Net_Socket    (ok, error, socket)      net.tcp_listen, net.tcp_accept, net.tcp_connect, net.udp_bind
+//   Net_Data      (ok, error, data)        net.read, net.read_line
+//   Net_Datagram  (ok, error, data, from)  net.recv_from
+// A socket is a number, like the files of fs.open, that the other calls take.
+const STATUS: &str = "Net_Status";
+const SOCKET: &str = "Net_Socket";
+const DATA: &str = "Net_Data";
+const DATAGRAM: &str = "Net_Datagram";
+
+// Type of `net.f(...)`, the natives themselves only see LLVM values.
+pub fn return_type(callee: &str) -> Option<TypeName> {
+    let name = match callee.strip_prefix("net.")? {
+        "local_addr" => return Some(TypeName::Builtin(Types::String)),
+        "tcp_listen" | "tcp_accept" | "tcp_connect" | "udp_bind" => SOCKET,
+        "read" | "read_line" => DATA,
+        "recv_from" => DATAGRAM,
+        "write" | "send_to" | "set_timeout" | "close" => STATUS,
+        _ => return None,
+    };
+    Some(TypeName::Named(name.to_string()))
+}
+
+fn declare_result(ctx: &mut CodeGenContext<'_>, name: &str, values: &[(&str, Types)]) {
+    if ctx.types.contains_key(name) {
+        return;
+    }
+
+    let field = |name: &str, ty| FieldDecl { name: name.to_string(), field_type: TypeName::Builtin(ty) };
+    let mut fields = vec![field("ok", Types::Boolean), field("error", Types::String)];
+    fields.extend(values.iter().map(|(name, ty)| field(name, *ty)));
+    declare_type(ctx, name, &TypeDefinition::Record { discriminant: None, fields, variants: Vec::new() })
+        .expect("net result type");
+}
+
+// Calls a runtime function that returns its error message, null when it worked, and builds the
+// `result` record out of it and the `values` the runtime left behind.
+fn call<'ctx>(ctx: &CodeGenContext<'ctx>, name: &str, args: &[BasicValueEnum<'ctx>], result: &str, values: &[(PointerValue<'ctx>, BasicTypeEnum<'ctx>)]) -> Result<BasicValueEnum<'ctx>, String> {
+    let mut args = args.to_vec();
+    args.extend(values.iter().map(|(out, _)| BasicValueEnum::from(*out)));
+    let error = runtime_value(ctx, name, ctx.string_type.into(), &args)?.into_pointer_value();
+    let ok = ctx.builder.build_is_null(error, "net_ok")
+        .map_err(|e| format!("net check failed: {:?}", e))?;
+
+    let record_type = ctx.get_llvm_type(&TypeName::Named(result.to_string()))?.into_struct_type();
+    let mut record = ctx.builder.build_insert_value(record_type.get_undef(), ok, 0, "net_result")
+        .map_err(|e| format!("net result failed: {:?}", e))?;
+    record = ctx.builder.build_insert_value(record, error, 1, "net_result")
+        .map_err(|e| format!("net result failed: {:?}", e))?;
+    for (i, (out, out_type)) in values.iter().enumerate() {
+        let value = ctx.builder.build_load(*out_type, *out, "net_value")
+            .map_err(|e| format!("load net value failed: {:?}", e))?;
+        record = ctx.builder.build_insert_value(record, value, i as u32 + 2, "net_result")
+            .map_err(|e| format!("net result failed: {:?}", e))?;
+    }
+    Ok(record.into_struct_value().into())
+}
+
+// Slot for a value a runtime call leaves behind, zeroed so a failed call leaves "" or 0.
+fn out_slot<'ctx>(ctx: &CodeGenContext<'ctx>, ty: BasicTypeEnum<'ctx>) -> (PointerValue<'ctx>, BasicTypeEnum<'ctx>) {
+    let slot = ctx.build_entry_alloca(ty, "net_out", None).expect("net out slot");
+    ctx.builder.build_store(slot, ty.const_zero()).expect("net out slot");
+    (slot, ty)
+}
+
+fn open<'ctx>(ctx: &CodeGenContext<'ctx>, name: &str, arg: BasicValueEnum<'ctx>) -> BasicValueEnum<'ctx> {
+    let out = out_slot(ctx, ctx.i64_type.into());
+    call(ctx, name, &[arg], SOCKET, &[out]).unwrap_or_else(|e| panic!("{} failed: {}", name, e))
+}
+
+// net.tcp_listen(addr) -> Net_Socket, listening on a host and port like "127.0.0.1:8080". Port 0
+// picks a free one, net.local_addr tells which.
+pub fn tcp_listen<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    open(ctx, "adan_net_tcp_listen", args[0])
+}
+
+// net.tcp_accept(listener) -> Net_Socket, the stream of the next connection.
+pub fn tcp_accept<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    let listener = args[0];
+    open(ctx, "adan_net_tcp_accept", listener)
+}
+
+// net.tcp_connect(addr) -> Net_Socket
+pub fn tcp_connect<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    open(ctx, "adan_net_tcp_connect", args[0])
+}
+
+// net.udp_bind(addr) -> Net_Socket
+pub fn udp_bind<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    open(ctx, "adan_net_udp_bind", args[0])
+}
+
+// net.local_addr(socket) -> String, "" for a socket that isn't open.
+pub fn local_addr<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    let socket = args[0];
+    runtime_value(ctx, "adan_net_local_addr", ctx.string_type.into(), &[socket]).expect("net.local_addr failed")
+}
+
+// net.read(stream, max) -> Net_Data, what has arrived up to `max` bytes. An empty `data` means the
+// other side closed the connection.
+pub fn read<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    let (stream, max) = (args[0], args[1]);
+    let out = out_slot(ctx, ctx.string_type.into());
+    call(ctx, "adan_net_read", &[stream, max], DATA, &[out]).expect("net.read failed")
+}
+
+// net.read_line(stream) -> Net_Data, the next line without its line ending.
+pub fn read_line<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    let stream = args[0];
+    let out = out_slot(ctx, ctx.string_type.into());
+    call(ctx, "adan_net_read_line", &[stream], DATA, &[out]).expect("net.read_line failed")
+}
+
+// net.write(stream, data) -> Net_Status, sending all of `data`.
+pub fn write<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    let stream = args[0];
+    call(ctx, "adan_net_write", &[stream, args[1]], STATUS, &[]).expect("net.write failed")
+}
+
+// net.send_to(udp, data, addr) -> Net_Status, one datagram.
+pub fn send_to<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    let socket = args[0];
+    call(ctx, "adan_net_send_to", &[socket, args[1], args[2]], STATUS, &[]).expect("net.send_to failed")
+}
+
+// net.recv_from(udp, max) -> Net_Datagram, the next datagram cut to `max` bytes and where it came
+// from, which is where to send an answer.
+pub fn recv_from<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    let (socket, max) = (args[0], args[1]);
+    let outs = [out_slot(ctx, ctx.string_type.into()), out_slot(ctx, ctx.string_type.into())];
+    call(ctx, "adan_net_recv_from", &[socket, max], DATAGRAM, &outs).expect("net.recv_from failed")
+}
+
+// net.set_timeout(socket, ms) -> Net_Status. Reads, writes and accepts on the socket give up with
+// an error after `ms` milliseconds, 0 lets them wait forever again.
+pub fn set_timeout<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    let (socket, ms) = (args[0], args[1]);
+    call(ctx, "adan_net_set_timeout", &[socket, ms], STATUS, &[]).expect("net.set_timeout failed")
+}
+
+// net.close(socket) -> Net_Status
+pub fn close<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    let socket = args[0];
+    call(ctx, "adan_net_close", &[socket], STATUS, &[]).expect("net.close failed")
+}
+
+pub fn register_native<'ctx>(ctx: &mut CodeGenContext<'ctx>) {
+    declare_result(ctx, STATUS, &[]);
+    declare_result(ctx, SOCKET, &[("socket", Types::i64)]);
+    declare_result(ctx, DATA, &[("data", Types::String)]);
+    declare_result(ctx, DATAGRAM, &[("data", Types::String), ("from", Types::String)]);
+
+    ctx.register_native_fn(LIBRARY_NAME, "tcp_listen", &[NativeParam::STRING], tcp_listen);
+    ctx.register_native_fn(LIBRARY_NAME, "tcp_accept", &[NativeParam::I64], tcp_accept);
+    ctx.register_native_fn(LIBRARY_NAME, "tcp_connect", &[NativeParam::STRING], tcp_connect);
+    ctx.register_native_fn(LIBRARY_NAME, "udp_bind", &[NativeParam::STRING], udp_bind);
+    ctx.register_native_fn(LIBRARY_NAME, "local_addr", &[NativeParam::I64], local_addr);
+    ctx.register_native_fn(LIBRARY_NAME, "read", &[NativeParam::I64, NativeParam::I64], read);
+    ctx.register_native_fn(LIBRARY_NAME, "read_line", &[NativeParam::I64], read_line);
+    ctx.register_native_fn(LIBRARY_NAME, "write", &[NativeParam::I64, NativeParam::STRING], write);
+    ctx.register_native_fn(LIBRARY_NAME, "send_to", &[NativeParam::I64, NativeParam::STRING, NativeParam::STRING], send_to);
+    ctx.register_native_fn(LIBRARY_NAME, "recv_from", &[NativeParam::I64, NativeParam::I64], recv_from);
+    ctx.register_native_fn(LIBRARY_NAME, "set_timeout", &[NativeParam::I64, NativeParam::I64], set_timeout);
+    ctx.register_native_fn(LIBRARY_NAME, "close", &[NativeParam::I64], close);
+}
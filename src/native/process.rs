@@ -0,0 +1,96 @@
+use inkwell::types::BasicTypeEnum;
+use inkwell::values::BasicValueEnum;
+use crate::code_gen::builder::{CodeGenContext, NativeParam};
+use crate::code_gen::types::declare_type;
+use crate::lexer::token::Types;
+use crate::native::vector::{handle_type, runtime_value};
+use crate::parser::ast::{FieldDecl, TypeDefinition, TypeName};
+
+static LIBRARY_NAME: &str = "process";
+
+// What process.run hands back. Like the `Fs_*` records, `ok` tells whether the command could be
+// started and `error` says why it couldn't. `status` is its exit status, -1 when a signal killed it.
+const OUTPUT: &str = "Process_Output";
+
+// Type of `process.f(...)`, the natives themselves only see LLVM values.
+pub fn return_type(callee: &str) -> Option<TypeName> {
+    match callee.strip_prefix("process.")? {
+        "args" => Some(TypeName::Vector(Box::new(TypeName::Builtin(Types::String)))),
+        "pid" => Some(TypeName::Builtin(Types::i64)),
+        "run" => Some(TypeName::Named(OUTPUT.to_string())),
+        _ => None,
+    }
+}
+
+fn declare_output(ctx: &mut CodeGenContext<'_>) {
+    if ctx.types.contains_key(OUTPUT) {
+        return;
+    }
+
+    let field = |name: &str, ty| FieldDecl { name: name.to_string(), field_type: TypeName::Builtin(ty) };
+    let fields = vec![
+        field("ok", Types::Boolean),
+        field("error", Types::String),
+        field("status", Types::i64),
+        field("stdout", Types::String),
+        field("stderr", Types::String),
+    ];
+    declare_type(ctx, OUTPUT, &TypeDefinition::Record { discriminant: None, fields, variants: Vec::new() })
+        .expect("process output type");
+}
+
+// process.args() is the command line in a new Vector<String>, the program's own name first.
+pub fn args<'ctx>(ctx: &mut CodeGenContext<'ctx>, _args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    runtime_value(ctx, "adan_process_args", handle_type(ctx), &[]).expect("process.args failed")
+}
+
+// process.exit(code) ends the program with the exit status `code`.
+pub fn exit<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    ctx.call_runtime("adan_process_exit", None, &[args[0]]).expect("process.exit failed");
+    ctx.i64_type.const_zero().into()
+}
+
+pub fn pid<'ctx>(ctx: &mut CodeGenContext<'ctx>, _args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    runtime_value(ctx, "adan_process_pid", ctx.i64_type.into(), &[]).expect("process.pid failed")
+}
+
+// process.run(cmd, args) runs `cmd` with the Vector<String> or array literal `args`, waits for it
+// and gives back a Process_Output with what it wrote to stdout and stderr. `cmd` is looked up in
+// the PATH and no shell is involved, so nothing in `args` needs quoting.
+pub fn run<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    // A literal like ["-l", dir] was already copied into a vector for the call.
+    let arguments = args.get(1).copied().unwrap_or_else(|| handle_type(ctx).into_pointer_type().const_null().into());
+
+    let out = |ty: BasicTypeEnum<'ctx>| {
+        let slot = ctx.build_entry_alloca(ty, "run_out", None).expect("process.run out slot");
+        ctx.builder.build_store(slot, ty.const_zero()).expect("process.run out slot");
+        (slot, ty)
+    };
+    let outs = [out(ctx.i64_type.into()), out(ctx.string_type.into()), out(ctx.string_type.into())];
+
+    let mut call_args = vec![args[0], arguments];
+    call_args.extend(outs.iter().map(|(slot, _)| BasicValueEnum::from(*slot)));
+    let error = runtime_value(ctx, "adan_process_run", ctx.string_type.into(), &call_args)
+        .expect("process.run failed")
+        .into_pointer_value();
+
+    let ok = ctx.builder.build_is_null(error, "run_ok").expect("process.run failed");
+    let record_type = ctx.get_llvm_type(&TypeName::Named(OUTPUT.to_string())).expect("process output type").into_struct_type();
+    let mut record = ctx.builder.build_insert_value(record_type.get_undef(), ok, 0, "run_result").expect("process.run failed");
+    record = ctx.builder.build_insert_value(record, error, 1, "run_result").expect("process.run failed");
+    for (i, (slot, ty)) in outs.into_iter().enumerate() {
+        let value = ctx.builder.build_load(ty, slot, "run_value").expect("process.run failed");
+        record = ctx.builder.build_insert_value(record, value, i as u32 + 2, "run_result").expect("process.run failed");
+    }
+    record.into_struct_value().into()
+}
+
+pub fn register_native<'ctx>(ctx: &mut CodeGenContext<'ctx>) {
+    declare_output(ctx);
+    let arguments = TypeName::Vector(Box::new(TypeName::Builtin(Types::String)));
+
+    ctx.register_native_fn(LIBRARY_NAME, "args", &[], args);
+    ctx.register_native_fn(LIBRARY_NAME, "exit", &[NativeParam::I64], exit);
+    ctx.register_native_fn(LIBRARY_NAME, "pid", &[], pid);
+    ctx.register_native_fn(LIBRARY_NAME, "run", &[NativeParam::STRING, NativeParam::Optional(arguments)], run);
+}
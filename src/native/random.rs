@@ -0,0 +1,131 @@
+use inkwell::IntPredicate;
+use inkwell::values::{BasicValueEnum, IntValue, PointerValue};
+use crate::code_gen::builder::{CodeGenContext, NativeParam};
+use crate::code_gen::managed;
+use crate::code_gen::types::{build_check, static_type, type_display};
+use crate::lexer::token::{Span, Types};
+use crate::native::vector;
+use crate::native::vector::runtime_value;
+use crate::parser::ast::{Expr, TypeName};
+
+static LIBRARY_NAME: &str = "random";
+
+// Numbers come from the runtime's own generator (adan_rt/src/random.rs) rather than from a crate,
+// so a program seeded with random.seed(n) draws the same numbers on every platform and release.
+// Without a seed it starts from the clock.
+
+// Type of `random.f(args)`, random.choice gives back an element of its argument.
+pub fn return_type(ctx: &CodeGenContext<'_>, callee: &str, args: &[Expr]) -> Option<TypeName> {
+    match callee.strip_prefix("random.")? {
+        "int" => Some(TypeName::Builtin(Types::i64)),
+        "float" => Some(TypeName::Builtin(Types::f64)),
+        "choice" => match static_type(ctx, args.first()?)? {
+            TypeName::Array { element, .. } | TypeName::Vector(element) => Some(*element),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// A random index into `count` elements, `count` must not be 0.
+fn below<'ctx>(ctx: &CodeGenContext<'ctx>, count: IntValue<'ctx>) -> Result<IntValue<'ctx>, String> {
+    let high = ctx.builder.build_int_sub(count, ctx.i64_type.const_int(1, false), "last")
+        .map_err(|e| format!("random index failed: {:?}", e))?;
+    Ok(runtime_value(ctx, "adan_random_int", ctx.i64_type.into(), &[ctx.i64_type.const_zero().into(), high.into()])?.into_int_value())
+}
+
+// random.seed(n) restarts the generator, the same seed always gives the same numbers.
+pub fn seed<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    ctx.call_runtime("adan_random_seed", None, &[args[0]]).expect("random.seed failed");
+    ctx.i64_type.const_zero().into()
+}
+
+// random.int(lo, hi) is any of lo..hi with the same chance, both ends included.
+pub fn int<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    let (low, high) = (args[0].into_int_value(), args[1].into_int_value());
+    let ordered = ctx.builder.build_int_compare(IntPredicate::SLE, low, high, "random_range")
+        .expect("random.int failed");
+    build_check(ctx, ordered, "random.int range is empty, lo is above hi", ctx.call_span).expect("random.int failed");
+    runtime_value(ctx, "adan_random_int", ctx.i64_type.into(), &[low.into(), high.into()]).expect("random.int failed")
+}
+
+// random.float() is in [0, 1).
+pub fn float<'ctx>(ctx: &mut CodeGenContext<'ctx>, _args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    runtime_value(ctx, "adan_random_float", ctx.f64_type.into(), &[]).expect("random.float failed")
+}
+
+// random.choice(items) is one of the elements of an array or vector, picked at random. A String
+// element is copied, like a record or an array holding strings, so the result is the caller's own.
+pub fn choice<'ctx>(ctx: &CodeGenContext<'ctx>, items: BasicValueEnum<'ctx>, ty: Option<TypeName>, span: Span) -> Result<BasicValueEnum<'ctx>, String> {
+    let at = format!("{}:{}:{}", ctx.source_name, span.line, span.column);
+    let (element, picked) = match ty {
+        Some(TypeName::Array { element, first, last }) => {
+            let array_type = items.get_type();
+            let slot = ctx.build_entry_alloca(array_type, "choice_items", None)?;
+            ctx.builder.build_store(slot, items)
+                .map_err(|e| format!("store choice items failed: {:?}", e))?;
+            let index = below(ctx, ctx.i64_type.const_int((last - first + 1) as u64, false))?;
+            let element_ptr = unsafe { ctx.builder.build_gep(array_type, slot, &[ctx.i64_type.const_zero(), index], "choice") }
+                .map_err(|e| format!("choice address failed: {:?}", e))?;
+            (element, element_ptr)
+        }
+        Some(TypeName::Vector(element)) => {
+            let len = vector::length(ctx, items)?;
+            let filled = ctx.builder.build_int_compare(IntPredicate::SGT, len, ctx.i64_type.const_zero(), "choice_filled")
+                .map_err(|e| format!("choice check failed: {:?}", e))?;
+            build_check(ctx, filled, "random.choice of an empty vector", span)?;
+            let index = below(ctx, len)?;
+            let element_ptr = runtime_value(ctx, "adan_vec_at", vector::handle_type(ctx), &[items, index.into()])?.into_pointer_value();
+            (element, element_ptr)
+        }
+        Some(other) => return Err(format!("{}: random.choice expects an array or a vector, got {}", at, type_display(&other))),
+        None => return Err(format!("{}: random.choice expects an array or a vector", at)),
+    };
+
+    let value = ctx.builder.build_load(ctx.get_llvm_type(&element)?, picked, "choice")
+        .map_err(|e| format!("load choice failed: {:?}", e))?;
+    managed::clone_value(ctx, value, &element)
+}
+
+// random.shuffle(items) puts the elements of the array or vector variable `items` in a random
+// order, in place.
+pub fn shuffle<'ctx>(ctx: &CodeGenContext<'ctx>, items: PointerValue<'ctx>, ty: Option<TypeName>, span: Span) -> Result<(), String> {
+    let at = format!("{}:{}:{}", ctx.source_name, span.line, span.column);
+    let (element, data, count) = match ty {
+        Some(TypeName::Array { element, first, last }) => (element, items, ctx.i64_type.const_int((last - first + 1) as u64, false)),
+        Some(TypeName::Vector(element)) => {
+            let handle = ctx.builder.build_load(vector::handle_type(ctx), items, "vec")
+                .map_err(|e| format!("load vector failed: {:?}", e))?;
+            let len = vector::length(ctx, handle)?;
+            // Element 0 is where the elements start, even when there are none.
+            let data = runtime_value(ctx, "adan_vec_at", vector::handle_type(ctx), &[handle, ctx.i64_type.const_zero().into()])?.into_pointer_value();
+            (element, data, len)
+        }
+        Some(other) => return Err(format!("{}: random.shuffle expects an array or a vector, got {}", at, type_display(&other))),
+        None => return Err(format!("{}: random.shuffle expects an array or a vector variable", at)),
+    };
+
+    let size = vector::element_size(ctx, &element)?;
+    ctx.call_runtime("adan_random_shuffle", None, &[data.into(), size, count.into()])?;
+    Ok(())
+}
+
+// Natives only see LLVM values, which don't tell an array from a vector. Calls to random.choice and
+// random.shuffle are generated through the functions above with the type of their argument.
+pub fn choice_untyped<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    choice(ctx, args[0], None, ctx.call_span).expect("random.choice failed")
+}
+
+pub fn shuffle_untyped<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    let items = args[0].into_pointer_value();
+    shuffle(ctx, items, None, ctx.call_span).expect("random.shuffle failed");
+    ctx.i64_type.const_zero().into()
+}
+
+pub fn register_native<'ctx>(ctx: &mut CodeGenContext<'ctx>) {
+    ctx.register_native_fn(LIBRARY_NAME, "seed", &[NativeParam::I64], seed);
+    ctx.register_native_fn(LIBRARY_NAME, "int", &[NativeParam::I64, NativeParam::I64], int);
+    ctx.register_native_fn(LIBRARY_NAME, "float", &[], float);
+    ctx.register_native_fn(LIBRARY_NAME, "choice", &[NativeParam::Generated], choice_untyped);
+    ctx.register_native_fn(LIBRARY_NAME, "shuffle", &[NativeParam::Generated], shuffle_untyped);
+}
@@ -0,0 +1,362 @@
+use inkwell::types::{BasicTypeEnum, StructType};
+use inkwell::values::{BasicValue, BasicValueEnum, IntValue, PointerValue};
+use crate::code_gen::builder::{Check, CodeGenContext, NativeParam};
+use crate::code_gen::types::{build_check, cast_value, convert_explicit, convert_to_type, static_type, type_display, VariantPart};
+use crate::lexer::token::{Span, Types};
+use crate::native::vector::{self, runtime_value};
+use crate::parser::ast::{Expr, FieldDecl, Operation, TypeName};
+
+static LIBRARY_NAME: &str = "string";
+
+// Strings point at bytes behind a length header (adan_rt/src/string.rs). Literals are constants,
+// any other string is a heap copy owned by one variable or container, like vectors. What an
+// expression builds along the way is freed as soon as it has been used.
+fn string_type<'ctx>(ctx: &CodeGenContext<'ctx>) -> BasicTypeEnum<'ctx> {
+    ctx.string_type.into()
+}
+
+pub fn is_string(ctx: &CodeGenContext<'_>, ty: &TypeName) -> bool {
+    ctx.resolve_builtin(ty) == Ok(Types::String)
+}
+
+// Expressions that build a string nobody owns yet. Functions and methods that return a String hand
+// back their own copy, natives included, while a conversion only relabels the string it's given.
+pub fn is_temporary(ctx: &CodeGenContext<'_>, expr: &Expr) -> bool {
+    let builds_string = || static_type(ctx, expr).is_some_and(|t| is_string(ctx, &t));
+    match expr {
+        Expr::Binary { op: Operation::Concat, .. } | Expr::Interpolation { .. } => true,
+        Expr::Slice { .. } | Expr::AttributeCall { .. } => builds_string(),
+        Expr::FCall { callee, .. } => !ctx.types.contains_key(callee) && builds_string(),
+        _ => false,
+    }
+}
+
+pub fn dup<'ctx>(ctx: &CodeGenContext<'ctx>, s: BasicValueEnum<'ctx>) -> Result<BasicValueEnum<'ctx>, String> {
+    runtime_value(ctx, "adan_str_dup", string_type(ctx), &[s])
+}
+
+pub fn free<'ctx>(ctx: &CodeGenContext<'ctx>, s: BasicValueEnum<'ctx>) -> Result<(), String> {
+    ctx.call_runtime("adan_str_free", None, &[s])?;
+    Ok(())
+}
+
+// Frees `value` once it has been used, when `expr` built it just for that.
+pub fn free_temporary<'ctx>(ctx: &CodeGenContext<'ctx>, expr: &Expr, value: BasicValueEnum<'ctx>) -> Result<(), String> {
+    if is_temporary(ctx, expr) {
+        free(ctx, value)?;
+    }
+    Ok(())
+}
+
+pub fn length<'ctx>(ctx: &CodeGenContext<'ctx>, s: BasicValueEnum<'ctx>) -> Result<IntValue<'ctx>, String> {
+    Ok(runtime_value(ctx, "adan_str_len", ctx.i64_type.into(), &[s])?.into_int_value())
+}
+
+pub fn from_char<'ctx>(ctx: &CodeGenContext<'ctx>, c: BasicValueEnum<'ctx>) -> Result<BasicValueEnum<'ctx>, String> {
+    runtime_value(ctx, "adan_str_from_char", string_type(ctx), &[c])
+}
+
+pub fn concat<'ctx>(ctx: &CodeGenContext<'ctx>, lhs: BasicValueEnum<'ctx>, rhs: BasicValueEnum<'ctx>) -> Result<BasicValueEnum<'ctx>, String> {
+    runtime_value(ctx, "adan_str_concat", string_type(ctx), &[lhs, rhs])
+}
+
+// `pieces` stored in a stack array, for the runtime functions that take a list of strings.
+fn string_array<'ctx>(ctx: &CodeGenContext<'ctx>, pieces: &[BasicValueEnum<'ctx>]) -> Result<(BasicValueEnum<'ctx>, BasicValueEnum<'ctx>), String> {
+    let array_type = ctx.string_type.array_type(pieces.len() as u32);
+    let array = ctx.build_entry_alloca(array_type.into(), "pieces", None)?;
+    for (i, piece) in pieces.iter().enumerate() {
+        let index = ctx.i64_type.const_int(i as u64, false);
+        let slot = unsafe { ctx.builder.build_in_bounds_gep(array_type, array, &[ctx.i64_type.const_zero(), index], "piece") }
+            .map_err(|e| format!("piece access failed: {:?}", e))?;
+        ctx.builder.build_store(slot, *piece)
+            .map_err(|e| format!("store piece failed: {:?}", e))?;
+    }
+
+    Ok((array.into(), ctx.i64_type.const_int(pieces.len() as u64, false).into()))
+}
+
+// One string made of `pieces` in order, joined by the runtime in a single allocation.
+pub fn join<'ctx>(ctx: &CodeGenContext<'ctx>, pieces: &[BasicValueEnum<'ctx>]) -> Result<BasicValueEnum<'ctx>, String> {
+    let (array, count) = string_array(ctx, pieces)?;
+    runtime_value(ctx, "adan_str_join", string_type(ctx), &[array, count])
+}
+
+// `format` with each `{}` replaced by the next of `pieces`. The runtime counts the placeholders, so a
+// format that's only known when the program runs still panics at `span` when the counts differ,
+// with `what` (the function being called) in front of the runtime's message.
+pub fn format<'ctx>(ctx: &CodeGenContext<'ctx>, format: BasicValueEnum<'ctx>, pieces: &[BasicValueEnum<'ctx>], what: &str, span: Span) -> Result<BasicValueEnum<'ctx>, String> {
+    let (array, count) = string_array(ctx, pieces)?;
+    let out = ctx.build_entry_alloca(string_type(ctx), "formatted", None)?;
+    let error = runtime_value(ctx, "adan_str_format", string_type(ctx), &[format, array, count, out.into()])?.into_pointer_value();
+    let formatted = ctx.builder.build_is_null(error, "format_ok")
+        .map_err(|e| format!("format check failed: {:?}", e))?;
+
+    let func = ctx.builder.get_insert_block().and_then(|b| b.get_parent()).ok_or("Format outside of a function")?;
+    let fail_block = ctx.context.append_basic_block(func, "format_fail");
+    let ok_block = ctx.context.append_basic_block(func, "format_ok");
+    ctx.builder.build_conditional_branch(formatted, ok_block, fail_block)
+        .map_err(|e| format!("format branch failed: {:?}", e))?;
+    ctx.builder.position_at_end(fail_block);
+    let prefix = ctx.build_string_literal(&format!("{} ", what));
+    let message = concat(ctx, prefix.into(), error.into())?;
+    ctx.build_panic(message, span)?;
+
+    ctx.builder.position_at_end(ok_block);
+    ctx.builder.build_load(ctx.string_type, out, "formatted")
+        .map_err(|e| format!("load formatted string failed: {:?}", e))
+}
+
+// s[low..high] copies the bytes into a new string, with the same bounds as slicing a vector.
+pub fn slice<'ctx>(ctx: &CodeGenContext<'ctx>, s: BasicValueEnum<'ctx>, low: IntValue<'ctx>, high: IntValue<'ctx>, span: Span) -> Result<BasicValueEnum<'ctx>, String> {
+    if ctx.check_enabled(Check::Index) {
+        let len = length(ctx, s)?;
+        vector::build_slice_check(ctx, low, high, len, span)?;
+    }
+    runtime_value(ctx, "adan_str_slice", string_type(ctx), &[s, low.into(), high.into()])
+}
+
+// Type of `s.method(...)` when `s` is a String variable.
+pub fn method_type(ctx: &CodeGenContext<'_>, callee: &str) -> Option<TypeName> {
+    let (receiver, method) = callee.rsplit_once('.')?;
+    if !ctx.place_type(receiver).is_some_and(|t| is_string(ctx, &t)) {
+        return None;
+    }
+
+    match method {
+        "contains" => Some(TypeName::Builtin(Types::Boolean)),
+        "find" => Some(TypeName::Builtin(Types::i64)),
+        "split" => Some(TypeName::Vector(Box::new(TypeName::Builtin(Types::String)))),
+        "replace" | "trim" | "to_upper" | "to_lower" => Some(TypeName::Builtin(Types::String)),
+        _ => None,
+    }
+}
+
+// s.contains(t), s.find(t), s.replace(from, to), s.split(separator), s.trim(), s.to_upper() and
+// s.to_lower(). None of them change `s`, the ones that return a String return a new one.
+pub fn codegen_method<'ctx>(ctx: &CodeGenContext<'ctx>, s: BasicValueEnum<'ctx>, method: &str, args: &[(BasicValueEnum<'ctx>, Option<TypeName>)], span: Span) -> Result<BasicValueEnum<'ctx>, String> {
+    let arity = match method {
+        "trim" | "to_upper" | "to_lower" => 0,
+        "contains" | "find" | "split" => 1,
+        "replace" => 2,
+        _ => return Err(format!("Strings have no method '{}'", method)),
+    };
+    if args.len() != arity {
+        return Err(format!("String method '{}' expects {} arguments, got {}", method, arity, args.len()));
+    }
+
+    let mut operands = vec![s];
+    for (value, source) in args {
+        operands.push(convert_to_type(ctx, *value, source.as_ref(), &TypeName::Builtin(Types::String), span)?);
+    }
+    let returns = match method {
+        "contains" => ctx.bool_type.into(),
+        "find" => ctx.i64_type.into(),
+        "split" => vector::handle_type(ctx),
+        _ => string_type(ctx),
+    };
+    runtime_value(ctx, &format!("adan_str_{}", method), returns, &operands)
+}
+
+// T'Image(x) for numbers, Booleans, Chars, enumerations and records of those.
+pub fn image<'ctx>(ctx: &CodeGenContext<'ctx>, prefix: &TypeName, value: BasicValueEnum<'ctx>, source: Option<&TypeName>, span: Span) -> Result<BasicValueEnum<'ctx>, String> {
+    let value = convert_to_type(ctx, value, source, prefix, span)?;
+    if let Some(literals) = ctx.enum_literals_of(prefix) {
+        return enum_image(ctx, &literals, value.into_int_value());
+    }
+    let record = match prefix {
+        TypeName::Named(name) => ctx.record_fields(name).ok().map(|fields| (name, fields)),
+        _ => None,
+    };
+    if let Some((name, fields)) = record {
+        return record_image(ctx, prefix, name, &fields, value, span);
+    }
+
+    match ctx.resolve_builtin(prefix)? {
+        Types::Char => from_char(ctx, value),
+        // Rust reads a bool from a whole byte.
+        Types::Boolean => {
+            let byte = cast_value(ctx, value, true, ctx.i8_type.into(), true)?;
+            runtime_value(ctx, "adan_str_image_bool", string_type(ctx), &[byte])
+        }
+        Types::f32 => runtime_value(ctx, "adan_str_image_f32", string_type(ctx), &[value]),
+        Types::f64 => runtime_value(ctx, "adan_str_image_f64", string_type(ctx), &[value]),
+        _ if value.is_int_value() => {
+            let unsigned = ctx.is_unsigned(prefix);
+            let wide = cast_value(ctx, value, unsigned, ctx.i64_type.into(), unsigned)?;
+            let name = if unsigned { "adan_str_image_u64" } else { "adan_str_image_i64" };
+            runtime_value(ctx, name, string_type(ctx), &[wide])
+        }
+        _ => Err(format!("{}:{}:{}: {} has no 'Image", ctx.source_name, span.line, span.column, type_display(prefix))),
+    }
+}
+
+// An enumeration's image is the name of its literal, read out of a constant table.
+fn enum_image<'ctx>(ctx: &CodeGenContext<'ctx>, literals: &[String], position: IntValue<'ctx>) -> Result<BasicValueEnum<'ctx>, String> {
+    let names: Vec<PointerValue<'ctx>> = literals.iter().map(|l| ctx.build_string_literal(l)).collect();
+    let table_type = ctx.string_type.array_type(names.len() as u32);
+    let table = ctx.module.add_global(table_type, None, "enum_images");
+    table.set_initializer(&ctx.string_type.const_array(&names));
+    table.set_constant(true);
+
+    let index = cast_value(ctx, position.into(), true, ctx.i64_type.into(), false)?.into_int_value();
+    let name_ptr = unsafe {
+        ctx.builder.build_in_bounds_gep(table_type, table.as_pointer_value(), &[ctx.i64_type.const_zero(), index], "image_ptr")
+    }.map_err(|e| format!("image table access failed: {:?}", e))?;
+    ctx.builder.build_load(ctx.string_type, name_ptr, "image")
+        .map_err(|e| format!("load image failed: {:?}", e))
+}
+
+// (x => 1, name => "Bob") like Ada, followed by the fields of whichever variant the record holds.
+fn record_image<'ctx>(ctx: &CodeGenContext<'ctx>, ty: &TypeName, name: &str, fields: &[FieldDecl], value: BasicValueEnum<'ctx>, span: Span) -> Result<BasicValueEnum<'ctx>, String> {
+    let struct_type = ctx.get_llvm_type(ty)?.into_struct_type();
+    let record = ctx.build_entry_alloca(struct_type.into(), "image_record", None)?;
+    ctx.builder.build_store(record, value)
+        .map_err(|e| format!("store record failed: {:?}", e))?;
+
+    let mut images = vec![fields_image(ctx, struct_type, record, fields, "", span)?];
+    if let Some(part) = ctx.variant_part(name) {
+        images.push(variant_image(ctx, ty, &part, struct_type, record, fields.len() as u32, span)?);
+    }
+
+    let mut pieces = vec![ctx.build_string_literal("(").into()];
+    pieces.extend(&images);
+    pieces.push(ctx.build_string_literal(")").into());
+    let image = join(ctx, &pieces)?;
+    for piece in images {
+        free(ctx, piece)?;
+    }
+    Ok(image)
+}
+
+// `name => image` for each field behind `pointer`, `lead` goes before the first one. Strings are
+// quoted so their contents can't be mistaken for other fields.
+fn fields_image<'ctx>(ctx: &CodeGenContext<'ctx>, struct_type: StructType<'ctx>, pointer: PointerValue<'ctx>, fields: &[FieldDecl], lead: &str, span: Span) -> Result<BasicValueEnum<'ctx>, String> {
+    let mut pieces = Vec::new();
+    let mut images = Vec::new();
+    for (i, field) in fields.iter().enumerate() {
+        let separator = if i == 0 { lead } else { ", " };
+        pieces.push(ctx.build_string_literal(&format!("{}{} => ", separator, field.name)).into());
+
+        let field_ptr = ctx.builder.build_struct_gep(struct_type, pointer, i as u32, &field.name)
+            .map_err(|e| format!("field access '{}' failed: {:?}", field.name, e))?;
+        let value = ctx.builder.build_load(ctx.get_llvm_type(&field.field_type)?, field_ptr, &field.name)
+            .map_err(|e| format!("load field '{}' failed: {:?}", field.name, e))?;
+        if is_string(ctx, &field.field_type) {
+            let quote: BasicValueEnum<'ctx> = ctx.build_string_literal("\"").into();
+            pieces.extend([quote, value, quote]);
+            continue;
+        }
+
+        let image = image(ctx, &field.field_type, value, Some(&field.field_type), span)?;
+        images.push(image);
+        pieces.push(image);
+    }
+
+    let joined = join(ctx, &pieces)?;
+    for image in images {
+        free(ctx, image)?;
+    }
+    Ok(joined)
+}
+
+// The fields of the variant `record` holds, picked by a switch on its discriminant.
+fn variant_image<'ctx>(ctx: &CodeGenContext<'ctx>, ty: &TypeName, part: &VariantPart, struct_type: StructType<'ctx>, record: PointerValue<'ctx>, payload: u32, span: Span) -> Result<BasicValueEnum<'ctx>, String> {
+    let disc_ptr = ctx.builder.build_struct_gep(struct_type, record, 0, "disc_ptr")
+        .map_err(|e| format!("discriminant access failed: {:?}", e))?;
+    let disc = ctx.builder.build_load(ctx.i32_type, disc_ptr, "disc")
+        .map_err(|e| format!("discriminant load failed: {:?}", e))?
+        .into_int_value();
+    let payload_ptr = ctx.builder.build_struct_gep(struct_type, record, payload, "payload")
+        .map_err(|e| format!("variant access failed: {:?}", e))?;
+
+    let entry = ctx.builder.get_insert_block().ok_or("Record image outside of a function")?;
+    let func = entry.get_parent().ok_or("Record image outside of a function")?;
+    let done = ctx.context.append_basic_block(func, "variant_image_done");
+    let mut cases = Vec::new();
+    let mut incoming = Vec::new();
+    for variant in &part.variants {
+        let (_, position) = ctx.enum_literal(&variant.choice).ok_or_else(|| format!("Unknown variant '{}'", variant.choice))?;
+        let block = ctx.context.append_basic_block(func, "variant_image");
+        cases.push((ctx.i32_type.const_int(position, false), block));
+
+        ctx.builder.position_at_end(block);
+        let image = fields_image(ctx, ctx.variant_struct_type(ty, &variant.choice)?, payload_ptr, &variant.fields, ", ", span)?;
+        let end = ctx.builder.get_insert_block().ok_or("Record image outside of a function")?;
+        ctx.builder.build_unconditional_branch(done)
+            .map_err(|e| format!("variant image branch failed: {:?}", e))?;
+        incoming.push((image, end));
+    }
+
+    // Every value of the discriminant has a variant, the default only keeps LLVM happy.
+    ctx.builder.position_at_end(entry);
+    ctx.builder.build_switch(disc, done, &cases)
+        .map_err(|e| format!("variant image switch failed: {:?}", e))?;
+    incoming.push((ctx.build_string_literal("").into(), entry));
+
+    ctx.builder.position_at_end(done);
+    let phi = ctx.builder.build_phi(ctx.string_type, "variant_image")
+        .map_err(|e| format!("variant image phi failed: {:?}", e))?;
+    for (image, block) in &incoming {
+        phi.add_incoming(&[(image as &dyn BasicValue, *block)]);
+    }
+    Ok(phi.as_basic_value())
+}
+
+// T'Value(s) reads back what T'Image writes, ignoring surrounding whitespace. Text that doesn't
+// spell a T fails at runtime, and so does a number outside T's range.
+pub fn value<'ctx>(ctx: &CodeGenContext<'ctx>, prefix: &TypeName, s: BasicValueEnum<'ctx>, span: Span) -> Result<BasicValueEnum<'ctx>, String> {
+    let failure = format!("invalid text for {}'Value", type_display(prefix));
+    if let Some(literals) = ctx.enum_literals_of(prefix) {
+        return enum_value(ctx, prefix, &literals, s, &failure, span);
+    }
+
+    let (name, read_type) = match ctx.resolve_builtin(prefix)? {
+        Types::Boolean => ("adan_str_value_bool", Types::u8),
+        Types::f32 | Types::f64 => ("adan_str_value_f64", Types::f64),
+        Types::u8 | Types::u32 | Types::u64 => ("adan_str_value_u64", Types::u64),
+        Types::i8 | Types::i32 | Types::i64 => ("adan_str_value_i64", Types::i64),
+        _ => return Err(format!("{}:{}:{}: {} has no 'Value", ctx.source_name, span.line, span.column, type_display(prefix))),
+    };
+
+    let read_llvm_type = ctx.get_llvm_type(&TypeName::Builtin(read_type))?;
+    let out = ctx.build_entry_alloca(read_llvm_type, "value_out", None)?;
+    let valid = runtime_value(ctx, name, ctx.bool_type.into(), &[s, out.into()])?.into_int_value();
+    build_check(ctx, valid, &failure, span)?;
+
+    let read = ctx.builder.build_load(read_llvm_type, out, "value")
+        .map_err(|e| format!("load value failed: {:?}", e))?;
+    if read_type == Types::u8 {
+        return ctx.builder.build_int_compare(inkwell::IntPredicate::NE, read.into_int_value(), ctx.u8_type.const_zero(), "value_bool")
+            .map(|v| v.into())
+            .map_err(|e| format!("value compare failed: {:?}", e));
+    }
+    convert_explicit(ctx, read, Some(&TypeName::Builtin(read_type)), prefix, span)
+}
+
+// Compares `s` with the name of every literal, which is short enough for any enumeration.
+fn enum_value<'ctx>(ctx: &CodeGenContext<'ctx>, prefix: &TypeName, literals: &[String], s: BasicValueEnum<'ctx>, failure: &str, span: Span) -> Result<BasicValueEnum<'ctx>, String> {
+    let position_type = ctx.get_llvm_type(prefix)?.into_int_type();
+    let mut position = position_type.const_zero();
+    let mut found = ctx.bool_type.const_zero();
+    for (i, literal) in literals.iter().enumerate() {
+        let name = ctx.build_string_literal(literal);
+        let matches = runtime_value(ctx, "adan_str_eq", ctx.bool_type.into(), &[s, name.into()])?.into_int_value();
+        position = ctx.builder.build_select(matches, position_type.const_int(i as u64, false), position, "enum_value")
+            .map_err(|e| format!("select failed: {:?}", e))?
+            .into_int_value();
+        found = ctx.builder.build_or(found, matches, "enum_found")
+            .map_err(|e| format!("enum value or failed: {:?}", e))?;
+    }
+
+    build_check(ctx, found, failure, span)?;
+    Ok(position.into())
+}
+
+// string.len(s), for callers that prefer functions over attributes.
+pub fn len<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    length(ctx, args[0]).expect("string.len failed").into()
+}
+
+pub fn register_native<'ctx>(ctx: &mut CodeGenContext<'ctx>) {
+    ctx.register_native_fn(LIBRARY_NAME, "len", &[NativeParam::STRING], len);
+}
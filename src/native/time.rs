@@ -0,0 +1,93 @@
+use inkwell::values::BasicValueEnum;
+use crate::code_gen::builder::{CodeGenContext, NativeParam};
+use crate::lexer::token::Types;
+use crate::native::vector::runtime_value;
+use crate::parser::ast::TypeName;
+
+static LIBRARY_NAME: &str = "time";
+
+// Durations are plain i64 nanoseconds, so `time.now() - start` is one and they add up like any
+// other number. time.millis and time.seconds build them, time.as_millis and time.as_seconds read
+// them back. Wall-clock timestamps are i64 milliseconds since the Unix epoch.
+const NANOS_PER_MILLI: u64 = 1_000_000;
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+// Type of `time.f(...)`, the natives themselves only see LLVM values.
+pub fn return_type(callee: &str) -> Option<TypeName> {
+    match callee.strip_prefix("time.")? {
+        "now" | "since" | "unix_ms" | "millis" | "seconds" | "as_millis" => Some(TypeName::Builtin(Types::i64)),
+        "as_seconds" => Some(TypeName::Builtin(Types::f64)),
+        "iso8601" => Some(TypeName::Builtin(Types::String)),
+        _ => None,
+    }
+}
+
+
+// time.now() is a monotonic time in nanoseconds, for measuring how long something took.
+pub fn now<'ctx>(ctx: &mut CodeGenContext<'ctx>, _args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    runtime_value(ctx, "adan_time_now", ctx.i64_type.into(), &[]).expect("time.now failed")
+}
+
+// time.since(start) is the duration from the time.now() `start` to now.
+pub fn since<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    let start = args[0].into_int_value();
+    let now = runtime_value(ctx, "adan_time_now", ctx.i64_type.into(), &[]).expect("time.since failed");
+    ctx.builder.build_int_sub(now.into_int_value(), start, "elapsed").expect("time.since failed").into()
+}
+
+// time.unix_ms() is the wall-clock time in milliseconds since 1970-01-01T00:00:00Z.
+pub fn unix_ms<'ctx>(ctx: &mut CodeGenContext<'ctx>, _args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    runtime_value(ctx, "adan_time_unix_ms", ctx.i64_type.into(), &[]).expect("time.unix_ms failed")
+}
+
+// time.sleep(ms) pauses the program for `ms` milliseconds.
+pub fn sleep<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    ctx.call_runtime("adan_time_sleep", None, &[args[0]]).expect("time.sleep failed");
+    ctx.i64_type.const_zero().into()
+}
+
+fn scale<'ctx>(ctx: &CodeGenContext<'ctx>, name: &str, args: &[BasicValueEnum<'ctx>], factor: u64) -> BasicValueEnum<'ctx> {
+    let value = args[0].into_int_value();
+    ctx.builder.build_int_mul(value, ctx.i64_type.const_int(factor, false), name).expect("time scaling failed").into()
+}
+
+// time.millis(n) and time.seconds(n) are durations of n milliseconds and n seconds.
+pub fn millis<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    scale(ctx, "millis", &args, NANOS_PER_MILLI)
+}
+
+pub fn seconds<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    scale(ctx, "seconds", &args, NANOS_PER_SECOND)
+}
+
+// time.as_millis(d) is the whole milliseconds in the duration `d`, rounded towards zero.
+pub fn as_millis<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    let duration = args[0].into_int_value();
+    ctx.builder.build_int_signed_div(duration, ctx.i64_type.const_int(NANOS_PER_MILLI, false), "as_millis").expect("time.as_millis failed").into()
+}
+
+// time.as_seconds(d) is the duration `d` in seconds, with its fraction.
+pub fn as_seconds<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    let duration = args[0].into_int_value();
+    let nanos = ctx.builder.build_signed_int_to_float(duration, ctx.f64_type, "nanos").expect("time.as_seconds failed");
+    let per_second = ctx.f64_type.const_float(NANOS_PER_SECOND as f64);
+    ctx.builder.build_float_div(nanos, per_second, "as_seconds").expect("time.as_seconds failed").into()
+}
+
+// time.iso8601(unix_ms) formats a wall-clock time like 2026-10-18T09:30:00.250Z, in UTC.
+pub fn iso8601<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    let unix_ms = args[0].into_int_value();
+    runtime_value(ctx, "adan_time_iso8601", ctx.string_type.into(), &[unix_ms.into()]).expect("time.iso8601 failed")
+}
+
+pub fn register_native<'ctx>(ctx: &mut CodeGenContext<'ctx>) {
+    ctx.register_native_fn(LIBRARY_NAME, "now", &[], now);
+    ctx.register_native_fn(LIBRARY_NAME, "since", &[NativeParam::I64], since);
+    ctx.register_native_fn(LIBRARY_NAME, "unix_ms", &[], unix_ms);
+    ctx.register_native_fn(LIBRARY_NAME, "sleep", &[NativeParam::I64], sleep);
+    ctx.register_native_fn(LIBRARY_NAME, "millis", &[NativeParam::I64], millis);
+    ctx.register_native_fn(LIBRARY_NAME, "seconds", &[NativeParam::I64], seconds);
+    ctx.register_native_fn(LIBRARY_NAME, "as_millis", &[NativeParam::I64], as_millis);
+    ctx.register_native_fn(LIBRARY_NAME, "as_seconds", &[NativeParam::I64], as_seconds);
+    ctx.register_native_fn(LIBRARY_NAME, "iso8601", &[NativeParam::I64], iso8601);
+}
@@ -0,0 +1,269 @@
+use inkwell::types::{BasicType, BasicTypeEnum};
+use inkwell::values::{ArrayValue, BasicValueEnum, IntValue, PointerValue};
+use inkwell::{AddressSpace, IntPredicate};
+use crate::code_gen::builder::{Check, CodeGenContext, NativeParam};
+use crate::code_gen::managed;
+use crate::code_gen::types::{build_check, cast_value, convert_to_type};
+use crate::lexer::token::{Span, Types};
+use crate::native::string;
+use crate::parser::ast::TypeName;
+
+static LIBRARY_NAME: &str = "vector";
+
+// Vectors are handles to storage owned by the runtime (adan_rt/src/vector.rs), which only knows
+// the size of an element. Everything typed happens here, elements go in and out through memory.
+pub fn handle_type<'ctx>(ctx: &CodeGenContext<'ctx>) -> BasicTypeEnum<'ctx> {
+    ctx.context.ptr_type(AddressSpace::from(0)).into()
+}
+
+pub fn element_size<'ctx>(ctx: &CodeGenContext<'ctx>, element: &TypeName) -> Result<BasicValueEnum<'ctx>, String> {
+    let size = ctx.get_llvm_type(element)?.size_of().ok_or_else(|| format!("{:?} has no size", element))?;
+    Ok(size.into())
+}
+
+pub fn runtime_value<'ctx>(ctx: &CodeGenContext<'ctx>, name: &str, returns: BasicTypeEnum<'ctx>, args: &[BasicValueEnum<'ctx>]) -> Result<BasicValueEnum<'ctx>, String> {
+    ctx.call_runtime(name, Some(returns), args)?.ok_or_else(|| format!("{} returned nothing", name))
+}
+
+pub fn build_new<'ctx>(ctx: &CodeGenContext<'ctx>, element: &TypeName) -> Result<BasicValueEnum<'ctx>, String> {
+    let size = element_size(ctx, element)?;
+    runtime_value(ctx, "adan_vec_new", handle_type(ctx), &[size])
+}
+
+// `[1, 2, 3]` is converted element by element, then copied into a new vector.
+pub fn from_array<'ctx>(ctx: &CodeGenContext<'ctx>, value: ArrayValue<'ctx>, source: Option<&TypeName>, element: &TypeName, span: Span) -> Result<BasicValueEnum<'ctx>, String> {
+    let count = value.get_type().len() as i64;
+    let array_type = TypeName::Array { element: Box::new(element.clone()), first: 0, last: count - 1 };
+    let items = convert_to_type(ctx, value.into(), source, &array_type, span)?;
+
+    let items_ptr = ctx.build_entry_alloca(items.get_type(), "vec_items", None)?;
+    ctx.builder.build_store(items_ptr, items)
+        .map_err(|e| format!("store vector items failed: {:?}", e))?;
+    runtime_value(ctx, "adan_vec_from", handle_type(ctx), &[
+        element_size(ctx, element)?,
+        items_ptr.into(),
+        ctx.i64_type.const_int(count as u64, false).into(),
+    ])
+}
+
+// A vector of strings owns its strings, so copying it copies them too and freeing it frees them.
+pub fn clone_vector<'ctx>(ctx: &CodeGenContext<'ctx>, vector: BasicValueEnum<'ctx>, element: &TypeName) -> Result<BasicValueEnum<'ctx>, String> {
+    let copy = runtime_value(ctx, "adan_vec_clone", handle_type(ctx), &[vector])?;
+    own_strings(ctx, copy, element)?;
+    Ok(copy)
+}
+
+// Replaces the strings in a fresh copy of a vector with copies of their own, including the ones in
+// records and arrays.
+pub fn own_strings<'ctx>(ctx: &CodeGenContext<'ctx>, vector: BasicValueEnum<'ctx>, element: &TypeName) -> Result<(), String> {
+    if string::is_string(ctx, element) {
+        ctx.call_runtime("adan_vec_dup_strings", None, &[vector])?;
+    } else if managed::holds_strings(ctx, element) {
+        for_each_element(ctx, vector, &|item| managed::own_strings(ctx, item, element))?;
+    }
+    Ok(())
+}
+
+// Frees the strings the elements hold, leaving the vector itself alone.
+fn free_strings<'ctx>(ctx: &CodeGenContext<'ctx>, vector: BasicValueEnum<'ctx>, element: &TypeName) -> Result<(), String> {
+    if string::is_string(ctx, element) {
+        ctx.call_runtime("adan_vec_free_strings", None, &[vector])?;
+    } else if managed::holds_strings(ctx, element) {
+        for_each_element(ctx, vector, &|item| managed::free_strings(ctx, item, element))?;
+    }
+    Ok(())
+}
+
+// Calls `f` with the address of each element in turn.
+fn for_each_element<'ctx>(ctx: &CodeGenContext<'ctx>, vector: BasicValueEnum<'ctx>, f: &dyn Fn(PointerValue<'ctx>) -> Result<(), String>) -> Result<(), String> {
+    let len = length(ctx, vector)?;
+    managed::build_counted_loop(ctx, len, &|index| {
+        let item = runtime_value(ctx, "adan_vec_at", handle_type(ctx), &[vector, index.into()])?.into_pointer_value();
+        f(item)
+    })
+}
+
+pub fn free<'ctx>(ctx: &CodeGenContext<'ctx>, vector: BasicValueEnum<'ctx>, element: &TypeName) -> Result<(), String> {
+    free_strings(ctx, vector, element)?;
+    ctx.call_runtime("adan_vec_free", None, &[vector])?;
+    Ok(())
+}
+
+pub fn length<'ctx>(ctx: &CodeGenContext<'ctx>, vector: BasicValueEnum<'ctx>) -> Result<IntValue<'ctx>, String> {
+    Ok(runtime_value(ctx, "adan_vec_len", ctx.i64_type.into(), &[vector])?.into_int_value())
+}
+
+// Address of element `index`, failing at runtime when it lies outside the vector.
+pub fn element_ptr<'ctx>(ctx: &CodeGenContext<'ctx>, vector: BasicValueEnum<'ctx>, index: IntValue<'ctx>, failure: &str, span: Span) -> Result<PointerValue<'ctx>, String> {
+    if ctx.check_enabled(Check::Index) {
+        let len = length(ctx, vector)?;
+        // Unsigned, so negative indices wrap around and fail too.
+        let in_bounds = ctx.builder.build_int_compare(IntPredicate::ULT, index, len, "in_bounds")
+            .map_err(|e| format!("bounds check compare failed: {:?}", e))?;
+        build_check(ctx, in_bounds, failure, span)?;
+    }
+
+    Ok(runtime_value(ctx, "adan_vec_at", handle_type(ctx), &[vector, index.into()])?.into_pointer_value())
+}
+
+// v[low..high] copies the elements into a new vector, both ends included so v[i..i - 1] is empty.
+pub fn slice<'ctx>(ctx: &CodeGenContext<'ctx>, vector: BasicValueEnum<'ctx>, element: &TypeName, low: IntValue<'ctx>, high: IntValue<'ctx>, span: Span) -> Result<BasicValueEnum<'ctx>, String> {
+    if ctx.check_enabled(Check::Index) {
+        let len = length(ctx, vector)?;
+        build_slice_check(ctx, low, high, len, span)?;
+    }
+
+    let sliced = runtime_value(ctx, "adan_vec_slice", handle_type(ctx), &[vector, low.into(), high.into()])?;
+    own_strings(ctx, sliced, element)?;
+    Ok(sliced)
+}
+
+// Fails unless low..high lies within 0..len - 1, or is the empty range right after low.
+pub fn build_slice_check<'ctx>(ctx: &CodeGenContext<'ctx>, low: IntValue<'ctx>, high: IntValue<'ctx>, len: IntValue<'ctx>, span: Span) -> Result<(), String> {
+    let one = ctx.i64_type.const_int(1, false);
+    let compare = |predicate: IntPredicate, lhs: IntValue<'ctx>, rhs: IntValue<'ctx>| ctx.builder.build_int_compare(predicate, lhs, rhs, "slice_bound")
+        .map_err(|e| format!("slice check compare failed: {:?}", e));
+
+    let low_ok = compare(IntPredicate::SGE, low, ctx.i64_type.const_zero())?;
+    let high_ok = compare(IntPredicate::SLT, high, len)?;
+    let after_high = ctx.builder.build_int_add(high, one, "after_high")
+        .map_err(|e| format!("slice check add failed: {:?}", e))?;
+    let ordered = compare(IntPredicate::SLE, low, after_high)?;
+    let in_bounds = ctx.builder.build_and(low_ok, high_ok, "slice_ok")
+        .and_then(|ok| ctx.builder.build_and(ok, ordered, "slice_ok"))
+        .map_err(|e| format!("slice check and failed: {:?}", e))?;
+    build_check(ctx, in_bounds, "slice out of bounds", span)
+}
+
+// Type of `v.method(...)` when `v` is a vector variable.
+pub fn method_type(ctx: &CodeGenContext<'_>, callee: &str) -> Option<TypeName> {
+    let (receiver, method) = callee.rsplit_once('.')?;
+    let Some(TypeName::Vector(element)) = ctx.place_type(receiver) else {
+        return None;
+    };
+
+    match method {
+        // A popped string belongs to the caller now.
+        "pop" | "remove" => Some(*element),
+        "slice" => Some(TypeName::Vector(element)),
+        "push" | "insert" | "clear" | "len" => Some(TypeName::Builtin(Types::i64)),
+        _ => None,
+    }
+}
+
+// Converts a method argument to the element type and puts it in memory for the runtime.
+pub fn spill_item<'ctx>(ctx: &CodeGenContext<'ctx>, (value, source): &(BasicValueEnum<'ctx>, Option<TypeName>), element: &TypeName, span: Span) -> Result<PointerValue<'ctx>, String> {
+    let item = convert_to_type(ctx, *value, source.as_ref(), element, span)?;
+    let item_ptr = ctx.build_entry_alloca(item.get_type(), "vec_item", None)?;
+    ctx.builder.build_store(item_ptr, item)
+        .map_err(|e| format!("store vector item failed: {:?}", e))?;
+    Ok(item_ptr)
+}
+
+// A string item is copied, and so is a record or an array holding strings. The caller frees its
+// own value as usual.
+pub fn own_item<'ctx>(ctx: &CodeGenContext<'ctx>, (value, source): &(BasicValueEnum<'ctx>, Option<TypeName>), element: &TypeName) -> Result<(BasicValueEnum<'ctx>, Option<TypeName>), String> {
+    if string::is_string(ctx, element) && value.is_pointer_value() {
+        return Ok((string::dup(ctx, *value)?, source.clone()));
+    }
+    if managed::holds_strings(ctx, element) && !string::is_string(ctx, element) {
+        return Ok((managed::clone_value(ctx, *value, element)?, source.clone()));
+    }
+    Ok((*value, source.clone()))
+}
+
+fn index_arg<'ctx>(ctx: &CodeGenContext<'ctx>, (value, source): &(BasicValueEnum<'ctx>, Option<TypeName>)) -> Result<IntValue<'ctx>, String> {
+    let unsigned = source.as_ref().is_some_and(|t| ctx.is_unsigned(t));
+    Ok(cast_value(ctx, *value, unsigned, ctx.i64_type.into(), false)?.into_int_value())
+}
+
+// Empties the vector, freeing its strings first.
+pub fn clear_items<'ctx>(ctx: &CodeGenContext<'ctx>, vector: BasicValueEnum<'ctx>, element: &TypeName) -> Result<(), String> {
+    free_strings(ctx, vector, element)?;
+    ctx.call_runtime("adan_vec_clear", None, &[vector])?;
+    Ok(())
+}
+
+// v.push(x), v.pop(), v.insert(i, x), v.remove(i), v.slice(low, high), v.len() and v.clear().
+// Methods that only change the vector return its new length. Strings that go in are copied, the
+// ones that come out belong to the caller.
+pub fn codegen_method<'ctx>(ctx: &mut CodeGenContext<'ctx>, slot: PointerValue<'ctx>, element: &TypeName, method: &str, args: Vec<(BasicValueEnum<'ctx>, Option<TypeName>)>, span: Span) -> Result<BasicValueEnum<'ctx>, String> {
+    let arity = match method {
+        "pop" | "len" | "clear" => 0,
+        "push" | "remove" => 1,
+        "insert" | "slice" => 2,
+        _ => return Err(format!("Vectors have no method '{}'", method)),
+    };
+    if args.len() != arity {
+        return Err(format!("Vector method '{}' expects {} arguments, got {}", method, arity, args.len()));
+    }
+
+    let vector = ctx.builder.build_load(handle_type(ctx), slot, "vec")
+        .map_err(|e| format!("load vector failed: {:?}", e))?;
+    let element_type = ctx.get_llvm_type(element)?;
+
+    match method {
+        "push" => {
+            let item = spill_item(ctx, &own_item(ctx, &args[0], element)?, element, span)?;
+            ctx.call_runtime("adan_vec_push", None, &[vector, item.into()])?;
+        }
+        "insert" => {
+            let index = index_arg(ctx, &args[0])?;
+            if ctx.check_enabled(Check::Index) {
+                let len = length(ctx, vector)?;
+                let in_bounds = ctx.builder.build_int_compare(IntPredicate::ULE, index, len, "in_bounds")
+                    .map_err(|e| format!("bounds check compare failed: {:?}", e))?;
+                build_check(ctx, in_bounds, "insert position out of bounds", span)?;
+            }
+
+            let item = spill_item(ctx, &own_item(ctx, &args[1], element)?, element, span)?;
+            ctx.call_runtime("adan_vec_insert", None, &[vector, index.into(), item.into()])?;
+        }
+        "pop" | "remove" => {
+            let out = ctx.build_entry_alloca(element_type, "vec_out", None)?;
+            if method == "pop" {
+                if ctx.check_enabled(Check::Index) {
+                    let len = length(ctx, vector)?;
+                    let not_empty = ctx.builder.build_int_compare(IntPredicate::NE, len, ctx.i64_type.const_zero(), "not_empty")
+                        .map_err(|e| format!("empty check compare failed: {:?}", e))?;
+                    build_check(ctx, not_empty, "pop from an empty vector", span)?;
+                }
+                ctx.call_runtime("adan_vec_pop", None, &[vector, out.into()])?;
+            } else {
+                let index = index_arg(ctx, &args[0])?;
+                if ctx.check_enabled(Check::Index) {
+                    element_ptr(ctx, vector, index, "remove index out of bounds", span)?;
+                }
+                ctx.call_runtime("adan_vec_remove", None, &[vector, index.into(), out.into()])?;
+            }
+
+            return ctx.builder.build_load(element_type, out, "vec_elem")
+                .map_err(|e| format!("load vector element failed: {:?}", e));
+        }
+        "slice" => {
+            let (low, high) = (index_arg(ctx, &args[0])?, index_arg(ctx, &args[1])?);
+            return slice(ctx, vector, element, low, high, span);
+        }
+        "clear" => clear_items(ctx, vector, element)?,
+        _ => {}
+    }
+
+    Ok(length(ctx, vector)?.into())
+}
+
+// vector.len(v) and vector.clear(v), for callers that prefer functions over methods.
+pub fn len<'ctx>(ctx: &mut CodeGenContext<'ctx>, args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    length(ctx, args[0]).expect("vector.len failed").into()
+}
+
+// Calls to vector.clear go through codegen_method with the element type (see the FCall codegen),
+// a bare handle doesn't say whether there are strings to free.
+pub fn clear<'ctx>(ctx: &mut CodeGenContext<'ctx>, _args: Vec<BasicValueEnum<'ctx>>) -> BasicValueEnum<'ctx> {
+    let span = ctx.call_span;
+    panic!("{}:{}:{}: vector.clear needs a vector variable", ctx.source_name, span.line, span.column)
+}
+
+pub fn register_native<'ctx>(ctx: &mut CodeGenContext<'ctx>) {
+    ctx.register_native_fn(LIBRARY_NAME, "len", &[NativeParam::AnyVector], len);
+    ctx.register_native_fn(LIBRARY_NAME, "clear", &[NativeParam::Generated], clear);
+}
@@ -1,35 +1,90 @@
-use crate::lexer::token::Types;
+use crate::lexer::token::{Span, Types};
 
 #[derive(Debug, Clone)]
 pub enum Expr {
     Binary {
         left: Box<Expr>,
         op: Operation,
-        right: Box<Expr>
+        right: Box<Expr>,
+        span: Span,     // Where the operator is, for overflow and division checks.
     },
 
     Unary {
         op: Operation,
         right: Box<Expr>,
+        span: Span,
     },
 
     Assign {
-        name: String,
-        value: Box<Expr>
+        target: Box<Expr>,  // A `Variable` or `Index` place.
+        value: Box<Expr>,
+        span: Span,
     },
 
     FCall {
         callee: String,
         args: Vec<Expr>,
+        span: Span,
     },
     Literal(Literal),
+    ArrayLiteral {      // [1, 2, 3]
+        elements: Vec<Expr>,
+        span: Span,
+    },
+    MapLiteral {        // { "a" -> 1, "b" -> 2 }
+        entries: Vec<(Expr, Expr)>,
+        span: Span,
+    },
+    Index {             // a[i]
+        base: Box<Expr>,
+        index: Box<Expr>,
+        span: Span,
+    },
+    Conversion {        // i32(x), f64(n), Percent(x)
+        target: TypeName,
+        value: Box<Expr>,
+        span: Span,
+    },
+    Slice {             // v[low..high], both ends included
+        base: Box<Expr>,
+        low: Box<Expr>,
+        high: Box<Expr>,
+        span: Span,
+    },
+    Attribute {         // a'Length
+        prefix: Box<Expr>,
+        attribute: String,
+        span: Span,
+    },
+    AttributeCall {     // Char'Pos(c), Color'Val(2)
+        prefix: TypeName,
+        attribute: String,
+        args: Vec<Expr>,
+        span: Span,
+    },
+    Interpolation {     // "x = {x}"
+        parts: Vec<Interpolation>,
+        span: Span,
+    },
+    Aggregate {         // Point { x -> 1.0, y -> 2.0 }
+        type_name: String,
+        fields: Vec<(String, Expr)>,
+        span: Span,
+    },
     Variable {
         var_name: String,
-        var_type: Option<Types>,
+        var_type: Option<TypeName>,
+        span: Span,
     },
     Block(Vec<Expr>),
 }
 
+#[derive(Debug, Clone)]
+pub enum Interpolation {
+    Text(String),
+    Value(Expr),        // Rendered with its type's 'Image.
+}
+
 #[derive(Debug, Clone)]
 pub enum Operation {
     Add,
@@ -37,18 +92,22 @@ pub enum Operation {
     Multiply,
     Divide,
     Modulo,
+    Concat,             // a & b, on Strings and Chars.
     Negate,             // Flips the sign of a Number. (e.g. 2 -> -2, -3 -> 3)
     Not,                // Flips the value of a boolean. (e.g. false -> true, true -> false)
     Greater,
     Lesser,
     Gequal,
     Equal,
+    NotEqual,           // a /= b
     Lequal,
 }
 
 #[derive(Debug, Clone)]
 pub enum Literal {
-    Number(f64),
+    // Numeric literals have no type of their own, they take the one their context expects.
+    Integer(i128),      // i64::MIN through u64::MAX.
+    Number(f64),        // A real, written with a fraction or an exponent.
     String(String),
     Bool(bool),
     Nil,
@@ -60,8 +119,13 @@ pub enum Statement {
     Expression(Expr),
     VarDecl {           // local <var> -> <val>;
         name: String,
-        var_type: Option<Types>,
+        var_type: Option<TypeName>,
         initializer: Option<Expr>,
+        span: Span,
+    },
+    TypeDecl {          // type <name> is <definition>;
+        name: String,
+        definition: TypeDefinition,
     },
     Block(Vec<Statement>), // { }
     If {
@@ -73,11 +137,91 @@ pub enum Statement {
         condition: Expr,
         body: Box<Statement>,
     },
+    For {               // for <var> in <low>..<high> { } / for <var> in <array> { }
+        var: String,
+        iteration: Iteration,
+        body: Vec<Statement>,
+        span: Span,
+    },
+    Case {              // case <subject> { when <choice> => { } ... }
+        subject: Expr,
+        arms: Vec<CaseArm>,
+    },
     Function(FunctionDecl),
     Return {
         value: Option<Expr>,
+        span: Span,
     },
     Include(String),
+    Pragma {            // pragma Suppress(Overflow_Check, Division_Check);
+        name: String,
+        args: Vec<String>,
+        span: Span,
+    },
+}
+
+// A type as written in a declaration, either a builtin keyword or a user declared name.
+#[derive(Debug, PartialEq, Clone)]
+pub enum TypeName {
+    Builtin(Types),
+    Named(String),
+    Array {             // Array<T, N> is indexed 0..N-1, array (first..last) of T by its range.
+        element: Box<TypeName>,
+        first: i64,
+        last: i64,
+    },
+    Vector(Box<TypeName>),
+    Map {
+        key: Box<TypeName>,
+        value: Box<TypeName>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub enum Iteration {
+    Range { low: Expr, high: Expr }, // Also what `a'Range` expands to.
+    Elements(Expr),
+}
+
+#[derive(Debug, Clone)]
+pub enum TypeDefinition {
+    // `type T is range low..high;` has no base, `subtype T is Base range low..high;` names one.
+    // A subtype without a range is a plain alias of its base.
+    Range {
+        base: Option<TypeName>,
+        bounds: Option<(i64, i64)>,
+    },
+    // type Byte is mod 2**8;
+    Modular {
+        modulus: u128,
+    },
+    Enumeration {
+        literals: Vec<String>,
+    },
+    // type Shape(kind: ShapeKind) is record { <fields>, case kind { when Circle => <fields>, ... } }
+    Record {
+        discriminant: Option<FieldDecl>,
+        fields: Vec<FieldDecl>,
+        variants: Vec<Variant>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub choice: String,
+    pub fields: Vec<FieldDecl>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CaseArm {
+    pub choice: Option<String>, // `None` for `when others`.
+    pub body: Vec<Statement>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldDecl {
+    pub name: String,
+    pub field_type: TypeName,
 }
 
 // ADANs Function Declaration
@@ -85,6 +229,14 @@ pub enum Statement {
 #[derive(Debug, Clone)]
 pub struct FunctionDecl {
     pub name: String,
-    pub params: Vec<String>, // Params or Arguments
+    pub params: Vec<Param>, // Params or Arguments
+    pub return_type: Option<TypeName>, // `: T` after the parameters, f64 when left out.
     pub body: Vec<Statement>,
 }
+
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub name: String,
+    pub param_type: TypeName,
+    pub by_reference: bool, // `in out` parameters alias the caller's variable.
+}
@@ -3,18 +3,29 @@ use crate::parser::ast::*;
 
 pub struct Parser {
     tokens: Vec<Token>,
+    spans: Vec<Span>,
     pos: usize,
+    no_aggregates: bool, // Set while a `{` can only open a block, as after `for x in a`.
 }
 
 impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, pos: 0 }
+    pub fn new(tokens: Vec<(Token, Span)>) -> Self {
+        let (tokens, spans) = tokens.into_iter().unzip();
+        Self { tokens, spans, pos: 0, no_aggregates: false }
     }
 
     fn peek(&self) -> Option<&Token> {
         self.tokens.get(self.pos)
     }
 
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + offset)
+    }
+
+    fn current_span(&self) -> Span {
+        self.spans.get(self.pos).or(self.spans.last()).copied().unwrap_or_default()
+    }
+
     fn next(&mut self) -> Option<&Token> {
         if self.pos < self.tokens.len() {
             let tok = &self.tokens[self.pos];
@@ -101,6 +112,9 @@ impl Parser {
         if self.match_keyword(Keyword::Include) {
             return self.parse_include();
         }
+        if self.match_keyword(Keyword::Pragma) {
+            return self.parse_pragma();
+        }
         if self.match_keyword(Keyword::Local) || self.match_keyword(Keyword::Global) {
             return self.parse_var_decl();
         }
@@ -110,12 +124,27 @@ impl Parser {
         if self.match_keyword(Keyword::If) {
             return self.parse_if_statement();
         }
+        if self.match_keyword(Keyword::Case) {
+            return self.parse_case_statement();
+        }
+        if self.match_keyword(Keyword::For) {
+            return self.parse_for_loop();
+        }
         if self.match_keyword(Keyword::Program) {
             return self.parse_functions();
         }
         if self.match_keyword(Keyword::Return) {
             return self.parse_return();
         }
+        if self.match_keyword(Keyword::Type) {
+            return self.parse_type_decl(false);
+        }
+        if self.match_keyword(Keyword::Subtype) {
+            return self.parse_type_decl(true);
+        }
+        if self.assignment_ahead() {
+            return self.parse_assignment();
+        }
         if self.match_symbol(Symbols::LCurlyBracket) {
             return Ok(Statement::Block(self.parse_block()?));
         }
@@ -143,16 +172,35 @@ impl Parser {
         Ok(Statement::Include(path))
     }
 
+    fn parse_pragma(&mut self) -> Result<Statement, String> {
+        let span = self.current_span();
+        let name = self.expect_ident()?;
+
+        let mut args = Vec::new();
+        if self.match_symbol(Symbols::LParen) {
+            loop {
+                args.push(self.expect_ident()?);
+                if !self.match_symbol(Symbols::Comma) {
+                    break;
+                }
+            }
+            self.expect_symbol(Symbols::RParen)?;
+        }
+        self.expect_symbol(Symbols::SemiColon)?;
+
+        Ok(Statement::Pragma { name, args, span })
+    }
+
     fn parse_return(&mut self) -> Result<Statement, String> {
         //self.expect_keyword(Keyword::Return)?;
-        let value = if !self.match_symbol(Symbols::SemiColon) {
-            Some(self.parse_expr()?)
-        } else {
-            None
-        };
+        let span = self.current_span();
+        if self.match_symbol(Symbols::SemiColon) {
+            return Ok(Statement::Return { value: None, span });
+        }
 
+        let value = Some(self.parse_expr()?);
         self.expect_symbol(Symbols::SemiColon)?;
-        Ok(Statement::Return { value })
+        Ok(Statement::Return { value, span })
     }
 
     fn parse_functions(&mut self) -> Result<Statement, String> {
@@ -164,19 +212,25 @@ impl Parser {
             while !self.match_symbol(Symbols::RParen) {
                 let param_name = self.expect_ident()?;
                 self.expect_symbol(Symbols::Colon)?;
-                if let Some(Token::Types(_ty)) = self.peek().cloned() {
-                    self.next();
-                } else {
-                    return Err("Expected type after ':' in function parameter".to_string());
-                }
+                self.match_keyword(Keyword::In);
+                let by_reference = self.match_keyword(Keyword::Out);
+                let param_type = self.parse_type()
+                    .map_err(|_| "Expected type after ':' in function parameter".to_string())?;
         
                 self.match_symbol(Symbols::Comma);
-                params.push(param_name);
+                params.push(Param { name: param_name, param_type, by_reference });
             }
         }
-        
+
+        // program -> area(r: f32): f32 { ... }
+        let return_type = if self.match_symbol(Symbols::Colon) {
+            Some(self.parse_type().map_err(|_| "Expected return type after ':'".to_string())?)
+        } else {
+            None
+        };
+
         let body = self.parse_block()?;
-        Ok(Statement::Function(FunctionDecl { name, params, body }))
+        Ok(Statement::Function(FunctionDecl { name, params, return_type, body }))
     }
 
     fn parse_while_loops(&mut self) -> Result<Statement, String> {
@@ -221,6 +275,53 @@ impl Parser {
         Ok(Statement::If { condition, then_branch, else_branch })
     }
 
+    // for <var> in <low>..<high> { ... }
+    // for <var> in <array>'Range { ... }
+    // for <var> in <array> { ... }
+    fn parse_for_loop(&mut self) -> Result<Statement, String> {
+        let span = self.current_span();
+        let var = self.expect_ident()?;
+        self.expect_keyword(Keyword::In)?;
+
+        self.no_aggregates = true;
+        let first = self.parse_expr();
+        let iteration = match first {
+            Ok(low) if self.match_symbol(Symbols::DoublePeriod) => self.parse_expr().map(|high| Iteration::Range { low, high }),
+            Ok(Expr::Attribute { prefix, attribute, span }) if attribute == "Range" => Ok(Iteration::Range {
+                low: Expr::Attribute { prefix: prefix.clone(), attribute: "First".to_string(), span },
+                high: Expr::Attribute { prefix, attribute: "Last".to_string(), span },
+            }),
+            Ok(other) => Ok(Iteration::Elements(other)),
+            Err(e) => Err(e),
+        };
+        self.no_aggregates = false;
+
+        let iteration = iteration?;
+        let body = self.parse_block()?;
+        Ok(Statement::For { var, iteration, body, span })
+    }
+
+    // case <subject> { when <choice> => { ... } when others => { ... } }
+    fn parse_case_statement(&mut self) -> Result<Statement, String> {
+        let subject = self.parse_expr()?;
+        self.expect_symbol(Symbols::LCurlyBracket)?;
+
+        let mut arms = Vec::new();
+        while !self.match_symbol(Symbols::RCurlyBracket) {
+            self.expect_keyword(Keyword::When)?;
+            let choice = if self.match_keyword(Keyword::Others) {
+                None
+            } else {
+                Some(self.expect_ident()?)
+            };
+            self.expect_symbol(Symbols::Arrow)?;
+
+            arms.push(CaseArm { choice, body: self.parse_block()? });
+        }
+
+        Ok(Statement::Case { subject, arms })
+    }
+
     fn parse_block(&mut self) -> Result<Vec<Statement>, String> {
         self.expect_symbol(Symbols::LCurlyBracket)?;
         
@@ -233,11 +334,11 @@ impl Parser {
     }
 
     fn parse_var_decl(&mut self) -> Result<Statement, String> {
+        let span = self.current_span();
         let name = self.expect_ident()?;
         self.expect_symbol(Symbols::Colon)?;
-        let var_type = if let Some(Token::Types(t)) = self.peek().cloned() {
-            self.next();
-            Some(t)
+        let var_type = if let Some(Token::Types(_) | Token::Ident(_) | Token::Keyword(Keyword::Array)) = self.peek() {
+            Some(self.parse_type()?)
         } else {
             None
         };
@@ -249,7 +350,242 @@ impl Parser {
         };
 
         self.expect_symbol(Symbols::SemiColon)?;
-        Ok(Statement::VarDecl { name, var_type, initializer })
+        Ok(Statement::VarDecl { name, var_type, initializer, span })
+    }
+
+    // <name>[.<field>]*[\[<index>\]]* -> <value>;
+    fn assignment_ahead(&self) -> bool {
+        if !matches!(self.peek(), Some(Token::Ident(_))) {
+            return false;
+        }
+
+        let mut offset = 1;
+        loop {
+            match self.peek_at(offset) {
+                Some(Token::Keyword(Keyword::Assign)) => return true,
+                Some(Token::Symbols(Symbols::Period)) if matches!(self.peek_at(offset + 1), Some(Token::Ident(_))) => offset += 2,
+                Some(Token::Symbols(Symbols::LBracket)) => {
+                    let mut depth = 0;
+                    loop {
+                        match self.peek_at(offset) {
+                            Some(Token::Symbols(Symbols::LBracket)) => depth += 1,
+                            Some(Token::Symbols(Symbols::RBracket)) => depth -= 1,
+                            None => return false,
+                            _ => {}
+                        }
+                        offset += 1;
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                }
+                _ => return false,
+            }
+        }
+    }
+
+    fn parse_assignment(&mut self) -> Result<Statement, String> {
+        let span = self.current_span();
+        let mut name = self.expect_ident()?;
+        while self.match_symbol(Symbols::Period) {
+            name = format!("{}.{}", name, self.expect_ident()?);
+        }
+        let target = self.parse_postfix(Expr::Variable { var_name: name, var_type: None, span })?;
+        self.expect_keyword(Keyword::Assign)?;
+
+        let value = self.parse_expr()?;
+        self.expect_symbol(Symbols::SemiColon)?;
+        Ok(Statement::Expression(Expr::Assign { target: Box::new(target), value: Box::new(value), span }))
+    }
+
+    fn parse_type(&mut self) -> Result<TypeName, String> {
+        match self.next().cloned() {
+            // Array<T, N>
+            Some(Token::Types(Types::Array)) => {
+                self.expect_symbol(Symbols::Lesser)?;
+                let element = self.parse_type()?;
+                self.expect_symbol(Symbols::Comma)?;
+                let length = self.parse_int_bound()?;
+                self.expect_symbol(Symbols::Greater)?;
+                if length < 0 {
+                    return Err(format!("Array length must not be negative, got {}", length));
+                }
+
+                Ok(TypeName::Array { element: Box::new(element), first: 0, last: length - 1 })
+            }
+            // Vector<T>
+            Some(Token::Types(Types::Vector)) => {
+                self.expect_symbol(Symbols::Lesser)?;
+                let element = self.parse_type()?;
+                self.expect_symbol(Symbols::Greater)?;
+                Ok(TypeName::Vector(Box::new(element)))
+            }
+            // Map<K, V>
+            Some(Token::Types(Types::Map)) => {
+                self.expect_symbol(Symbols::Lesser)?;
+                let key = self.parse_type()?;
+                self.expect_symbol(Symbols::Comma)?;
+                let value = self.parse_type()?;
+                self.expect_symbol(Symbols::Greater)?;
+                Ok(TypeName::Map { key: Box::new(key), value: Box::new(value) })
+            }
+            // array (<first>..<last>) of T
+            Some(Token::Keyword(Keyword::Array)) => {
+                self.expect_symbol(Symbols::LParen)?;
+                let first = self.parse_int_bound()?;
+                self.expect_symbol(Symbols::DoublePeriod)?;
+                let last = self.parse_int_bound()?;
+                self.expect_symbol(Symbols::RParen)?;
+                self.expect_keyword(Keyword::Of)?;
+
+                let element = self.parse_type()?;
+                Ok(TypeName::Array { element: Box::new(element), first, last })
+            }
+            Some(Token::Types(t)) => Ok(TypeName::Builtin(t)),
+            Some(Token::Ident(name)) => Ok(TypeName::Named(name)),
+            other => Err(format!("Expected type, got {:?}", other)),
+        }
+    }
+
+    // type <name> is range <low>..<high>;
+    // type <name> is (<literal>, ...);
+    // type <name>[(<discriminant>: <type>)] is record { <field>: <type>, ... }
+    // subtype <name> is <base> [range <low>..<high>];
+    fn parse_type_decl(&mut self, is_subtype: bool) -> Result<Statement, String> {
+        let name = self.expect_ident()?;
+        let discriminant = if !is_subtype && self.match_symbol(Symbols::LParen) {
+            let disc_name = self.expect_ident()?;
+            self.expect_symbol(Symbols::Colon)?;
+            let field_type = self.parse_type()?;
+            self.expect_symbol(Symbols::RParen)?;
+            Some(FieldDecl { name: disc_name, field_type })
+        } else {
+            None
+        };
+        self.expect_keyword(Keyword::Is)?;
+
+        if !is_subtype && self.match_keyword(Keyword::Record) {
+            let (fields, variants) = self.parse_record_body(discriminant.as_ref())?;
+            self.match_symbol(Symbols::SemiColon);
+            return Ok(Statement::TypeDecl { name, definition: TypeDefinition::Record { discriminant, fields, variants } });
+        }
+        if discriminant.is_some() {
+            return Err(format!("Only record types can have a discriminant ('{}')", name));
+        }
+
+        if !is_subtype && self.match_symbol(Symbols::LParen) {
+            let mut literals = vec![self.expect_ident()?];
+            while !self.match_symbol(Symbols::RParen) {
+                self.expect_symbol(Symbols::Comma)?;
+                literals.push(self.expect_ident()?);
+            }
+
+            self.expect_symbol(Symbols::SemiColon)?;
+            return Ok(Statement::TypeDecl { name, definition: TypeDefinition::Enumeration { literals } });
+        }
+
+        if !is_subtype && self.match_keyword(Keyword::Mod) {
+            let modulus = self.parse_modulus()?;
+            self.expect_symbol(Symbols::SemiColon)?;
+            return Ok(Statement::TypeDecl { name, definition: TypeDefinition::Modular { modulus } });
+        }
+
+        let (base, has_range) = if is_subtype {
+            let base = self.parse_type()?;
+            (Some(base), self.match_keyword(Keyword::Range))
+        } else {
+            self.expect_keyword(Keyword::Range)?;
+            (None, true)
+        };
+
+        let bounds = if has_range {
+            let low = self.parse_int_bound()?;
+            self.expect_symbol(Symbols::DoublePeriod)?;
+            let high = self.parse_int_bound()?;
+            if low > high {
+                return Err(format!("Empty range {}..{} in declaration of '{}'", low, high, name));
+            }
+            Some((low, high))
+        } else {
+            None
+        };
+
+        self.expect_symbol(Symbols::SemiColon)?;
+        Ok(Statement::TypeDecl { name, definition: TypeDefinition::Range { base, bounds } })
+    }
+
+    // { x: f64, y: f64 } or { x, y: f64, case <discriminant> { when <choice> => <fields>, ... } }
+    fn parse_record_body(&mut self, discriminant: Option<&FieldDecl>) -> Result<(Vec<FieldDecl>, Vec<Variant>), String> {
+        self.expect_symbol(Symbols::LCurlyBracket)?;
+
+        let mut fields = Vec::new();
+        let mut variants = Vec::new();
+        while !self.match_symbol(Symbols::RCurlyBracket) {
+            if !self.match_keyword(Keyword::Case) {
+                fields.extend(self.parse_field_group()?);
+                continue;
+            }
+
+            let selector = self.expect_ident()?;
+            if discriminant.map(|d| &d.name) != Some(&selector) {
+                return Err(format!("Variant part must select on the record discriminant, got '{}'", selector));
+            }
+
+            self.expect_symbol(Symbols::LCurlyBracket)?;
+            while !self.match_symbol(Symbols::RCurlyBracket) {
+                self.expect_keyword(Keyword::When)?;
+                let choice = self.expect_ident()?;
+                self.expect_symbol(Symbols::Arrow)?;
+
+                let mut variant_fields = Vec::new();
+                while !matches!(self.peek(), Some(Token::Keyword(Keyword::When) | Token::Symbols(Symbols::RCurlyBracket))) {
+                    variant_fields.extend(self.parse_field_group()?);
+                }
+                variants.push(Variant { choice, fields: variant_fields });
+            }
+            self.match_symbol(Symbols::Comma);
+        }
+
+        Ok((fields, variants))
+    }
+
+    // x: f64 or w, h: f64, followed by an optional comma.
+    fn parse_field_group(&mut self) -> Result<Vec<FieldDecl>, String> {
+        let mut names = vec![self.expect_ident()?];
+        while !self.match_symbol(Symbols::Colon) {
+            self.expect_symbol(Symbols::Comma)?;
+            names.push(self.expect_ident()?);
+        }
+
+        let field_type = self.parse_type()?;
+        self.match_symbol(Symbols::Comma);
+        Ok(names.into_iter().map(|name| FieldDecl { name, field_type: field_type.clone() }).collect())
+    }
+
+    fn parse_int_bound(&mut self) -> Result<i64, String> {
+        let negative = self.match_symbol(Symbols::Sub);
+        match self.next() {
+            Some(Token::Number(n)) => Ok(signed_integer(n, negative, (i64::MIN as i128, i64::MAX as i128), "range bound")? as i64),
+            other => Err(format!("Expected integer range bound, got {:?}", other)),
+        }
+    }
+
+    // `256` or `2**8`.
+    fn parse_modulus(&mut self) -> Result<u128, String> {
+        let base = match self.next() {
+            Some(Token::Number(n)) => n.parse::<u128>().map_err(|e| format!("Invalid modulus '{}': {}", n, e))?,
+            other => return Err(format!("Expected a modulus, got {:?}", other)),
+        };
+        if !self.match_symbol(Symbols::Mul) {
+            return Ok(base);
+        }
+        self.expect_symbol(Symbols::Mul)?;
+
+        let exponent = match self.next() {
+            Some(Token::Number(n)) => n.parse::<u32>().map_err(|e| format!("Invalid exponent '{}': {}", n, e))?,
+            other => return Err(format!("Expected an exponent, got {:?}", other)),
+        };
+        base.checked_pow(exponent).ok_or_else(|| format!("Modulus {}**{} is too large", base, exponent))
     }
 
     // PEMDAS RULING
@@ -258,11 +594,17 @@ impl Parser {
 
     fn parse_equality(&mut self) -> Result<Expr, String> {
         let mut left = self.parse_comparisons()?;
-        while let Some(Token::Symbols(Symbols::Equal)) = self.peek() {
+        while let Some(tok) = self.peek() {
+            let op = match tok {
+                Token::Symbols(Symbols::Equal) => Operation::Equal,
+                Token::Symbols(Symbols::NotEqual) => Operation::NotEqual,
+                _ => break,
+            };
+            let span = self.current_span();
             self.next();
             
             let right = self.parse_comparisons()?;
-            left = Expr::Binary { left: Box::new(left), op: Operation::Equal, right: Box::new(right) };
+            left = Expr::Binary { left: Box::new(left), op, right: Box::new(right), span };
         }
 
         Ok(left)
@@ -278,10 +620,11 @@ impl Parser {
                 Token::Symbols(Symbols::Lequal) => Operation::Lequal,
                 _ => break,
             };
+            let span = self.current_span();
             self.next();
 
             let right = self.parse_add_sub()?;
-            left = Expr::Binary { left: Box::new(left), op, right: Box::new(right) };
+            left = Expr::Binary { left: Box::new(left), op, right: Box::new(right), span };
         }
 
         Ok(left)
@@ -293,12 +636,14 @@ impl Parser {
             let op = match tok {
                 Token::Symbols(Symbols::Add) => Operation::Add,
                 Token::Symbols(Symbols::Sub) => Operation::Subtract,
+                Token::Symbols(Symbols::Ampersand) => Operation::Concat,
                 _ => break,
             };
+            let span = self.current_span();
             self.next();
 
             let right = self.parse_mul_div_mod()?;
-            left = Expr::Binary { left: Box::new(left), op, right: Box::new(right) };
+            left = Expr::Binary { left: Box::new(left), op, right: Box::new(right), span };
         }
 
         Ok(left)
@@ -313,10 +658,11 @@ impl Parser {
                 Token::Symbols(Symbols::Mod) => Operation::Modulo,
                 _ => break,
             };
+            let span = self.current_span();
             self.next();
 
             let right = self.parse_unary()?;
-            left = Expr::Binary { left: Box::new(left), op, right: Box::new(right) };
+            left = Expr::Binary { left: Box::new(left), op, right: Box::new(right), span };
         }
 
         Ok(left)
@@ -326,14 +672,23 @@ impl Parser {
         let next_token = self.peek();
         match next_token {
             Some(Token::Symbols(Symbols::Sub)) => {
+                let span = self.current_span();
                 self.next();
+                // `-9223372036854775808` is a literal, its magnitude alone doesn't fit in an i64.
+                if let Some(Token::Number(n)) = self.peek().cloned() {
+                    if !n.contains(['.', 'e']) {
+                        self.next();
+                        return Ok(Expr::Literal(Literal::Integer(signed_integer(&n, true, INTEGER_LITERALS, "integer literal")?)));
+                    }
+                }
                 let right = self.parse_unary()?;
-                Ok(Expr::Unary { op: Operation::Negate, right: Box::new(right) })
+                Ok(Expr::Unary { op: Operation::Negate, right: Box::new(right), span })
             }
             Some(Token::Symbols(Symbols::Not)) => {
+                let span = self.current_span();
                 self.next();
                 let right = self.parse_unary()?;
-                Ok(Expr::Unary { op: Operation::Not, right: Box::new(right) })
+                Ok(Expr::Unary { op: Operation::Not, right: Box::new(right), span })
             }
             _ => self.parse_primary(),
         }
@@ -342,19 +697,96 @@ impl Parser {
     fn parse_primary(&mut self) -> Result<Expr, String> {
         match self.peek().cloned() {
             Some(Token::Number(n)) => {
-                let value = n.parse::<f64>().unwrap_or(0.0);
                 self.next();
-                Ok(Expr::Literal(Literal::Number(value)))
+                if n.contains(['.', 'e']) {
+                    let value = n.parse::<f64>().map_err(|e| format!("Invalid real literal '{}': {}", n, e))?;
+                    return Ok(Expr::Literal(Literal::Number(value)));
+                }
+
+                Ok(Expr::Literal(Literal::Integer(signed_integer(&n, false, INTEGER_LITERALS, "integer literal")?)))
+            }
+            Some(Token::Types(t)) if matches!(self.peek_at(1), Some(Token::Symbols(Symbols::LParen))) => { // i32(<value>)
+                let span = self.current_span();
+                self.next();
+                self.next();
+                let value = self.parse_expr()?;
+                self.expect_symbol(Symbols::RParen)?;
+                self.parse_postfix(Expr::Conversion { target: TypeName::Builtin(t), value: Box::new(value), span })
+            }
+            Some(Token::Types(t)) if matches!(self.peek_at(1), Some(Token::Symbols(Symbols::SingleQuote))) => { // Char'Pos(<value>)
+                let span = self.current_span();
+                self.next();
+                self.next();
+                let attribute = self.expect_ident()?;
+                self.expect_symbol(Symbols::LParen)?;
+                let args = self.parse_call_args()?;
+                self.parse_postfix(Expr::AttributeCall { prefix: TypeName::Builtin(t), attribute, args, span })
             }
             Some(Token::Literal(s)) => {
                 self.next();
                 Ok(Expr::Literal(Literal::String(s)))
             }
+            Some(Token::Interpolated(fragments)) => { // "x = {x}"
+                let span = self.current_span();
+                self.next();
+                let mut parts = Vec::new();
+                for fragment in fragments {
+                    match fragment {
+                        Fragment::Text(text) => parts.push(Interpolation::Text(text)),
+                        Fragment::Code(tokens) if tokens.is_empty() => return Err("Empty {} in string literal".to_string()),
+                        Fragment::Code(tokens) => {
+                            let mut inner = Parser::new(tokens);
+                            let value = inner.parse_expr()?;
+                            if let Some(extra) = inner.peek() {
+                                return Err(format!("Unexpected token in string interpolation: {:?}", extra));
+                            }
+                            parts.push(Interpolation::Value(value));
+                        }
+                    }
+                }
+
+                Ok(Expr::Interpolation { parts, span })
+            }
             Some(Token::CharLiteral(c)) => {
                 self.next();
                 Ok(Expr::Literal(Literal::Char(c)))
             }
+            Some(Token::Symbols(Symbols::LBracket)) => { // [<element>, ...]
+                let span = self.current_span();
+                self.next();
+                let mut elements = Vec::new();
+                if !self.match_symbol(Symbols::RBracket) {
+                    loop {
+                        elements.push(self.parse_expr()?);
+                        if self.match_symbol(Symbols::RBracket) {
+                            break;
+                        }
+
+                        self.expect_symbol(Symbols::Comma)?;
+                    }
+                }
+
+                Ok(Expr::ArrayLiteral { elements, span })
+            }
+            Some(Token::Symbols(Symbols::LCurlyBracket)) => { // { <key> -> <value>, ... }
+                let span = self.current_span();
+                self.next();
+                let mut entries = Vec::new();
+                while !self.match_symbol(Symbols::RCurlyBracket) {
+                    let key = self.parse_expr()?;
+                    self.expect_keyword(Keyword::Assign)?;
+                    entries.push((key, self.parse_expr()?));
+
+                    if !self.match_symbol(Symbols::Comma) {
+                        self.expect_symbol(Symbols::RCurlyBracket)?;
+                        break;
+                    }
+                }
+
+                Ok(Expr::MapLiteral { entries, span })
+            }
             Some(Token::Ident(name)) => {
+                let span = self.current_span();
                 let mut base = name.clone();
                 self.next();
                 while self.match_symbol(Symbols::Period) {
@@ -363,21 +795,12 @@ impl Parser {
                 }
 
                 if self.match_symbol(Symbols::LParen) { // (
-                    let mut args = Vec::new();
-                    if !self.match_symbol(Symbols::RParen) {
-                        loop {
-                            args.push(self.parse_expr()?);
-                            if self.match_symbol(Symbols::RParen) {
-                                break;
-                            }
-
-                            self.expect_symbol(Symbols::Comma)?; // (<param_1>, <param_2>, ...)
-                        }
-                    }
-
-                    Ok(Expr::FCall { callee: base, args })
+                    let args = self.parse_call_args()?;
+                    Ok(Expr::FCall { callee: base, args, span })
+                } else if self.aggregate_ahead() {
+                    self.parse_aggregate(base, span)
                 } else {
-                    Ok(Expr::Variable { var_name: base, var_type: None }) // `if (<var>) {}` instead of `if (<var>: String) {}`
+                    self.parse_postfix(Expr::Variable { var_name: base, var_type: None, span }) // `if (<var>) {}` instead of `if (<var>: String) {}`
                 }
             }
             Some(Token::Symbols(Symbols::LParen)) => { // Opening part of the if statement
@@ -390,6 +813,79 @@ impl Parser {
         }
     }
 
+    // <expr>[<index>] and <expr>'<Attribute>, in any order.
+    fn parse_postfix(&mut self, mut expr: Expr) -> Result<Expr, String> {
+        loop {
+            let span = self.current_span();
+            if self.match_symbol(Symbols::LBracket) {
+                let index = self.parse_expr()?;
+                if self.match_symbol(Symbols::DoublePeriod) {
+                    let high = self.parse_expr()?;
+                    self.expect_symbol(Symbols::RBracket)?;
+                    expr = Expr::Slice { base: Box::new(expr), low: Box::new(index), high: Box::new(high), span };
+                    continue;
+                }
+                self.expect_symbol(Symbols::RBracket)?;
+                expr = Expr::Index { base: Box::new(expr), index: Box::new(index), span };
+            } else if self.match_symbol(Symbols::SingleQuote) {
+                let attribute = self.expect_ident()?;
+                // Only a type name can take arguments, as in Color'Val(2).
+                if let Expr::Variable { var_name, .. } = &expr {
+                    if self.match_symbol(Symbols::LParen) {
+                        let prefix = TypeName::Named(var_name.clone());
+                        expr = Expr::AttributeCall { prefix, attribute, args: self.parse_call_args()?, span };
+                        continue;
+                    }
+                }
+                expr = Expr::Attribute { prefix: Box::new(expr), attribute, span };
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    // (<param_1>, <param_2>, ...), after the opening parenthesis.
+    fn parse_call_args(&mut self) -> Result<Vec<Expr>, String> {
+        let mut args = Vec::new();
+        if !self.match_symbol(Symbols::RParen) {
+            loop {
+                args.push(self.parse_expr()?);
+                if self.match_symbol(Symbols::RParen) {
+                    break;
+                }
+
+                self.expect_symbol(Symbols::Comma)?;
+            }
+        }
+        Ok(args)
+    }
+
+    // <type> { <field> -> <value>, ... }
+    fn aggregate_ahead(&self) -> bool {
+        !self.no_aggregates && matches!(
+            (self.peek(), self.peek_at(1), self.peek_at(2)),
+            (Some(Token::Symbols(Symbols::LCurlyBracket)), Some(Token::Ident(_)), Some(Token::Keyword(Keyword::Assign)))
+        )
+    }
+
+    fn parse_aggregate(&mut self, type_name: String, span: Span) -> Result<Expr, String> {
+        self.expect_symbol(Symbols::LCurlyBracket)?;
+
+        let mut fields = Vec::new();
+        while !self.match_symbol(Symbols::RCurlyBracket) {
+            let field = self.expect_ident()?;
+            self.expect_keyword(Keyword::Assign)?;
+            fields.push((field, self.parse_expr()?));
+
+            if !self.match_symbol(Symbols::Comma) {
+                self.expect_symbol(Symbols::RCurlyBracket)?;
+                break;
+            }
+        }
+
+        Ok(Expr::Aggregate { type_name, fields, span })
+    }
+
     // ------------------------
     // Expressions
     // ------------------------
@@ -402,3 +898,195 @@ impl Parser {
         self.parse_unary()
     }
 }
+
+// Integer literals range from i64::MIN to u64::MAX, whichever type they end up in checks the rest.
+const INTEGER_LITERALS: (i128, i128) = (i64::MIN as i128, u64::MAX as i128);
+
+// `n` with its sign applied, which must lie within `low..=high`. The magnitude goes through u64 so
+// that both i64::MIN and values past i64::MAX can be written.
+fn signed_integer(n: &str, negative: bool, (low, high): (i128, i128), what: &str) -> Result<i128, String> {
+    let magnitude = n.parse::<u64>().map_err(|e| format!("Invalid {} '{}': {}", what, n, e))? as i128;
+    let value = if negative { -magnitude } else { magnitude };
+    if value < low || value > high {
+        return Err(format!("The {} {} doesn't fit in {}..{}", what, value, low, high));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lexer::Lexer;
+
+    fn parse(source: &str) -> Result<Vec<Statement>, String> {
+        Parser::new(Lexer::new(source).tokenize()?).parse()
+    }
+
+    fn parse_one(source: &str) -> Statement {
+        let mut statements = parse(source).unwrap_or_else(|e| panic!("expected '{}' to parse, got '{}'", source, e));
+        assert_eq!(statements.len(), 1, "expected one statement in '{}'", source);
+        statements.remove(0)
+    }
+
+    #[test]
+    fn range_types_and_subtypes() {
+        assert!(matches!(parse_one("type Percent is range 0..100;"),
+            Statement::TypeDecl { definition: TypeDefinition::Range { base: None, bounds: Some((0, 100)) }, .. }));
+        assert!(matches!(parse_one("subtype Index is i32 range -1..10;"),
+            Statement::TypeDecl { definition: TypeDefinition::Range { base: Some(TypeName::Builtin(Types::i32)), bounds: Some((-1, 10)) }, .. }));
+        assert!(matches!(parse_one("subtype Count is i64;"),
+            Statement::TypeDecl { definition: TypeDefinition::Range { base: Some(_), bounds: None }, .. }));
+    }
+
+    #[test]
+    fn empty_and_unbounded_ranges_are_rejected() {
+        assert!(parse("type Backwards is range 10..1;").unwrap_err().starts_with("Empty range 10..1"));
+        assert!(parse("type Open is range 0..;").is_err());
+        assert!(parse("type Real is range 0.5..1;").is_err());
+    }
+
+    #[test]
+    fn pragmas_take_a_list_of_names() {
+        let Statement::Pragma { name, args, .. } = parse_one("pragma Suppress(Overflow_Check, Range_Check);") else {
+            panic!("expected a pragma");
+        };
+        assert_eq!(name, "Suppress");
+        assert_eq!(args, ["Overflow_Check", "Range_Check"]);
+        assert!(parse("pragma Suppress(Overflow_Check Range_Check);").is_err());
+    }
+
+    #[test]
+    fn records_group_fields_that_share_a_type() {
+        let Statement::TypeDecl { definition: TypeDefinition::Record { discriminant: None, fields, variants }, .. } =
+            parse_one("type Point is record { x, y: f64, label: String }") else {
+            panic!("expected a record");
+        };
+        let names: Vec<&str> = fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["x", "y", "label"]);
+        assert_eq!(fields[1].field_type, TypeName::Builtin(Types::f64));
+        assert!(variants.is_empty());
+        assert!(parse("type Point is record { x y: f64 }").is_err());
+    }
+
+    #[test]
+    fn aggregates_and_in_out_parameters() {
+        let Statement::VarDecl { initializer: Some(Expr::Aggregate { type_name, fields, .. }), .. } =
+            parse_one("local p: Point -> Point { x -> 1.0, y -> 2.0 };") else {
+            panic!("expected an aggregate");
+        };
+        assert_eq!(type_name, "Point");
+        assert_eq!(fields.len(), 2);
+        assert!(parse("local p: Point -> Point { x -> 1.0 y -> 2.0 };").is_err());
+
+        let Statement::Function(function) = parse_one("program -> shift(p: in out Point, by: in f64) { }") else {
+            panic!("expected a function");
+        };
+        assert!(function.params[0].by_reference);
+        assert!(!function.params[1].by_reference);
+    }
+
+    #[test]
+    fn variant_parts_select_on_the_discriminant() {
+        let source = "type Shape(kind: Kind) is record { name: String, case kind { when Circle => radius: f64 when Rect => w, h: f64 } }";
+        let Statement::TypeDecl { definition: TypeDefinition::Record { discriminant: Some(discriminant), fields, variants }, .. } = parse_one(source) else {
+            panic!("expected a discriminated record");
+        };
+        assert_eq!(discriminant.name, "kind");
+        assert_eq!(fields.len(), 1);
+        assert_eq!(variants.iter().map(|v| (v.choice.as_str(), v.fields.len())).collect::<Vec<_>>(), [("Circle", 1), ("Rect", 2)]);
+
+        let error = parse("type Shape(kind: Kind) is record { case other { when Circle => radius: f64 } }").unwrap_err();
+        assert!(error.starts_with("Variant part must select on the record discriminant, got 'other'"), "{}", error);
+        let error = parse("type Size(kind: Kind) is range 0..10;").unwrap_err();
+        assert!(error.starts_with("Only record types can have a discriminant ('Size')"), "{}", error);
+    }
+
+    #[test]
+    fn array_types_slices_and_ranges() {
+        let Statement::VarDecl { var_type: Some(generic), .. } = parse_one("local a: Array<i32, 3>;") else {
+            panic!("expected a declaration");
+        };
+        assert_eq!(generic, TypeName::Array { element: Box::new(TypeName::Builtin(Types::i32)), first: 0, last: 2 });
+        let Statement::VarDecl { var_type: Some(ada), .. } = parse_one("local a: array (1..10) of f64;") else {
+            panic!("expected a declaration");
+        };
+        assert_eq!(ada, TypeName::Array { element: Box::new(TypeName::Builtin(Types::f64)), first: 1, last: 10 });
+        assert!(parse("local a: Array<i32, -1>;").unwrap_err().starts_with("Array length must not be negative"));
+        assert!(parse("local a: Array<i32>;").is_err());
+
+        assert!(matches!(parse_one("local b: -> a[1..2];"), Statement::VarDecl { initializer: Some(Expr::Slice { .. }), .. }));
+        let Statement::For { iteration: Iteration::Range { low, high }, .. } = parse_one("for i in a'Range { }") else {
+            panic!("expected a range loop");
+        };
+        assert!(matches!(low, Expr::Attribute { attribute, .. } if attribute == "First"));
+        assert!(matches!(high, Expr::Attribute { attribute, .. } if attribute == "Last"));
+        assert!(matches!(parse_one("for x in a { }"), Statement::For { iteration: Iteration::Elements(_), .. }));
+    }
+
+    #[test]
+    fn operators_bind_by_precedence() {
+        let Statement::Expression(Expr::Binary { op: Operation::Add, right, .. }) = parse_one("a + b * c % d;") else {
+            panic!("expected an addition");
+        };
+        assert!(matches!(*right, Expr::Binary { op: Operation::Modulo, .. }));
+        let Statement::Expression(Expr::Binary { op: Operation::Divide, left, .. }) = parse_one("-a / b;") else {
+            panic!("expected a division");
+        };
+        assert!(matches!(*left, Expr::Unary { op: Operation::Negate, .. }));
+        assert!(parse("a + ;").is_err());
+    }
+
+    #[test]
+    fn moduli_are_numbers_or_powers() {
+        assert!(matches!(parse_one("type Byte is mod 2**8;"), Statement::TypeDecl { definition: TypeDefinition::Modular { modulus: 256 }, .. }));
+        assert!(matches!(parse_one("type Hour is mod 24;"), Statement::TypeDecl { definition: TypeDefinition::Modular { modulus: 24 }, .. }));
+        assert!(parse("type Byte is mod 2*8;").is_err());
+        assert!(parse("type Huge is mod 2**200;").unwrap_err().starts_with("Modulus 2**200 is too large"));
+    }
+
+    #[test]
+    fn literals_and_conversions() {
+        assert!(matches!(parse_one("local x: -> 5;"), Statement::VarDecl { initializer: Some(Expr::Literal(Literal::Integer(5))), .. }));
+        assert!(matches!(parse_one("local x: -> 5.0;"), Statement::VarDecl { initializer: Some(Expr::Literal(Literal::Number(_))), .. }));
+        assert!(matches!(parse_one("local x: -> 1e3;"), Statement::VarDecl { initializer: Some(Expr::Literal(Literal::Number(n))), .. } if n == 1000.0));
+        let Statement::VarDecl { initializer: Some(Expr::Conversion { target, .. }), .. } = parse_one("local x: -> u8(y + 1);") else {
+            panic!("expected a conversion");
+        };
+        assert_eq!(target, TypeName::Builtin(Types::u8));
+        assert!(parse("local x: -> u8(y;").is_err());
+    }
+
+    #[test]
+    fn integer_literals_span_i64_min_to_u64_max() {
+        assert!(matches!(parse_one("local x: -> 18446744073709551615;"), Statement::VarDecl { initializer: Some(Expr::Literal(Literal::Integer(n))), .. } if n == u64::MAX as i128));
+        assert!(matches!(parse_one("local x: -> -9223372036854775808;"), Statement::VarDecl { initializer: Some(Expr::Literal(Literal::Integer(n))), .. } if n == i64::MIN as i128));
+        assert!(parse("local x: -> 18446744073709551616;").unwrap_err().starts_with("Invalid integer literal"));
+        assert!(parse("local x: -> -9223372036854775809;").unwrap_err().contains("doesn't fit"));
+        assert!(matches!(parse_one("type T is range -9223372036854775808..0;"),
+            Statement::TypeDecl { definition: TypeDefinition::Range { bounds: Some((i64::MIN, 0)), .. }, .. }));
+        assert!(parse("type T is range 0..9223372036854775808;").unwrap_err().contains("range bound 9223372036854775808 doesn't fit"));
+    }
+
+    #[test]
+    fn attributes_of_types_take_arguments() {
+        let Statement::VarDecl { initializer: Some(Expr::AttributeCall { prefix, attribute, args, .. }), .. } = parse_one("local n: -> Char'Pos('a');") else {
+            panic!("expected an attribute call");
+        };
+        assert_eq!((prefix, attribute.as_str(), args.len()), (TypeName::Builtin(Types::Char), "Pos", 1));
+        let Statement::VarDecl { initializer: Some(Expr::AttributeCall { prefix, attribute, .. }), .. } = parse_one("local c: -> Color'Val(2);") else {
+            panic!("expected an attribute call");
+        };
+        assert_eq!((prefix, attribute.as_str()), (TypeName::Named("Color".to_string()), "Val"));
+        assert!(matches!(parse_one("local n: -> s'Length;"), Statement::VarDecl { initializer: Some(Expr::Attribute { .. }), .. }));
+    }
+
+    #[test]
+    fn not_equal_binds_like_equal() {
+        let Statement::Expression(Expr::Binary { op: Operation::NotEqual, left, right, .. }) = parse_one("a + 1 /= b < c;") else {
+            panic!("expected a not-equal comparison");
+        };
+        assert!(matches!(*left, Expr::Binary { op: Operation::Add, .. }));
+        assert!(matches!(*right, Expr::Binary { op: Operation::Lesser, .. }));
+        assert!(matches!(parse_one("a / b;"), Statement::Expression(Expr::Binary { op: Operation::Divide, .. })));
+    }
+}